@@ -0,0 +1,319 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::{Read, Write},
+  path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use lslib::{
+  compression::{CompressionOptions, Level, Method},
+  file::File,
+  lsv::{file::File as PackageFile, header::Version, package::Package, write::sorted_file_paths},
+};
+use serde::{Deserialize, Serialize};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::error::Error;
+
+// Stored at the root of exported archives to restore the package header and file compression
+const SIDECAR_NAME: &str = ".lspk.json";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Sidecar {
+  version: i32,
+  priority: u8,
+  flags: u8,
+  // Compression flags by file path
+  files: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArchiveFormat {
+  Tar,
+  TarGz,
+  Zip,
+}
+
+impl ArchiveFormat {
+  fn from_path(path: &Path) -> Result<Self, Error> {
+    let name = path.to_string_lossy().to_lowercase();
+    if name.ends_with(".tar") {
+      Ok(Self::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+      Ok(Self::TarGz)
+    } else if name.ends_with(".zip") {
+      Ok(Self::Zip)
+    } else {
+      Err(Error::InvalidArgument(path.to_string_lossy().into_owned()))
+    }
+  }
+}
+
+enum ArchiveWriter {
+  Tar(tar::Builder<fs::File>),
+  TarGz(tar::Builder<GzEncoder<fs::File>>),
+  Zip(ZipWriter<fs::File>),
+}
+
+fn tar_header(size: usize) -> tar::Header {
+  let mut header = tar::Header::new_gnu();
+  header.set_size(size as u64);
+  header.set_mode(0o644);
+  header.set_cksum();
+  header
+}
+
+impl ArchiveWriter {
+  fn create(path: &Path, format: ArchiveFormat) -> Result<Self, Error> {
+    let file = fs::File::create(path)?;
+    Ok(match format {
+      ArchiveFormat::Tar => Self::Tar(tar::Builder::new(file)),
+      ArchiveFormat::TarGz => Self::TarGz(tar::Builder::new(GzEncoder::new(
+        file,
+        Compression::default(),
+      ))),
+      ArchiveFormat::Zip => Self::Zip(ZipWriter::new(file)),
+    })
+  }
+
+  fn append(&mut self, name: &str, contents: &[u8]) -> Result<(), Error> {
+    match self {
+      Self::Tar(builder) => builder.append_data(&mut tar_header(contents.len()), name, contents)?,
+      Self::TarGz(builder) => {
+        builder.append_data(&mut tar_header(contents.len()), name, contents)?
+      }
+      Self::Zip(writer) => {
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file(name, options)?;
+        writer.write_all(contents)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn finish(self) -> Result<(), Error> {
+    match self {
+      Self::Tar(builder) => {
+        builder.into_inner()?.flush()?;
+      }
+      Self::TarGz(builder) => {
+        builder.into_inner()?.finish()?.flush()?;
+      }
+      Self::Zip(mut writer) => {
+        writer.finish()?.flush()?;
+      }
+    }
+    Ok(())
+  }
+}
+
+// Reads all regular files of an archive as (path, contents)
+fn read_archive(path: &Path, format: ArchiveFormat) -> Result<Vec<(String, Vec<u8>)>, Error> {
+  fn read_tar<R: Read>(reader: R) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let mut entries = vec![];
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+      let mut entry = entry?;
+      if !entry.header().entry_type().is_file() {
+        continue;
+      }
+      let name = entry.path()?.to_string_lossy().into_owned();
+      let mut contents = vec![];
+      entry.read_to_end(&mut contents)?;
+      entries.push((name, contents));
+    }
+    Ok(entries)
+  }
+
+  let file = fs::File::open(path)?;
+  match format {
+    ArchiveFormat::Tar => read_tar(file),
+    ArchiveFormat::TarGz => read_tar(GzDecoder::new(file)),
+    ArchiveFormat::Zip => {
+      let mut entries = vec![];
+      let mut archive = ZipArchive::new(file)?;
+      for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_dir() {
+          continue;
+        }
+        let name = entry.name().to_owned();
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents)?;
+        entries.push((name, contents));
+      }
+      Ok(entries)
+    }
+  }
+}
+
+pub async fn cli_lsv_export(path: PathBuf, out: PathBuf) -> Result<(), Error> {
+  let format = ArchiveFormat::from_path(&out)?;
+  let mut handle = File::open(&path)?.as_lsv()?;
+  let header = *handle.header();
+  let file_paths = sorted_file_paths(handle.package());
+
+  let mut writer = ArchiveWriter::create(&out, format)?;
+  let mut files = BTreeMap::new();
+  for file_path in &file_paths {
+    let name = file_path.to_string_lossy().replace('\\', "/");
+    let mut contents = vec![];
+    handle.file_stream(file_path)?.read_to_end(&mut contents)?;
+    writer.append(&name, &contents)?;
+    files.insert(name, handle.file(file_path).unwrap().flags());
+  }
+
+  let sidecar = Sidecar {
+    version: header.version() as i32,
+    priority: header.priority(),
+    flags: header.flags(),
+    files,
+  };
+  writer.append(SIDECAR_NAME, &serde_json::to_vec_pretty(&sidecar)?)?;
+  writer.finish()?;
+  println!(
+    "Exported {} files to {}",
+    file_paths.len(),
+    out.to_string_lossy()
+  );
+  Ok(())
+}
+
+pub async fn cli_lsv_import(archive: PathBuf, out: PathBuf) -> Result<(), Error> {
+  let format = ArchiveFormat::from_path(&archive)?;
+  let mut entries = read_archive(&archive, format)?;
+
+  // Archives created without the sidecar are packed like lsv pack does by default
+  let sidecar = match entries.iter().position(|(name, _)| name == SIDECAR_NAME) {
+    Some(index) => Some(serde_json::from_slice::<Sidecar>(&entries.remove(index).1)?),
+    None => None,
+  };
+
+  let mut package = Package::default();
+  if let Some(sidecar) = &sidecar {
+    // Older versions can be exported, but packages are only written as 13, 15, 16 or 18
+    let version = match sidecar.version {
+      13 | 15 | 16 | 18 => Version::from(sidecar.version),
+      version => {
+        return Err(Error::InvalidArgument(format!(
+          "version {} (only 13, 15, 16 and 18 can be written)",
+          version
+        )))
+      }
+    };
+    let header = package.header_mut();
+    header.set_version(version);
+    header.set_priority(sidecar.priority);
+    header.set_flags(sidecar.flags);
+  }
+
+  let default_flags: u8 = CompressionOptions::new(Method::Lz4, Level::Default).into();
+  let file_count = entries.len();
+  for (name, contents) in entries {
+    let flags = sidecar
+      .as_ref()
+      .and_then(|sidecar| sidecar.files.get(&name).copied())
+      .unwrap_or(default_flags as u32);
+    let mut file = PackageFile::new();
+    file.set_path(PathBuf::from(name));
+    file.set_flags(flags);
+    file.set_contents(Some(contents));
+    package
+      .file_table_mut()
+      .insert(file.path().to_owned(), file);
+  }
+
+  Package::create_file(&out)?.write(&mut package)?;
+  println!(
+    "Imported {} files into {}",
+    file_count,
+    out.to_string_lossy()
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_package(path: &Path, version: Version) {
+    let mut package = Package::default();
+    package.header_mut().set_version(version);
+    package.header_mut().set_priority(7);
+    for (name, method) in [
+      ("Public/Stats.txt", Method::Zlib),
+      ("Mods/meta.lsx", Method::None),
+    ] {
+      let mut file = PackageFile::new();
+      file.set_path(PathBuf::from(name));
+      if method != Method::None {
+        let flags: u8 = CompressionOptions::new(method, Level::Default).into();
+        file.set_flags(flags as u32);
+      }
+      file.set_contents(Some(name.repeat(3).into_bytes()));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    Package::create_file(path)
+      .unwrap()
+      .write(&mut package)
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_export_import() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let path = dir.join("Mod.pak");
+    write_package(&path, Version::V18);
+
+    for name in ["Mod.tar", "Mod.tar.gz", "Mod.zip"] {
+      let archive = dir.join(name);
+      let out = dir.join(format!("{}.pak", name));
+      cli_lsv_export(path.clone(), archive.clone()).await.unwrap();
+      cli_lsv_import(archive, out.clone()).await.unwrap();
+
+      let mut handle = Package::open_file(&out).unwrap();
+      assert_eq!(handle.header().version(), Version::V18);
+      assert_eq!(handle.header().priority(), 7);
+      assert_eq!(handle.files().len(), 2);
+      let stats = Path::new("Public/Stats.txt");
+      assert_eq!(
+        handle.file(stats).unwrap().compression_method(),
+        Method::Zlib
+      );
+      assert_eq!(
+        handle.file_contents(stats).unwrap(),
+        &"Public/Stats.txt".repeat(3).into_bytes()
+      );
+    }
+  }
+
+  #[tokio::test]
+  async fn test_import_unwritable_version() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let archive = dir.join("Mod.tar");
+    let sidecar = Sidecar {
+      version: 10,
+      priority: 0,
+      flags: 0,
+      files: BTreeMap::new(),
+    };
+    let mut writer = ArchiveWriter::create(&archive, ArchiveFormat::Tar).unwrap();
+    writer.append("Stats.txt", b"stats").unwrap();
+    writer
+      .append(SIDECAR_NAME, &serde_json::to_vec(&sidecar).unwrap())
+      .unwrap();
+    writer.finish().unwrap();
+
+    let out = dir.join("Mod.pak");
+    assert!(matches!(
+      cli_lsv_import(archive, out.clone()).await,
+      Err(Error::InvalidArgument(_))
+    ));
+    assert!(!out.exists());
+  }
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use lslib::{
+  file::File,
+  lsv::diff::{DiffOptions, EntryChange},
+};
+
+use crate::error::Error;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+  // Show node and attribute changes of LSF and LSB files
+  #[arg(short, long)]
+  resources: bool,
+}
+
+pub async fn cli_lsv_diff(
+  old_path: PathBuf,
+  new_path: PathBuf,
+  args: DiffArgs,
+) -> Result<(), Error> {
+  let mut old = File::open(&old_path)?.as_lsv()?;
+  let mut new = File::open(&new_path)?.as_lsv()?;
+  let mut options = DiffOptions::new();
+  options.set_compare_resources(args.resources);
+  let diff = old.diff(&mut new, &options)?;
+
+  for entry in diff.entries() {
+    let path = entry.path().to_string_lossy();
+    match entry.change() {
+      EntryChange::Added => println!("+ {} ({} bytes)", path, entry.new_summary().unwrap().size()),
+      EntryChange::Removed => {
+        println!("- {} ({} bytes)", path, entry.old_summary().unwrap().size())
+      }
+      EntryChange::Changed => {
+        let (old, new) = (entry.old_summary().unwrap(), entry.new_summary().unwrap());
+        println!(
+          "~ {} ({} bytes, crc {:08x} -> {} bytes, crc {:08x})",
+          path,
+          old.size(),
+          old.crc().unwrap_or_default(),
+          new.size(),
+          new.crc().unwrap_or_default()
+        );
+      }
+    }
+    for resource_change in entry.resource_changes() {
+      println!("    {}", resource_change.message());
+    }
+  }
+  println!("{} files differ", diff.entries().len());
+  Ok(())
+}
@@ -0,0 +1,64 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use clap::Args;
+use lslib::lsv::package::Package;
+
+use crate::error::Error;
+
+use super::{parse_level, parse_method};
+
+#[derive(Args, Debug)]
+pub struct AddArgs {
+  // Path of the file inside the package, defaults to the file name
+  #[arg(short, long)]
+  name: Option<PathBuf>,
+  // Compression method of the added file (none, zlib, lz4 or zstd)
+  #[arg(short, long, default_value = "lz4")]
+  method: String,
+  // Compression level (fast, default or max)
+  #[arg(short, long, default_value = "default")]
+  level: String,
+}
+
+pub async fn cli_lsv_add(path: PathBuf, file: PathBuf, args: AddArgs) -> Result<(), Error> {
+  let name = match args.name {
+    Some(name) => name,
+    None => PathBuf::from(
+      file
+        .file_name()
+        .ok_or(Error::InvalidArgument(file.to_string_lossy().into_owned()))?,
+    ),
+  };
+  let contents = fs::read(&file)?;
+
+  let mut handle = Package::open_file(&path)?;
+  let mut edit = handle.edit();
+  edit.set_method(parse_method(&args.method)?);
+  edit.set_level(parse_level(&args.level)?);
+  edit.add(&name, contents)?;
+  edit.commit()?;
+  Ok(())
+}
+
+pub async fn cli_lsv_replace(path: PathBuf, name: PathBuf, file: PathBuf) -> Result<(), Error> {
+  let contents = fs::read(&file)?;
+
+  let mut handle = Package::open_file(&path)?;
+  let mut edit = handle.edit();
+  edit.replace(&name, contents)?;
+  edit.commit()?;
+  Ok(())
+}
+
+pub async fn cli_lsv_rm(path: PathBuf, names: Vec<PathBuf>) -> Result<(), Error> {
+  let mut handle = Package::open_file(&path)?;
+  let mut edit = handle.edit();
+  for name in &names {
+    edit.remove(Path::new(name))?;
+  }
+  edit.commit()?;
+  Ok(())
+}
@@ -1,31 +1,138 @@
+mod archive;
+mod diff;
+mod edit;
 mod list;
+mod pack;
 mod unpack;
+mod verify;
 
 use std::path::PathBuf;
 
 use clap::Subcommand;
+use lslib::compression::{Level, Method};
 
 use crate::error::Error;
 
-use self::{list::cli_lsv_list, unpack::cli_lsv_unpack};
+use self::{
+  archive::{cli_lsv_export, cli_lsv_import},
+  diff::{cli_lsv_diff, DiffArgs},
+  edit::{cli_lsv_add, cli_lsv_replace, cli_lsv_rm, AddArgs},
+  list::cli_lsv_list,
+  pack::{cli_lsv_pack, PackArgs},
+  unpack::{cli_lsv_unpack, UnpackArgs},
+  verify::cli_lsv_verify,
+};
+
+// Compression options shared by the commands writing packages
+fn parse_method(method: &str) -> Result<Method, Error> {
+  match method.to_lowercase().as_str() {
+    "none" => Ok(Method::None),
+    "zlib" => Ok(Method::Zlib),
+    "lz4" => Ok(Method::Lz4),
+    "zstd" => Ok(Method::Zstd),
+    _ => Err(Error::InvalidArgument(method.to_owned())),
+  }
+}
+
+fn parse_level(level: &str) -> Result<Level, Error> {
+  match level.to_lowercase().as_str() {
+    "fast" => Ok(Level::Fast),
+    "default" => Ok(Level::Default),
+    "max" => Ok(Level::Max),
+    _ => Err(Error::InvalidArgument(level.to_owned())),
+  }
+}
 
 #[derive(Subcommand, Debug)]
 pub enum LsvCommand {
+  Add {
+    #[arg(index = 1)]
+    path: PathBuf,
+    #[arg(index = 2)]
+    file: PathBuf,
+    #[command(flatten)]
+    args: AddArgs,
+  },
+  Diff {
+    #[arg(index = 1)]
+    old_path: PathBuf,
+    #[arg(index = 2)]
+    new_path: PathBuf,
+    #[command(flatten)]
+    args: DiffArgs,
+  },
+  Export {
+    #[arg(index = 1)]
+    path: PathBuf,
+    // Archive to create, the format follows its extension (.tar, .tar.gz, .tgz or .zip)
+    #[arg(index = 2)]
+    out: PathBuf,
+  },
+  Import {
+    #[arg(index = 1)]
+    archive: PathBuf,
+    #[arg(index = 2)]
+    out: PathBuf,
+  },
   List {
     #[arg(index = 1)]
     path: PathBuf,
   },
+  Pack {
+    #[arg(index = 1)]
+    dir: PathBuf,
+    #[arg(index = 2)]
+    out: PathBuf,
+    #[command(flatten)]
+    args: PackArgs,
+  },
+  Replace {
+    #[arg(index = 1)]
+    path: PathBuf,
+    #[arg(index = 2)]
+    name: PathBuf,
+    #[arg(index = 3)]
+    file: PathBuf,
+  },
+  Rm {
+    #[arg(index = 1)]
+    path: PathBuf,
+    #[arg(index = 2, required = true)]
+    names: Vec<PathBuf>,
+  },
   Unpack {
     #[arg(index = 1)]
     path: PathBuf,
     #[arg(index = 2)]
     target_dir: PathBuf,
+    #[command(flatten)]
+    args: UnpackArgs,
+  },
+  Verify {
+    #[arg(index = 1)]
+    path: PathBuf,
   },
 }
 
 pub async fn cli_lsv(command: LsvCommand) -> Result<(), Error> {
   match command {
+    LsvCommand::Add { path, file, args } => cli_lsv_add(path, file, args).await,
+    LsvCommand::Diff {
+      old_path,
+      new_path,
+      args,
+    } => cli_lsv_diff(old_path, new_path, args).await,
+    LsvCommand::Export { path, out } => cli_lsv_export(path, out).await,
+    LsvCommand::Import { archive, out } => cli_lsv_import(archive, out).await,
     LsvCommand::List { path } => cli_lsv_list(path).await,
-    LsvCommand::Unpack { path, target_dir } => cli_lsv_unpack(path, target_dir).await,
+    LsvCommand::Pack { dir, out, args } => cli_lsv_pack(dir, out, args).await,
+    LsvCommand::Replace { path, name, file } => cli_lsv_replace(path, name, file).await,
+    LsvCommand::Rm { path, names } => cli_lsv_rm(path, names).await,
+    LsvCommand::Unpack {
+      path,
+      target_dir,
+      args,
+    } => cli_lsv_unpack(path, target_dir, args).await,
+    LsvCommand::Verify { path } => cli_lsv_verify(path).await,
   }
 }
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use lslib::{
+  lsf,
+  lsv::{
+    header::{Header, Version},
+    pack::{pack_directory, PackOptions},
+    package::Package,
+  },
+};
+
+use crate::error::Error;
+
+use super::{parse_level, parse_method};
+
+#[derive(Args, Debug)]
+pub struct PackArgs {
+  // Package version (13, 15, 16 or 18), defaults to 16 (BG3 early access)
+  #[arg(long)]
+  package_version: Option<u32>,
+  // Compression method of the files (none, zlib, lz4 or zstd)
+  #[arg(short, long, default_value = "lz4")]
+  method: String,
+  // Compression level (fast, default or max)
+  #[arg(short, long, default_value = "default")]
+  level: String,
+  // Compress all files into a single frame, only supported by v13 packages
+  #[arg(long)]
+  solid: bool,
+  // Load order priority of the package
+  #[arg(short, long, default_value_t = 0)]
+  priority: u8,
+  // Additional package flags, the solid flag can only be set through --solid
+  #[arg(long, default_value_t = 0)]
+  flags: u8,
+  // Convert LSX resources to LSF before packing them
+  #[arg(long)]
+  convert_lsx: bool,
+  // LSF version of converted resources, defaults to the one matching the package version
+  #[arg(long, requires = "convert_lsx", value_parser = clap::value_parser!(u32).range(1..=7))]
+  lsf_version: Option<u32>,
+}
+
+impl PackArgs {
+  fn options(&self) -> Result<PackOptions, Error> {
+    let mut options = PackOptions::new();
+    if let Some(version) = self.package_version {
+      options.set_version(match version {
+        13 => Version::V13,
+        15 => Version::V15,
+        16 => Version::V16,
+        18 => Version::V18,
+        _ => return Err(Error::InvalidArgument(version.to_string())),
+      });
+    }
+    options.set_method(parse_method(&self.method)?);
+    options.set_level(parse_level(&self.level)?);
+    options.set_priority(self.priority);
+    if self.flags & Header::FLAGS_SOLID != 0 {
+      return Err(Error::InvalidArgument("--flags".to_string()));
+    }
+    options.set_flags(self.flags);
+    if self.solid {
+      if options.version() != Version::V13 {
+        return Err(Error::InvalidArgument("--solid".to_string()));
+      }
+      options.set_solid(true);
+    }
+    options.set_convert_lsx(self.convert_lsx);
+    options.set_lsf_version(
+      self
+        .lsf_version
+        .map(lsf::header::Version::try_from)
+        .transpose()?,
+    );
+    Ok(options)
+  }
+}
+
+pub async fn cli_lsv_pack(dir: PathBuf, out: PathBuf, args: PackArgs) -> Result<(), Error> {
+  let options = args.options()?;
+  let mut package = pack_directory(&dir, &options)?;
+  Package::create_file(&out)?.write(&mut package)?;
+  println!(
+    "Packed {} files into {}",
+    package.files().len(),
+    out.to_string_lossy()
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(solid: bool, flags: u8) -> PackArgs {
+    PackArgs {
+      package_version: Some(13),
+      method: "lz4".to_string(),
+      level: "default".to_string(),
+      solid,
+      priority: 0,
+      flags,
+      convert_lsx: false,
+      lsf_version: None,
+    }
+  }
+
+  #[test]
+  fn test_pack_solid_flag() {
+    assert!(args(true, 0).options().unwrap().is_solid());
+    assert!(!args(false, Header::FLAGS_PRELOAD)
+      .options()
+      .unwrap()
+      .is_solid());
+    assert!(matches!(
+      args(false, Header::FLAGS_SOLID).options(),
+      Err(Error::InvalidArgument(argument)) if argument == "--flags"
+    ));
+  }
+}
@@ -1,10 +1,119 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use lslib::{file::File, lsv::package::Unpack};
+use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
+use lslib::{
+  file::File,
+  lsv::{
+    extract::{Pattern, TextFormat, UnpackOptions},
+    package::Unpack,
+  },
+};
 
 use crate::error::Error;
-pub async fn cli_lsv_unpack(path: PathBuf, target_dir: PathBuf) -> Result<(), Error> {
+
+#[derive(Args, Debug)]
+pub struct UnpackArgs {
+  // Only unpack files matching one of these globs (e.g. Public/**/*.lsf)
+  #[arg(short, long)]
+  include: Vec<String>,
+  // Skip files matching one of these globs
+  #[arg(short, long)]
+  exclude: Vec<String>,
+  // Only unpack files matching one of these regular expressions
+  #[arg(long)]
+  include_regex: Vec<String>,
+  // Skip files matching one of these regular expressions
+  #[arg(long)]
+  exclude_regex: Vec<String>,
+  // Replace a leading directory of the unpacked files, given as FROM=TO
+  #[arg(short, long)]
+  rewrite: Vec<String>,
+  // Unpack all files directly into the target directory
+  #[arg(short, long)]
+  flatten: bool,
+  // Only list the files that would be unpacked
+  #[arg(short = 'n', long)]
+  dry_run: bool,
+  // Convert LSF and LSB resources to a text format (lsx, json or yaml)
+  #[arg(short, long)]
+  convert: Option<String>,
+  // Keep the binary resources next to the converted ones
+  #[arg(short, long, requires = "convert")]
+  keep_binary: bool,
+}
+
+impl UnpackArgs {
+  fn options(&self) -> Result<UnpackOptions, Error> {
+    let mut options = UnpackOptions::new();
+    for pattern in &self.include {
+      options.add_include(Pattern::glob(pattern)?);
+    }
+    for pattern in &self.exclude {
+      options.add_exclude(Pattern::glob(pattern)?);
+    }
+    for pattern in &self.include_regex {
+      options.add_include(Pattern::regex(pattern)?);
+    }
+    for pattern in &self.exclude_regex {
+      options.add_exclude(Pattern::regex(pattern)?);
+    }
+    for rewrite in &self.rewrite {
+      let (from, to) = rewrite
+        .split_once('=')
+        .ok_or(Error::InvalidArgument(rewrite.to_owned()))?;
+      options.add_rewrite(Path::new(from), Path::new(to));
+    }
+    options.set_flatten(self.flatten);
+    options.set_dry_run(self.dry_run);
+    if let Some(convert) = &self.convert {
+      let text_format =
+        TextFormat::from_extension(convert).ok_or(Error::InvalidArgument(convert.to_owned()))?;
+      options.set_convert(Some(text_format));
+      options.set_keep_binary(self.keep_binary);
+    }
+    Ok(options)
+  }
+}
+
+pub async fn cli_lsv_unpack(
+  path: PathBuf,
+  target_dir: PathBuf,
+  args: UnpackArgs,
+) -> Result<(), Error> {
   let mut builder = File::open(&path)?.as_lsv()?;
-  builder.unpack(&target_dir)?;
+  let options = args.options()?;
+
+  let progress_bar = if options.dry_run() {
+    ProgressBar::hidden()
+  } else {
+    ProgressBar::new(0)
+  };
+  progress_bar.set_style(
+    ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({eta}) {wide_msg}").unwrap(),
+  );
+  let entries = builder.unpack_with_options(&target_dir, &options, &|progress| {
+    progress_bar.set_length(progress.total_bytes());
+    progress_bar.set_position(progress.bytes_done());
+    progress_bar.set_message(progress.path().to_string_lossy().into_owned());
+  })?;
+  progress_bar.finish_and_clear();
+
+  if options.dry_run() {
+    for entry in entries {
+      let mut target_paths = entry.text_path().into_iter().collect::<Vec<_>>();
+      if entry.writes_binary() {
+        target_paths.insert(0, entry.target_path());
+      }
+      for target_path in target_paths {
+        println!(
+          "{} -> {}",
+          entry.path().to_string_lossy(),
+          target_dir.join(target_path).to_string_lossy()
+        );
+      }
+    }
+  }
+
   Ok(())
 }
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use comfy_table::{presets::ASCII_HORIZONTAL_ONLY, ContentArrangement, Table};
+use lslib::{file::File, lsv::verify::Md5Status};
+
+use crate::error::Error;
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub async fn cli_lsv_verify(path: PathBuf) -> Result<(), Error> {
+  let mut package = File::open(&path)?.as_lsv()?;
+  let report = package.verify()?;
+
+  if !report.issues().is_empty() {
+    let mut table = Table::new();
+    table
+      .load_preset(ASCII_HORIZONTAL_ONLY)
+      .set_content_arrangement(ContentArrangement::Dynamic)
+      .set_header(vec!["Name", "Issue"]);
+    for file_issue in report.issues() {
+      table.add_row(vec![
+        file_issue.path().to_string_lossy().into_owned(),
+        file_issue.issue().message(),
+      ]);
+    }
+    println!("{}", table);
+  }
+
+  match report.md5() {
+    Md5Status::Match => println!("MD5: OK"),
+    Md5Status::Mismatch { expected, actual } => println!(
+      "MD5: mismatch (expected: {}, actual: {})",
+      hex(expected),
+      hex(actual)
+    ),
+    Md5Status::Unchecked => println!("MD5: not checked"),
+  }
+  println!(
+    "{} files checked, {} issues found",
+    report.file_count(),
+    report.issues().len()
+  );
+
+  if report.is_ok() {
+    Ok(())
+  } else {
+    Err(Error::VerificationFailed(path))
+  }
+}
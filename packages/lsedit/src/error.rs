@@ -1,20 +1,25 @@
-use std::{fmt::Debug, io::ErrorKind};
+use std::{fmt::Debug, io::ErrorKind, path::PathBuf};
 
 use quick_xml::DeError;
 
 pub enum Error {
   NoDataPath,
+  InvalidArgument(String),
+  VerificationFailed(PathBuf),
   Io(std::io::Error),
   Yaml(serde_yaml::Error),
   Json(serde_json::Error),
   Lslib(lslib::error::Error),
   XmlDeserialize(DeError),
+  Zip(zip::result::ZipError),
 }
 
 impl Error {
   pub fn message(&self) -> String {
     match self {
       Self::NoDataPath => "No data path specified".to_string(),
+      Self::InvalidArgument(argument) => format!("Invalid argument: {}", argument),
+      Self::VerificationFailed(path) => format!("Verification failed: {}", path.display()),
       Self::Io(error) => match error.kind() {
         ErrorKind::NotFound => format!("File not found: {}", error),
         _ => format!("IO error: {} - {}", error.kind(), error),
@@ -23,6 +28,7 @@ impl Error {
       Self::Json(error) => error.to_string(),
       Self::Lslib(error) => error.to_string(),
       Self::XmlDeserialize(error) => error.to_string(),
+      Self::Zip(error) => error.to_string(),
     }
   }
 }
@@ -68,3 +74,9 @@ impl From<DeError> for Error {
     Error::XmlDeserialize(error)
   }
 }
+
+impl From<zip::result::ZipError> for Error {
+  fn from(error: zip::result::ZipError) -> Self {
+    Error::Zip(error)
+  }
+}
@@ -1,15 +1,17 @@
 pub mod read;
+pub mod write;
 
 #[repr(u32)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Method {
   None = 0x00,
   Zlib = 0x01,
   Lz4 = 0x02,
+  Zstd = 0x03,
 }
 
 #[repr(u32)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Level {
   Fast = 0x10,
   Default = 0x20,
@@ -64,6 +66,7 @@ impl From<u8> for CompressionOptions {
     let method = match value & 0x0f {
       0x01 => Method::Zlib,
       0x02 => Method::Lz4,
+      0x03 => Method::Zstd,
       _ => Method::None,
     };
     let level = match value & 0xf0 {
@@ -87,6 +90,7 @@ impl Into<u8> for CompressionOptions {
     value |= match self.method {
       Method::Zlib => 0x01,
       Method::Lz4 => 0x02,
+      Method::Zstd => 0x03,
       _ => 0,
     };
     value |= match self.level {
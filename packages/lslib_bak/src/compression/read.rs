@@ -1,8 +1,13 @@
-use std::io::{Cursor, Read};
+use std::{
+  cell::RefCell,
+  io::{self, Cursor, Read},
+  rc::Rc,
+};
 
-use crc32fast::hash;
+use crc32fast::{hash, Hasher};
 use flate2::read::ZlibDecoder;
 use lz4_flex::{block, frame};
+use zstd::stream::read::Decoder as ZstdDecoder;
 
 use crate::{error::Error, util::read::BinaryReadExt};
 
@@ -40,8 +45,125 @@ pub trait DecompressReadExt: Read {
         method: Method::Lz4,
         ..
       } => Ok(block::decompress(&bytes, uncompressed_size)?),
+      CompressionOptions {
+        method: Method::Zstd,
+        ..
+      } => Ok(ZstdDecoder::new(Cursor::new(bytes))?.read_bytes(uncompressed_size)?),
     }
   }
 }
 
 impl<R: Read + ?Sized> DecompressReadExt for R {}
+
+// Wraps a reader of compressed bytes into a reader of the uncompressed bytes, so large files can
+// be processed without holding them in memory. LZ4 blocks can't be decompressed incrementally,
+// so they are decompressed up front. The CRC of the compressed bytes is checked once the
+// uncompressed bytes have been read, a mismatch fails the last read.
+pub fn decompress_reader<'a, R: Read + 'a>(
+  reader: R,
+  uncompressed_size: usize,
+  options: CompressionOptions,
+) -> Result<Box<dyn Read + 'a>, Error> {
+  let expected_crc = match options.compressed_crc() {
+    Some(expected_crc) => expected_crc,
+    None => return decoder(reader, uncompressed_size, options),
+  };
+
+  let compressed = Rc::new(RefCell::new(CrcReader {
+    reader,
+    hasher: Hasher::new(),
+    expected_crc,
+  }));
+  let decompressed = decoder(SharedReader(compressed.clone()), uncompressed_size, options)?;
+  Ok(Box::new(CheckedReader {
+    decompressed,
+    compressed,
+  }))
+}
+
+fn decoder<'a, R: Read + 'a>(
+  mut reader: R,
+  uncompressed_size: usize,
+  options: CompressionOptions,
+) -> Result<Box<dyn Read + 'a>, Error> {
+  match options {
+    CompressionOptions {
+      method: Method::None,
+      ..
+    } => Ok(Box::new(reader)),
+    CompressionOptions {
+      method: Method::Zlib,
+      ..
+    } => Ok(Box::new(ZlibDecoder::new(reader))),
+    CompressionOptions {
+      method: Method::Lz4,
+      chunked: true,
+      ..
+    } => Ok(Box::new(frame::FrameDecoder::new(reader))),
+    CompressionOptions {
+      method: Method::Lz4,
+      ..
+    } => {
+      let mut bytes = vec![];
+      reader.read_to_end(&mut bytes)?;
+      Ok(Box::new(Cursor::new(block::decompress(
+        &bytes,
+        uncompressed_size,
+      )?)))
+    }
+    CompressionOptions {
+      method: Method::Zstd,
+      ..
+    } => Ok(Box::new(ZstdDecoder::new(reader)?)),
+  }
+}
+
+// Hashes the compressed bytes while they are read and checks the CRC once all of them are
+struct CrcReader<R> {
+  reader: R,
+  hasher: Hasher,
+  expected_crc: u32,
+}
+
+impl<R: Read> Read for CrcReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.reader.read(buf)?;
+    self.hasher.update(&buf[..read]);
+    if read == 0 && !buf.is_empty() {
+      let crc = self.hasher.clone().finalize();
+      if crc != self.expected_crc {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          Error::CrcMismatch(self.expected_crc, crc).to_string(),
+        ));
+      }
+    }
+    Ok(read)
+  }
+}
+
+// Lets the decoder read the compressed bytes while CheckedReader keeps access to them
+struct SharedReader<R>(Rc<RefCell<CrcReader<R>>>);
+
+impl<R: Read> Read for SharedReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.0.borrow_mut().read(buf)
+  }
+}
+
+// Decoders may stop before the end of the compressed bytes, so their rest is hashed once the
+// uncompressed bytes are done
+struct CheckedReader<'a, R> {
+  decompressed: Box<dyn Read + 'a>,
+  compressed: Rc<RefCell<CrcReader<R>>>,
+}
+
+impl<'a, R: Read> Read for CheckedReader<'a, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.decompressed.read(buf)?;
+    if read == 0 && !buf.is_empty() {
+      io::copy(&mut *self.compressed.borrow_mut(), &mut io::sink())?;
+    }
+    Ok(read)
+  }
+}
@@ -0,0 +1,106 @@
+use std::io::{Cursor, Write};
+
+use flate2::{write::ZlibEncoder, Compression};
+use lz4_flex::{block, frame};
+
+use crate::error::Error;
+
+use super::{CompressionOptions, Level, Method};
+
+pub fn compress(bytes: &[u8], options: &CompressionOptions) -> Result<Vec<u8>, Error> {
+  match options {
+    CompressionOptions {
+      method: Method::None,
+      ..
+    } => Ok(bytes.to_vec()),
+    CompressionOptions {
+      method: Method::Zlib,
+      level,
+      ..
+    } => {
+      let compression = match level {
+        Level::Fast => Compression::fast(),
+        Level::Default => Compression::default(),
+        Level::Max => Compression::best(),
+      };
+      let mut encoder = ZlibEncoder::new(Vec::with_capacity(bytes.len()), compression);
+      encoder.write_all(bytes)?;
+      Ok(encoder.finish()?)
+    }
+    CompressionOptions {
+      method: Method::Lz4,
+      chunked: true,
+      ..
+    } => {
+      let mut encoder = frame::FrameEncoder::new(Cursor::new(Vec::with_capacity(bytes.len())));
+      encoder.write_all(bytes)?;
+      Ok(encoder.finish().map_err(std::io::Error::from)?.into_inner())
+    }
+    // lz4_flex has no high compression mode, so all levels produce the same block
+    CompressionOptions {
+      method: Method::Lz4,
+      ..
+    } => Ok(block::compress(bytes)),
+    CompressionOptions {
+      method: Method::Zstd,
+      level,
+      ..
+    } => {
+      let compression_level = match level {
+        Level::Fast => 1,
+        Level::Default => 3,
+        Level::Max => 19,
+      };
+      Ok(zstd::bulk::compress(bytes, compression_level)?)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+
+  use crate::compression::read::{decompress_reader, DecompressReadExt};
+
+  use super::*;
+
+  #[test]
+  fn test_compress_round_trip() {
+    let bytes = b"Hello, World! Hello, World! Hello, World! Hello, World!".to_vec();
+    for method in [Method::None, Method::Zlib, Method::Lz4, Method::Zstd] {
+      for chunked in [false, true] {
+        let mut options = CompressionOptions::new(method, Level::Default);
+        options.set_chunked(chunked);
+        let compressed = compress(&bytes, &options).unwrap();
+
+        let mut options = CompressionOptions::new(*options.method(), Level::Default);
+        options.set_chunked(chunked);
+        let uncompressed = Cursor::new(&compressed)
+          .read_decompressed(compressed.len(), bytes.len(), options)
+          .unwrap();
+        assert_eq!(uncompressed, bytes);
+      }
+    }
+  }
+
+  #[test]
+  fn test_decompress_reader_crc() {
+    let bytes = b"Hello, World! Hello, World! Hello, World! Hello, World!".to_vec();
+    for method in [Method::None, Method::Zlib, Method::Lz4, Method::Zstd] {
+      let compressed = compress(&bytes, &CompressionOptions::new(method, Level::Default)).unwrap();
+      let crc = crc32fast::hash(&compressed);
+      for (expected_crc, valid) in [(crc, true), (crc ^ 1, false)] {
+        let mut options: CompressionOptions = method.into();
+        options.set_compressed_crc(Some(expected_crc));
+        // LZ4 blocks are decompressed up front, so their CRC is already checked here
+        let mut uncompressed = vec![];
+        let result = decompress_reader(compressed.as_slice(), bytes.len(), options)
+          .and_then(|mut reader| Ok(reader.read_to_end(&mut uncompressed)?));
+        assert_eq!(result.is_ok(), valid);
+        if valid {
+          assert_eq!(uncompressed, bytes);
+        }
+      }
+    }
+  }
+}
@@ -4,19 +4,28 @@ pub enum Error {
   InvalidSignature([u8; 4], [u8; 4]),
   InvalidVersion(i32),
   InvalidFileTable,
-  FileTooLarge(String, u32),
+  FileTooLarge(String, u64),
   CrcMismatch(u32, u32),
   FileNotFound(String),
+  FileExists(String),
   FileEmpty(String),
   InvalidStringIndex(i32),
   InvalidStringOffset(i32, i32),
   InvalidAttributeIndex(i32),
+  InvalidNodeIndex(i32),
   InvalidTypeId(u32),
+  InvalidValue(u32, String),
+  InvalidLsx(String),
   InvalidPath(String),
+  Unsupported(String),
   Io(std::io::Error),
   Lz4Decompress(lz4_flex::block::DecompressError),
   Utf8(std::str::Utf8Error),
+  Json(serde_json::Error),
+  Xml(quick_xml::Error),
   Yaml(serde_yaml::Error),
+  Glob(globset::Error),
+  Regex(regex::Error),
 }
 
 impl Error {
@@ -38,6 +47,7 @@ impl Error {
         expected, expected, actual
       ),
       Self::FileNotFound(path) => format!("File not found: {}", path),
+      Self::FileExists(path) => format!("File already exists: {}", path),
       Self::FileEmpty(path) => format!("File empty: {}", path),
       Self::InvalidStringIndex(index) => format!("Invalid string index: {}", index),
       Self::InvalidStringOffset(index, offset) => format!(
@@ -47,15 +57,23 @@ impl Error {
       Self::InvalidAttributeIndex(index) => {
         format!("Invalid attribute index: {}", index)
       }
+      Self::InvalidNodeIndex(index) => format!("Invalid node index: {}", index),
       Self::InvalidTypeId(id) => format!("Invalid type ID: {}", id),
+      Self::InvalidValue(id, value) => format!("Invalid value for type ID {}: {}", id, value),
+      Self::InvalidLsx(message) => format!("Invalid LSX: {}", message),
       Self::InvalidPath(path) => format!("Invalid path: {}", path),
+      Self::Unsupported(message) => format!("Unsupported: {}", message),
       Self::Io(error) => match error.kind() {
         ErrorKind::NotFound => format!("File not found: {}", error),
         _ => format!("IO error: {} - {}", error.kind(), error),
       },
       Self::Lz4Decompress(error) => error.to_string(),
       Self::Utf8(error) => error.to_string(),
+      Self::Json(error) => error.to_string(),
+      Self::Xml(error) => error.to_string(),
       Self::Yaml(error) => error.to_string(),
+      Self::Glob(error) => error.to_string(),
+      Self::Regex(error) => error.to_string(),
     }
   }
 }
@@ -90,8 +108,32 @@ impl From<std::str::Utf8Error> for Error {
   }
 }
 
+impl From<serde_json::Error> for Error {
+  fn from(error: serde_json::Error) -> Self {
+    Error::Json(error)
+  }
+}
+
+impl From<quick_xml::Error> for Error {
+  fn from(error: quick_xml::Error) -> Self {
+    Error::Xml(error)
+  }
+}
+
 impl From<serde_yaml::Error> for Error {
   fn from(error: serde_yaml::Error) -> Self {
     Error::Yaml(error)
   }
 }
+
+impl From<globset::Error> for Error {
+  fn from(error: globset::Error) -> Self {
+    Error::Glob(error)
+  }
+}
+
+impl From<regex::Error> for Error {
+  fn from(error: regex::Error) -> Self {
+    Error::Regex(error)
+  }
+}
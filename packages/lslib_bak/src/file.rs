@@ -8,8 +8,8 @@ use std::{
 use crate::{
   error::Error,
   lsb::read::LsbReadExt,
-  lsf::read::LsfReadExt,
-  lsv::package::{Package, PackageHandle, Transform},
+  lsf::{lazy::LazyResource, read::LsfReadExt},
+  lsv::package::{PackageHandle, Transform},
   resource::Resource,
 };
 
@@ -47,8 +47,9 @@ impl<I: Read + Seek> FileReference<I> {
       Self::LsvFile(handle, path) => {
         let reader = PackageHandle::open(path, |path| {
           let mut handle = handle.borrow_mut();
-          let contents = handle.file_contents(path)?;
-          Ok(File::Cursor(Cursor::new(contents.clone())))
+          let mut contents = vec![];
+          handle.file_stream(path)?.read_to_end(&mut contents)?;
+          Ok(File::Cursor(Cursor::new(contents)))
         })?;
         Ok(reader)
       }
@@ -72,6 +73,19 @@ impl<I: Read + Seek> FileReference<I> {
     }
   }
 
+  pub fn as_lazy_lsf(&mut self) -> Result<LazyResource, Error> {
+    match self {
+      Self::LsvFile(handle, path) => {
+        let mut handle = handle.borrow_mut();
+        handle.lazy_lsf_file(path)
+      }
+      Self::OsFile(path) => {
+        let mut reader = fs::File::open(path)?;
+        reader.read_lsf_lazy_resource()
+      }
+    }
+  }
+
   pub fn as_lsb(&mut self) -> Result<Resource, Error> {
     match self {
       Self::LsvFile(handle, path) => {
@@ -87,38 +101,155 @@ impl<I: Read + Seek> FileReference<I> {
   }
 }
 
+fn is_package_path(path: &Path) -> bool {
+  path
+    .extension()
+    .map(|extension| extension.eq_ignore_ascii_case("pak") || extension.eq_ignore_ascii_case("lsv"))
+    .unwrap_or(false)
+}
+
+// Opens a package stored inside another one, its parts are looked up next to it
+fn open_nested_package(
+  handle: PackageHandle<File>,
+  path: &Path,
+) -> Result<PackageHandle<File>, Error> {
+  let handle = RefCell::new(handle);
+  PackageHandle::open(path, |part_path| {
+    let mut handle = handle.borrow_mut();
+    let mut contents = vec![];
+    handle.file_stream(part_path)?.read_to_end(&mut contents)?;
+    Ok(File::Cursor(Cursor::new(contents)))
+  })
+}
+
 impl File {
-  pub fn open(path: &Path) -> Result<FileReference<fs::File>, Error> {
-    let mut lsv_path_components: Option<Vec<Component>> = None;
-    let mut file_path_components: Vec<Component> = vec![];
-    let path_components: Vec<Component> = path.components().collect();
-    let last_index = path_components.len() - 1;
-    for (index, &component) in path_components.iter().enumerate() {
-      file_path_components.push(component);
-
-      if component.as_os_str().to_str().unwrap().ends_with(".lsv") && index != last_index {
-        lsv_path_components = Some(file_path_components.clone());
-        file_path_components.clear();
+  // Resolves a path through any number of packages, e.g. Mods.pak/Saves/Quick.lsv/globals.lsf.
+  // A .pak or .lsv component followed by further components is opened as package, if it's a file
+  // on disk or in the enclosing package. Otherwise the path is treated as a plain directory.
+  pub fn open(path: &Path) -> Result<FileReference<File>, Error> {
+    let components: Vec<Component> = path.components().collect();
+    if components.is_empty() {
+      return Err(Error::InvalidPath(path.to_string_lossy().into_owned()));
+    }
+
+    let mut remaining = components.iter().peekable();
+    let mut os_path = PathBuf::new();
+    let mut handle = loop {
+      let component = match remaining.next() {
+        Some(component) => component,
+        None => return Ok(FileReference::OsFile(os_path)),
+      };
+      os_path.push(component);
+      if remaining.peek().is_some() && is_package_path(&os_path) && os_path.is_file() {
+        break PackageHandle::open(&os_path, |path| Ok(File::FsFile(fs::File::open(path)?)))?;
+      }
+    };
+
+    let mut entry_path = String::new();
+    while let Some(component) = remaining.next() {
+      let name = component
+        .as_os_str()
+        .to_str()
+        .ok_or(Error::InvalidPath(path.to_string_lossy().into_owned()))?;
+      if !entry_path.is_empty() {
+        entry_path.push('/');
+      }
+      entry_path.push_str(name);
+
+      let entry = Path::new(&entry_path);
+      if remaining.peek().is_some() && is_package_path(entry) && handle.file(entry).is_some() {
+        handle = open_nested_package(handle, entry)?;
+        entry_path.clear();
       }
     }
 
-    let file_path = file_path_components
-      .iter()
-      .fold(PathBuf::new(), |mut path, component| {
-        path.push(component);
-        path
-      });
-
-    if let Some(components) = lsv_path_components {
-      let lsv_path = components
-        .iter()
-        .fold(PathBuf::new(), |mut path, component| {
-          path.push(component);
-          path
-        });
-      let handle = Package::open_file(&lsv_path)?;
-      return Ok(FileReference::LsvFile(RefCell::new(handle), file_path));
+    if handle.file(Path::new(&entry_path)).is_none() {
+      return Err(Error::FileNotFound(path.to_string_lossy().into_owned()));
     }
-    Ok(FileReference::OsFile(file_path))
+    Ok(FileReference::LsvFile(
+      RefCell::new(handle),
+      PathBuf::from(entry_path),
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    compression::{CompressionOptions, Level, Method},
+    lsf::{header::Version, write::LsfWriteExt},
+    lsv::{file::File as PackageFile, package::Package, write::LsvWriteExt},
+    resource::node::data::Data,
+    util::arena::{ArenaReader, ArenaWriter},
+  };
+
+  use super::*;
+
+  fn package(files: Vec<(&str, Vec<u8>)>) -> Package {
+    let mut package = Package::default();
+    let flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+    for (name, contents) in files {
+      let mut file = PackageFile::new();
+      file.set_path(PathBuf::from(name));
+      file.set_flags(flags as u32);
+      file.set_contents(Some(contents));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    package
+  }
+
+  #[test]
+  fn test_open_nested_path() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+
+    let mut resource = Resource::new();
+    resource.alloc(Data::new("Globals".to_string()), None);
+    let mut globals = vec![];
+    globals.write_lsf_resource(&resource, Version::V3).unwrap();
+    let mut save = Cursor::new(vec![]);
+    save
+      .write_lsv_package(&mut package(vec![("globals.lsf", globals)]))
+      .unwrap();
+    let mut mods = package(vec![
+      ("Saves/Quick.lsv", save.into_inner()),
+      ("readme.txt", b"readme".to_vec()),
+    ]);
+    Package::create_file(&dir.join("Mods.pak"))
+      .unwrap()
+      .write(&mut mods)
+      .unwrap();
+    // Unpacked packages are plain directories
+    fs::create_dir_all(dir.join("Unpacked.pak")).unwrap();
+
+    let resource = File::open(&dir.join("Mods.pak/Saves/Quick.lsv/globals.lsf"))
+      .unwrap()
+      .as_lsf()
+      .unwrap();
+    assert_eq!(resource.value(resource.root_indexes()[0]).name(), "Globals");
+    let save = File::open(&dir.join("Mods.pak/Saves/Quick.lsv"))
+      .unwrap()
+      .as_lsv()
+      .unwrap();
+    assert!(save.file(Path::new("globals.lsf")).is_some());
+    assert!(matches!(
+      File::open(&dir.join("Unpacked.pak/meta.lsx")).unwrap(),
+      FileReference::OsFile(_)
+    ));
+
+    assert!(matches!(
+      File::open(Path::new("")),
+      Err(Error::InvalidPath(_))
+    ));
+    assert!(matches!(
+      File::open(&dir.join("Mods.pak/Saves/Autosave.lsv/globals.lsf")),
+      Err(Error::FileNotFound(_))
+    ));
+    assert!(matches!(
+      File::open(&dir.join("Mods.pak/readme.txt/globals.lsf")),
+      Err(Error::FileNotFound(_))
+    ));
   }
 }
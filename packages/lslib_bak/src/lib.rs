@@ -5,5 +5,6 @@ pub mod file;
 pub mod lsb;
 pub mod lsf;
 pub mod lsv;
+pub mod lsx;
 pub mod resource;
 pub mod util;
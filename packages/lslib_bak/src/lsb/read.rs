@@ -14,6 +14,7 @@ use crate::{
       data::{Data, Kind},
     },
     value::{read::ResourceValueReadExt, Value},
+    visitor::{ResourceVisitor, Visit},
     Resource,
   },
   util::{
@@ -25,7 +26,11 @@ use crate::{
 use super::header::{SIGNATURE_BG3, SIGNATURE_FW3};
 
 pub trait LsbReadExt: Read + Seek {
-  fn read_lsb_resource(&mut self) -> Result<Resource, Error> {
+  // Reads everything before the regions, returns whether it's a BG3 file and the strings by index
+  fn read_lsb_header(
+    &mut self,
+    metadata: &mut Metadata,
+  ) -> Result<(bool, HashMap<u32, String>), Error> {
     let signature: [u8; 4] = self.read_u32::<LittleEndian>()?.to_be_bytes();
 
     if signature != SIGNATURE_BG3 && signature != SIGNATURE_FW3 {
@@ -37,8 +42,7 @@ pub trait LsbReadExt: Read + Seek {
     let _total_size = self.read_u32::<LittleEndian>()?;
     let _big_endian = self.read_u32::<LittleEndian>()?;
     let _unknown = self.read_u32::<LittleEndian>()?;
-    let mut metadata = Metadata::new();
-    self.read_resource_metadata(&mut metadata)?;
+    self.read_resource_metadata(metadata)?;
 
     let string_count = self.read_u32::<LittleEndian>()?;
     let mut strings: HashMap<u32, String> = HashMap::new();
@@ -48,6 +52,12 @@ pub trait LsbReadExt: Read + Seek {
       let index = self.read_u32::<LittleEndian>()?;
       strings.insert(index, string);
     }
+    Ok((is_bg3, strings))
+  }
+
+  fn read_lsb_resource(&mut self) -> Result<Resource, Error> {
+    let mut metadata = Metadata::new();
+    let (is_bg3, strings) = self.read_lsb_header(&mut metadata)?;
 
     let region_count = self.read_u32::<LittleEndian>()?;
     let mut arena: Arena<Data> = Arena::new();
@@ -67,7 +77,77 @@ pub trait LsbReadExt: Read + Seek {
       self.seek(SeekFrom::Start(last_region_position))?;
     }
 
-    Ok(Resource::new_with_arena(arena))
+    let mut resource = Resource::new_with_arena(arena);
+    resource.set_metadata(metadata);
+    Ok(resource)
+  }
+
+  // Traverses the resource without building its tree. LSB nodes don't store their size, so skipped
+  // subtrees are still read.
+  fn visit_lsb_resource(&mut self, visitor: &mut dyn ResourceVisitor) -> Result<(), Error> {
+    let (is_bg3, strings) = self.read_lsb_header(&mut Metadata::new())?;
+
+    let region_count = self.read_u32::<LittleEndian>()?;
+    for _ in 0..region_count {
+      let _region_name_id = self.read_u32::<LittleEndian>()?;
+      let region_offset = self.read_u32::<LittleEndian>()?;
+      let last_region_position = self.stream_position()?;
+      self.seek(SeekFrom::Start(region_offset as u64))?;
+      if self.visit_lsb_node(&strings, is_bg3, visitor, false)? == Visit::Stop {
+        break;
+      }
+      self.seek(SeekFrom::Start(last_region_position))?;
+    }
+    Ok(())
+  }
+
+  // Skipped nodes are read past without calling the visitor
+  fn visit_lsb_node(
+    &mut self,
+    strings: &HashMap<u32, String>,
+    is_bg3: bool,
+    visitor: &mut dyn ResourceVisitor,
+    skip: bool,
+  ) -> Result<Visit, Error> {
+    let node_name_id = self.read_u32::<LittleEndian>()?;
+    let attribute_count = self.read_u32::<LittleEndian>()?;
+    let child_count = self.read_u32::<LittleEndian>()?;
+    let node_name = lsb_string(strings, node_name_id)?;
+    let mut visit = match skip {
+      true => Visit::SkipSubtree,
+      false => visitor.enter_node(node_name),
+    };
+    if visit == Visit::Stop {
+      return Ok(Visit::Stop);
+    }
+
+    for _ in 0..attribute_count {
+      let attribute_name_id = self.read_u32::<LittleEndian>()?;
+      let attribute_type_id = self.read_u32::<LittleEndian>()?;
+      let attribute_value = self.read_lsb_attribute_value(attribute_type_id, is_bg3)?;
+      if visit == Visit::Continue {
+        let attribute_name = lsb_string(strings, attribute_name_id)?;
+        visit = visitor.attribute(attribute_name, &attribute_value);
+        if visit == Visit::Stop {
+          return Ok(Visit::Stop);
+        }
+      }
+    }
+
+    for _ in 0..child_count {
+      let skip_child = visit != Visit::Continue;
+      if self.visit_lsb_node(strings, is_bg3, visitor, skip_child)? == Visit::Stop {
+        return Ok(Visit::Stop);
+      }
+    }
+
+    if skip {
+      return Ok(Visit::Continue);
+    }
+    Ok(match visitor.exit_node(node_name) {
+      Visit::Stop => Visit::Stop,
+      _ => Visit::Continue,
+    })
   }
 
   fn read_lsb_node(
@@ -180,3 +260,10 @@ pub trait LsbReadExt: Read + Seek {
 }
 
 impl<R: Read + Seek + ?Sized> LsbReadExt for R {}
+
+fn lsb_string(strings: &HashMap<u32, String>, index: u32) -> Result<&str, Error> {
+  strings
+    .get(&index)
+    .map(String::as_str)
+    .ok_or(Error::InvalidStringIndex(index as i32))
+}
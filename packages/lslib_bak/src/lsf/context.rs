@@ -1,12 +1,17 @@
 use std::fmt::Debug;
 
-use super::{attribute::AttributeInfo, header::Header, node::NodeInfo};
+use crate::error::Error;
+
+use super::{attribute::AttributeInfo, header::Header, layout::Block, node::NodeInfo};
 
 pub struct Context {
   header: Header,
   string_lists: Vec<Vec<String>>,
   node_infos: Vec<NodeInfo>,
   attribute_infos: Vec<AttributeInfo>,
+  // Stored blocks are only kept when the layout of the file is captured
+  keep_blocks: bool,
+  blocks: Vec<Block>,
 }
 
 impl Context {
@@ -16,6 +21,8 @@ impl Context {
       string_lists: Default::default(),
       node_infos: Default::default(),
       attribute_infos: Default::default(),
+      keep_blocks: false,
+      blocks: Default::default(),
     }
   }
 
@@ -35,6 +42,17 @@ impl Context {
     &mut self.string_lists
   }
 
+  // Name referenced by its bucket and offset in the string table
+  pub fn string(&self, name_index: i32, name_offset: i32) -> Result<&str, Error> {
+    self
+      .string_lists
+      .get(name_index as usize)
+      .ok_or(Error::InvalidStringIndex(name_index))?
+      .get(name_offset as usize)
+      .map(String::as_str)
+      .ok_or(Error::InvalidStringOffset(name_index, name_offset))
+  }
+
   pub fn node_infos(&self) -> &Vec<NodeInfo> {
     &self.node_infos
   }
@@ -50,6 +68,22 @@ impl Context {
   pub fn attribute_infos_mut(&mut self) -> &mut Vec<AttributeInfo> {
     &mut self.attribute_infos
   }
+
+  pub fn keep_blocks(&self) -> bool {
+    self.keep_blocks
+  }
+
+  pub fn set_keep_blocks(&mut self, keep_blocks: bool) {
+    self.keep_blocks = keep_blocks;
+  }
+
+  pub fn blocks(&self) -> &Vec<Block> {
+    &self.blocks
+  }
+
+  pub fn blocks_mut(&mut self) -> &mut Vec<Block> {
+    &mut self.blocks
+  }
 }
 
 impl Debug for Context {
@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use crate::error::Error;
+
 pub type Signature = [u8; 4];
 
 pub const SIGNATURE: Signature = [0x4c, 0x53, 0x4f, 0x46]; // "LSOF"
@@ -12,19 +14,50 @@ pub enum Version {
   V3 = 3,
   V4 = 4, // BG3
   V5 = 5,
-  V6 = 6,
+  V6 = 6, // Keys block
+  V7 = 7, // BG3 patch 3, no layout changes over v6 are known, so it is read and written like v6
 }
 
-impl From<u32> for Version {
-  fn from(version: u32) -> Self {
+impl TryFrom<u32> for Version {
+  type Error = Error;
+
+  fn try_from(version: u32) -> Result<Self, Self::Error> {
     match version {
-      1 => Version::V1,
-      2 => Version::V2,
-      3 => Version::V3,
-      4 => Version::V4,
-      5 => Version::V5,
-      6 => Version::V6,
-      _ => panic!("Invalid package version: {}", version),
+      1 => Ok(Version::V1),
+      2 => Ok(Version::V2),
+      3 => Ok(Version::V3),
+      4 => Ok(Version::V4),
+      5 => Ok(Version::V5),
+      6 => Ok(Version::V6),
+      7 => Ok(Version::V7),
+      _ => Err(Error::InvalidVersion(version as i32)),
+    }
+  }
+}
+
+// Format of the node and attribute entries, stored from v3 on
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MetadataFormat {
+  None = 0,
+  // Node and attribute entries link to their siblings, from v6 on the keys block follows the values
+  KeysAndAdjacency = 1,
+  // Used by patched BG3 files, read like None
+  None2 = 2,
+}
+
+impl TryFrom<u32> for MetadataFormat {
+  type Error = Error;
+
+  fn try_from(format: u32) -> Result<Self, Self::Error> {
+    match format {
+      0 => Ok(MetadataFormat::None),
+      1 => Ok(MetadataFormat::KeysAndAdjacency),
+      2 => Ok(MetadataFormat::None2),
+      _ => Err(Error::Unsupported(format!(
+        "LSF metadata format {}",
+        format
+      ))),
     }
   }
 }
@@ -34,6 +67,8 @@ pub struct Header {
   engine_version: i64,
   strings_uncompressed_size: u32,
   strings_size_on_disk: u32,
+  keys_uncompressed_size: u32,
+  keys_size_on_disk: u32,
   nodes_uncompressed_size: u32,
   nodes_size_on_disk: u32,
   attributes_uncompressed_size: u32,
@@ -41,7 +76,9 @@ pub struct Header {
   values_uncompressed_size: u32,
   values_size_on_disk: u32,
   compression_flags: u8,
-  has_sibling_data: u32,
+  metadata_format: MetadataFormat,
+  unknown2: u8,
+  unknown3: u16,
 }
 
 impl Header {
@@ -77,6 +114,23 @@ impl Header {
     self.strings_size_on_disk = strings_size_on_disk;
   }
 
+  // The keys block is part of the header from v6 on
+  pub fn keys_uncompressed_size(&self) -> u32 {
+    self.keys_uncompressed_size
+  }
+
+  pub fn set_keys_uncompressed_size(&mut self, keys_uncompressed_size: u32) {
+    self.keys_uncompressed_size = keys_uncompressed_size;
+  }
+
+  pub fn keys_size_on_disk(&self) -> u32 {
+    self.keys_size_on_disk
+  }
+
+  pub fn set_keys_size_on_disk(&mut self, keys_size_on_disk: u32) {
+    self.keys_size_on_disk = keys_size_on_disk;
+  }
+
   pub fn nodes_uncompressed_size(&self) -> u32 {
     self.nodes_uncompressed_size
   }
@@ -133,12 +187,38 @@ impl Header {
     self.compression_flags = compression_flags;
   }
 
-  pub fn has_sibling_data(&self) -> u32 {
-    self.has_sibling_data
+  pub fn metadata_format(&self) -> MetadataFormat {
+    self.metadata_format
+  }
+
+  pub fn set_metadata_format(&mut self, metadata_format: MetadataFormat) {
+    self.metadata_format = metadata_format;
+  }
+
+  // Node and attribute entries link to their siblings
+  pub fn has_sibling_data(&self) -> bool {
+    self.version >= Version::V3 && self.metadata_format == MetadataFormat::KeysAndAdjacency
+  }
+
+  // The values are followed by the keys block
+  pub fn has_keys(&self) -> bool {
+    self.version >= Version::V6 && self.has_sibling_data()
+  }
+
+  pub fn unknown2(&self) -> u8 {
+    self.unknown2
+  }
+
+  pub fn set_unknown2(&mut self, unknown2: u8) {
+    self.unknown2 = unknown2;
+  }
+
+  pub fn unknown3(&self) -> u16 {
+    self.unknown3
   }
 
-  pub fn set_has_sibling_data(&mut self, has_sibling_data: u32) {
-    self.has_sibling_data = has_sibling_data;
+  pub fn set_unknown3(&mut self, unknown3: u16) {
+    self.unknown3 = unknown3;
   }
 }
 
@@ -149,6 +229,8 @@ impl Default for Header {
       engine_version: 0,
       strings_uncompressed_size: 0,
       strings_size_on_disk: 0,
+      keys_uncompressed_size: 0,
+      keys_size_on_disk: 0,
       nodes_uncompressed_size: 0,
       nodes_size_on_disk: 0,
       attributes_uncompressed_size: 0,
@@ -156,7 +238,9 @@ impl Default for Header {
       values_uncompressed_size: 0,
       values_size_on_disk: 0,
       compression_flags: 0,
-      has_sibling_data: 0,
+      metadata_format: MetadataFormat::None,
+      unknown2: 0,
+      unknown3: 0,
     }
   }
 }
@@ -168,6 +252,8 @@ impl Debug for Header {
       .field("engine_version", &self.engine_version)
       .field("strings_uncompressed_size", &self.strings_uncompressed_size)
       .field("strings_size_on_disk", &self.strings_size_on_disk)
+      .field("keys_uncompressed_size", &self.keys_uncompressed_size)
+      .field("keys_size_on_disk", &self.keys_size_on_disk)
       .field("nodes_uncompressed_size", &self.nodes_uncompressed_size)
       .field("nodes_size_on_disk", &self.nodes_size_on_disk)
       .field(
@@ -178,7 +264,9 @@ impl Debug for Header {
       .field("values_uncompressed_size", &self.values_uncompressed_size)
       .field("values_size_on_disk", &self.values_size_on_disk)
       .field("compression_flags", &self.compression_flags)
-      .field("has_sibling_data", &self.has_sibling_data)
+      .field("metadata_format", &self.metadata_format)
+      .field("unknown2", &self.unknown2)
+      .field("unknown3", &self.unknown3)
       .finish()
   }
 }
@@ -0,0 +1,193 @@
+use crc32fast::hash;
+
+use crate::error::Error;
+
+use super::{
+  context::Context,
+  header::{MetadataFormat, Version},
+};
+
+// A block as it was stored in the file. Compressed blocks are kept, so they can be written again
+// unchanged when their contents didn't change, even if the compressor gives different output.
+#[derive(Debug, Clone)]
+pub struct Block {
+  crc: u32,
+  uncompressed_size: u32,
+  size_on_disk: u32,
+  stored: Vec<u8>,
+}
+
+impl Block {
+  pub fn new(uncompressed: &[u8], stored: Vec<u8>) -> Self {
+    Self {
+      crc: hash(uncompressed),
+      uncompressed_size: uncompressed.len() as u32,
+      size_on_disk: stored.len() as u32,
+      stored,
+    }
+  }
+
+  // Blocks with a size on disk of zero are stored uncompressed
+  pub fn new_uncompressed(uncompressed: &[u8]) -> Self {
+    Self {
+      crc: hash(uncompressed),
+      uncompressed_size: uncompressed.len() as u32,
+      size_on_disk: 0,
+      stored: vec![],
+    }
+  }
+
+  pub fn crc(&self) -> u32 {
+    self.crc
+  }
+
+  pub fn uncompressed_size(&self) -> u32 {
+    self.uncompressed_size
+  }
+
+  pub fn size_on_disk(&self) -> u32 {
+    self.size_on_disk
+  }
+
+  pub fn stored(&self) -> &Vec<u8> {
+    &self.stored
+  }
+
+  pub fn matches(&self, uncompressed: &[u8]) -> bool {
+    self.uncompressed_size == uncompressed.len() as u32 && self.crc == hash(uncompressed)
+  }
+}
+
+// How a resource was laid out in the LSF file it was read from. Writing a resource reuses its
+// layout, so an unmodified resource is written back byte for byte.
+#[derive(Debug, Clone)]
+pub struct Layout {
+  version: Version,
+  engine_version: i64,
+  compression_flags: u8,
+  metadata_format: MetadataFormat,
+  unknown2: u8,
+  unknown3: u16,
+  string_lists: Vec<Vec<String>>,
+  // Attribute names of every node in file order as handles into the string table, indexed like the
+  // nodes of the resource
+  attribute_handles: Vec<Vec<(i32, i32)>>,
+  // Strings, nodes, attributes, values and from v6 on keys
+  blocks: Vec<Block>,
+}
+
+impl Layout {
+  pub fn from_context(context: &Context) -> Result<Self, Error> {
+    let header = context.header();
+
+    let mut attribute_handles = Vec::with_capacity(context.node_infos().len());
+    for node_info in context.node_infos() {
+      let mut handles = vec![];
+      let mut attribute_index = node_info.first_attribute_index();
+      while attribute_index != -1 {
+        let attribute_info = context
+          .attribute_infos()
+          .get(attribute_index as usize)
+          .ok_or(Error::InvalidAttributeIndex(attribute_index))?;
+        let handle = (attribute_info.name_index(), attribute_info.name_offset());
+        context.string(handle.0, handle.1)?;
+        handles.push(handle);
+        attribute_index = attribute_info.next_attribute_index();
+      }
+      attribute_handles.push(handles);
+    }
+
+    Ok(Self {
+      version: *header.version(),
+      engine_version: header.engine_version(),
+      compression_flags: header.compression_flags(),
+      metadata_format: header.metadata_format(),
+      unknown2: header.unknown2(),
+      unknown3: header.unknown3(),
+      string_lists: context.string_lists().clone(),
+      attribute_handles,
+      blocks: context.blocks().clone(),
+    })
+  }
+
+  pub fn version(&self) -> &Version {
+    &self.version
+  }
+
+  pub fn engine_version(&self) -> i64 {
+    self.engine_version
+  }
+
+  pub fn compression_flags(&self) -> u8 {
+    self.compression_flags
+  }
+
+  pub fn set_compression_flags(&mut self, compression_flags: u8) {
+    self.compression_flags = compression_flags;
+  }
+
+  pub fn metadata_format(&self) -> MetadataFormat {
+    self.metadata_format
+  }
+
+  pub fn set_metadata_format(&mut self, metadata_format: MetadataFormat) {
+    self.metadata_format = metadata_format;
+  }
+
+  pub fn unknown2(&self) -> u8 {
+    self.unknown2
+  }
+
+  pub fn set_unknown2(&mut self, unknown2: u8) {
+    self.unknown2 = unknown2;
+  }
+
+  pub fn unknown3(&self) -> u16 {
+    self.unknown3
+  }
+
+  pub fn set_unknown3(&mut self, unknown3: u16) {
+    self.unknown3 = unknown3;
+  }
+
+  pub fn string_lists(&self) -> &Vec<Vec<String>> {
+    &self.string_lists
+  }
+
+  pub fn string_lists_mut(&mut self) -> &mut Vec<Vec<String>> {
+    &mut self.string_lists
+  }
+
+  pub fn attribute_handles(&self) -> &Vec<Vec<(i32, i32)>> {
+    &self.attribute_handles
+  }
+
+  pub fn attribute_handles_mut(&mut self) -> &mut Vec<Vec<(i32, i32)>> {
+    &mut self.attribute_handles
+  }
+
+  // Attribute names of a node in file order, handles that aren't in the string table are skipped
+  pub fn attribute_names(&self, index: usize) -> Option<Vec<&str>> {
+    let handles = self.attribute_handles.get(index)?;
+    Some(
+      handles
+        .iter()
+        .filter_map(|(name_index, name_offset)| {
+          self
+            .string_lists
+            .get(*name_index as usize)?
+            .get(*name_offset as usize)
+            .map(String::as_str)
+        })
+        .collect(),
+    )
+  }
+
+  pub fn blocks(&self) -> &Vec<Block> {
+    &self.blocks
+  }
+
+  pub fn blocks_mut(&mut self) -> &mut Vec<Block> {
+    &mut self.blocks
+  }
+}
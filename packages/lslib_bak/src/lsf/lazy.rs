@@ -0,0 +1,319 @@
+use std::{fmt::Debug, io::Cursor, sync::OnceLock};
+
+use crate::{
+  error::Error,
+  resource::{metadata::Metadata, node::data::Data, reader::ResourceReader, Resource},
+  util::arena::{Arena, ArenaReader, ArenaWriter, Index, RecursiveIter},
+};
+
+use super::{context::Context, header::Version, layout::Layout, read::LsfReadExt};
+
+// Resource read from LSF that decodes a node, its name and attributes, when it's first accessed.
+// Only the uncompressed values block and the node and attribute tables are kept in memory.
+pub struct LazyResource {
+  context: Context,
+  values: Vec<u8>,
+  metadata: Metadata,
+  parent_indexes: Vec<Option<Index>>,
+  child_indexes: Vec<Vec<Index>>,
+  root_indexes: Vec<Index>,
+  nodes: Vec<OnceLock<Data>>,
+}
+
+impl LazyResource {
+  // Checks the tree structure, the names, the attribute chains and that every value lies within
+  // the values block. Values are only decoded when their node is accessed, see try_value.
+  pub fn new(context: Context, values: Vec<u8>) -> Result<Self, Error> {
+    check_attributes(&context, &values)?;
+    let node_count = context.node_infos().len();
+    let mut parent_indexes = Vec::with_capacity(node_count);
+    let mut child_indexes = vec![vec![]; node_count];
+    let mut root_indexes = vec![];
+    for (index, node_info) in context.node_infos().iter().enumerate() {
+      context.string(node_info.name_index(), node_info.name_offset())?;
+      match node_info.parent_index() {
+        -1 => {
+          root_indexes.push(index);
+          parent_indexes.push(None);
+        }
+        // Parents precede their children
+        parent_index if parent_index >= 0 && (parent_index as usize) < index => {
+          child_indexes[parent_index as usize].push(index);
+          parent_indexes.push(Some(parent_index as usize));
+        }
+        parent_index => return Err(Error::InvalidNodeIndex(parent_index)),
+      }
+    }
+
+    let header = context.header();
+    let metadata =
+      Metadata::from_packed_version(header.engine_version(), *header.version() >= Version::V5);
+    Ok(Self {
+      context,
+      values,
+      metadata,
+      parent_indexes,
+      child_indexes,
+      root_indexes,
+      nodes: (0..node_count).map(|_| OnceLock::new()).collect(),
+    })
+  }
+
+  pub fn metadata(&self) -> &Metadata {
+    &self.metadata
+  }
+
+  pub fn context(&self) -> &Context {
+    &self.context
+  }
+
+  pub fn is_decoded(&self, index: Index) -> bool {
+    self.nodes[index].get().is_some()
+  }
+
+  // Decodes the node if it hasn't been yet. Values that don't decode, e.g. with an unknown type id,
+  // are reported here.
+  pub fn try_value(&self, index: Index) -> Result<&Data, Error> {
+    if let Some(data) = self.nodes[index].get() {
+      return Ok(data);
+    }
+    let node_info = &self.context.node_infos()[index];
+    let data = Cursor::new(&self.values).read_lsf_node_data(node_info, &self.context)?;
+    Ok(self.nodes[index].get_or_init(|| data))
+  }
+
+  // Decodes the remaining nodes. The resource keeps the layout like one read with
+  // read_lsf_resource_with_layout, except for the stored blocks.
+  pub fn into_resource(self) -> Result<Resource, Error> {
+    let mut cursor = Cursor::new(&self.values);
+    let mut arena = Arena::with_capacity(self.nodes.len());
+    for (index, node) in self.nodes.into_iter().enumerate() {
+      let data = match node.into_inner() {
+        Some(data) => data,
+        None => cursor.read_lsf_node_data(&self.context.node_infos()[index], &self.context)?,
+      };
+      arena.alloc(data, self.parent_indexes[index]);
+    }
+
+    let mut resource = Resource::new_with_arena(arena);
+    resource.set_metadata(self.metadata);
+    resource.set_lsf_layout(Some(Layout::from_context(&self.context)?));
+    Ok(resource)
+  }
+}
+
+// ArenaReader can't return errors, so value panics if the node doesn't decode. LazyResource::new
+// rules out broken structure, names and value ranges, use try_value to handle values that don't
+// decode.
+impl ArenaReader<Data> for LazyResource {
+  fn size(&self) -> usize {
+    self.nodes.len()
+  }
+
+  fn parent_index(&self, index: Index) -> Option<Index> {
+    self.parent_indexes[index]
+  }
+
+  fn child_indexes(&self, index: Index) -> &Vec<Index> {
+    &self.child_indexes[index]
+  }
+
+  fn value(&self, index: Index) -> &Data {
+    self
+      .try_value(index)
+      .unwrap_or_else(|error| panic!("Invalid LSF node {}: {:?}", index, error))
+  }
+
+  fn root_indexes(&self) -> &Vec<Index> {
+    &self.root_indexes
+  }
+
+  fn recursive_iter(&self, index: Index) -> RecursiveIter<'_, Data> {
+    RecursiveIter::new(self, index)
+  }
+}
+
+impl ResourceReader for LazyResource {
+  // Names are looked up in the string table, without decoding the attributes
+  fn node_name(&self, index: Index) -> &str {
+    let node_info = &self.context.node_infos()[index];
+    self
+      .context
+      .string(node_info.name_index(), node_info.name_offset())
+      .unwrap()
+  }
+}
+
+fn check_attributes(context: &Context, values: &[u8]) -> Result<(), Error> {
+  let mut visited = vec![false; context.attribute_infos().len()];
+  for node_info in context.node_infos() {
+    if node_info.key_name_index() != -1 {
+      context.string(node_info.key_name_index(), node_info.key_name_offset())?;
+    }
+
+    let mut attribute_index = node_info.first_attribute_index();
+    while attribute_index != -1 {
+      // Attributes belong to a single chain, which also rules out cycles
+      let attribute_info = usize::try_from(attribute_index)
+        .ok()
+        .filter(|index| visited.get(*index) == Some(&false))
+        .map(|index| {
+          visited[index] = true;
+          &context.attribute_infos()[index]
+        })
+        .ok_or(Error::InvalidAttributeIndex(attribute_index))?;
+      context.string(attribute_info.name_index(), attribute_info.name_offset())?;
+
+      let start = attribute_info.data_offset() as u64;
+      let length = attribute_info.length() as u64;
+      if start + length > values.len() as u64 {
+        return Err(Error::InvalidValue(
+          attribute_info.type_id(),
+          format!("{} bytes at offset {}", length, start),
+        ));
+      }
+      attribute_index = attribute_info.next_attribute_index();
+    }
+  }
+  Ok(())
+}
+
+impl Debug for LazyResource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("LazyResource")
+      .field("node_count", &self.size())
+      .field("roots", &self.root_indexes)
+      .field(
+        "decoded",
+        &self
+          .nodes
+          .iter()
+          .filter(|node| node.get().is_some())
+          .count(),
+      )
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    compression::{Level, Method},
+    lsb::read::LsbReadExt,
+    lsf::write::LsfWriteExt,
+    resource::node::selector::Selector,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_lazy_resource() {
+    let lsb =
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/profile.lsb");
+    let lsb_resource = Cursor::new(lsb).read_lsb_resource().unwrap();
+    let mut lsf = vec![];
+    lsf
+      .write_lsf_resource_compressed(&lsb_resource, Version::V6, Method::Lz4, Level::Default)
+      .unwrap();
+    let resource = Cursor::new(&lsf).read_lsf_resource().unwrap();
+
+    let lazy = Cursor::new(&lsf).read_lsf_lazy_resource().unwrap();
+    assert!(lazy.context().blocks().is_empty());
+    assert_eq!(lazy.size(), resource.size());
+    assert_eq!(lazy.root_indexes(), resource.root_indexes());
+    assert_eq!(
+      lazy.metadata().packed_version(true),
+      resource.metadata().packed_version(true)
+    );
+
+    // Structure and names don't decode nodes
+    let names: Vec<_> = lazy
+      .recursive_iter(lazy.root_indexes()[0])
+      .map(|index| lazy.node_name(index).to_owned())
+      .collect();
+    let expected_names: Vec<_> = resource
+      .recursive_iter(resource.root_indexes()[0])
+      .map(|index| resource.value(index).name().to_owned())
+      .collect();
+    assert_eq!(names, expected_names);
+    let found = lazy.find(&Selector::Name(&names[names.len() - 1]));
+    assert!(!found.is_empty());
+    assert!((0..lazy.size()).all(|index| !lazy.is_decoded(index)));
+
+    let index = found[0];
+    assert_eq!(lazy.value(index), resource.value(index));
+    assert!(lazy.is_decoded(index));
+    assert_eq!(
+      (0..lazy.size())
+        .filter(|&index| lazy.is_decoded(index))
+        .count(),
+      1
+    );
+    if let Some((name, attribute)) = resource.value(index).attributes().iter().next() {
+      assert_eq!(lazy.attribute_value(index, name), Some(attribute.value()));
+      let selector = Selector::AttributeEquals(name, attribute.value().clone());
+      assert!(lazy.matches(index, &selector));
+    }
+    assert_eq!(lazy.full_path(index), resource.full_path(index));
+
+    // The decoded resource is written back byte for byte
+    let decoded = lazy.into_resource().unwrap();
+    for index in 0..resource.size() {
+      assert_eq!(decoded.value(index), resource.value(index));
+      assert_eq!(decoded.parent_index(index), resource.parent_index(index));
+    }
+    let mut rewritten = vec![];
+    rewritten.write_lsf_resource_with_layout(&decoded).unwrap();
+    assert_eq!(rewritten, lsf);
+  }
+
+  #[test]
+  fn test_lazy_resource_invalid() {
+    let lsb =
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/profile.lsb");
+    let lsb_resource = Cursor::new(lsb).read_lsb_resource().unwrap();
+    let mut lsf = vec![];
+    lsf.write_lsf_resource(&lsb_resource, Version::V6).unwrap();
+    let read_context = || {
+      let mut context = Context::new();
+      let values = Cursor::new(&lsf).read_lsf_context(&mut context).unwrap();
+      (context, values)
+    };
+
+    // Values past the end of the values block
+    let (context, mut values) = read_context();
+    values.pop();
+    assert!(matches!(
+      LazyResource::new(context, values),
+      Err(Error::InvalidValue(_, _))
+    ));
+
+    // An attribute chain that loops back on itself
+    let (mut context, values) = read_context();
+    let attribute_info = &mut context.attribute_infos_mut()[0];
+    attribute_info.set_next_attribute_index(0);
+    assert!(matches!(
+      LazyResource::new(context, values),
+      Err(Error::InvalidAttributeIndex(0))
+    ));
+
+    // Values that don't decode are only reported once their node is decoded
+    let (mut context, values) = read_context();
+    let attribute_info = &mut context.attribute_infos_mut()[0];
+    attribute_info.set_type_id(255);
+    let index = context
+      .node_infos()
+      .iter()
+      .position(|node_info| node_info.first_attribute_index() == 0)
+      .unwrap();
+    let lazy = LazyResource::new(context, values).unwrap();
+    assert!((0..lazy.size()).all(|index| !lazy.is_decoded(index)));
+    assert!(matches!(
+      lazy.try_value(index),
+      Err(Error::InvalidTypeId(255))
+    ));
+    assert!(!lazy.is_decoded(index));
+    let other = (0..lazy.size()).find(|&other| other != index).unwrap();
+    assert!(lazy.try_value(other).is_ok());
+  }
+}
@@ -1,6 +1,9 @@
 pub mod attribute;
 pub mod context;
 pub mod header;
+pub mod layout;
+pub mod lazy;
 pub mod node;
 pub mod read;
+pub mod string_table;
 pub mod write;
@@ -6,6 +6,8 @@ pub struct NodeInfo {
   name_index: i32,
   name_offset: i32,
   first_attribute_index: i32,
+  key_name_index: i32,
+  key_name_offset: i32,
 }
 
 impl NodeInfo {
@@ -44,6 +46,23 @@ impl NodeInfo {
   pub fn set_first_attribute_index(&mut self, first_attribute_index: i32) {
     self.first_attribute_index = first_attribute_index;
   }
+
+  // Name of the key attribute, from the keys block
+  pub fn key_name_index(&self) -> i32 {
+    self.key_name_index
+  }
+
+  pub fn set_key_name_index(&mut self, key_name_index: i32) {
+    self.key_name_index = key_name_index;
+  }
+
+  pub fn key_name_offset(&self) -> i32 {
+    self.key_name_offset
+  }
+
+  pub fn set_key_name_offset(&mut self, key_name_offset: i32) {
+    self.key_name_offset = key_name_offset;
+  }
 }
 
 impl Default for NodeInfo {
@@ -53,6 +72,8 @@ impl Default for NodeInfo {
       name_index: -1,
       name_offset: -1,
       first_attribute_index: -1,
+      key_name_index: -1,
+      key_name_offset: -1,
     }
   }
 }
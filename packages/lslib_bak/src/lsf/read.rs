@@ -6,8 +6,10 @@ use crate::{
   compression::{read::DecompressReadExt, CompressionOptions},
   error::Error,
   resource::{
+    metadata::Metadata,
     node::{attribute::Attribute, data::Data},
     value::{read::ResourceValueReadExt, TranslatedFsStringArgument, Value},
+    visitor::{ResourceVisitor, Visit},
     Resource,
   },
   util::{
@@ -19,19 +21,116 @@ use crate::{
 use super::{
   attribute::AttributeInfo,
   context::Context,
-  header::{Version, SIGNATURE},
+  header::{MetadataFormat, Version, SIGNATURE},
+  layout::{Block, Layout},
+  lazy::LazyResource,
   node::NodeInfo,
 };
 
 pub trait LsfReadExt: Read + Seek {
+  // Reads the blocks and tables, nodes are decoded when they're accessed. Stored blocks aren't
+  // kept, see read_lsf_resource_with_layout.
+  fn read_lsf_lazy_resource(&mut self) -> Result<LazyResource, Error> {
+    let mut context = Context::new();
+    let values = self.read_lsf_context(&mut context)?;
+    LazyResource::new(context, values)
+  }
+
+  // Traverses the resource without decoding it into nodes
+  fn visit_lsf_resource(&mut self, visitor: &mut dyn ResourceVisitor) -> Result<(), Error> {
+    let mut context = Context::new();
+    let values = self.read_lsf_context(&mut context)?;
+
+    let mut child_indexes = vec![vec![]; context.node_infos().len()];
+    let mut root_indexes = vec![];
+    for (index, node_info) in context.node_infos().iter().enumerate() {
+      match node_info.parent_index() {
+        -1 => root_indexes.push(index),
+        // Parents precede their children
+        parent_index if parent_index >= 0 && (parent_index as usize) < index => {
+          child_indexes[parent_index as usize].push(index)
+        }
+        parent_index => return Err(Error::InvalidNodeIndex(parent_index)),
+      }
+    }
+
+    let mut cursor = Cursor::new(values);
+    for index in root_indexes {
+      if cursor.visit_lsf_node(index, &child_indexes, &context, visitor)? == Visit::Stop {
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  fn visit_lsf_node(
+    &mut self,
+    index: usize,
+    child_indexes: &[Vec<usize>],
+    context: &Context,
+    visitor: &mut dyn ResourceVisitor,
+  ) -> Result<Visit, Error> {
+    let node_info = &context.node_infos()[index];
+    let name = context.string(node_info.name_index(), node_info.name_offset())?;
+    let mut visit = visitor.enter_node(name);
+    if visit == Visit::Stop {
+      return Ok(Visit::Stop);
+    }
+
+    let mut attribute_index = node_info.first_attribute_index();
+    while visit == Visit::Continue && attribute_index != -1 {
+      let attribute_info = context
+        .attribute_infos()
+        .get(attribute_index as usize)
+        .ok_or(Error::InvalidAttributeIndex(attribute_index))?;
+      self.seek(SeekFrom::Start(attribute_info.data_offset() as u64))?;
+      let attribute = self.read_lsf_attribute(attribute_info, context)?;
+      let attribute_name =
+        context.string(attribute_info.name_index(), attribute_info.name_offset())?;
+      visit = visitor.attribute(attribute_name, attribute.value());
+      if visit == Visit::Stop {
+        return Ok(Visit::Stop);
+      }
+      attribute_index = attribute_info.next_attribute_index();
+    }
+
+    if visit == Visit::Continue {
+      for &child_index in &child_indexes[index] {
+        if self.visit_lsf_node(child_index, child_indexes, context, visitor)? == Visit::Stop {
+          return Ok(Visit::Stop);
+        }
+      }
+    }
+    Ok(match visitor.exit_node(name) {
+      Visit::Stop => Visit::Stop,
+      _ => Visit::Continue,
+    })
+  }
+
   fn read_lsf_resource(&mut self) -> Result<Resource, Error> {
+    self.read_lsf_resource_from_context(&mut Context::new())
+  }
+
+  // Also keeps the layout of the file, so write_lsf_resource_with_layout can write an unmodified
+  // resource back byte for byte
+  fn read_lsf_resource_with_layout(&mut self) -> Result<Resource, Error> {
     let mut context = Context::new();
-    self.read_lsf_header(&mut context)?;
-    self.read_lsf_strings(&mut context)?;
-    self.read_lsf_node_infos(&mut context)?;
-    self.read_lsf_attribute_infos(&mut context)?;
-    let arena = self.read_lsf_node_arena(&mut context)?;
-    Ok(Resource::new_with_arena(arena))
+    context.set_keep_blocks(true);
+    let mut resource = self.read_lsf_resource_from_context(&mut context)?;
+    resource.set_lsf_layout(Some(Layout::from_context(&context)?));
+    Ok(resource)
+  }
+
+  fn read_lsf_resource_from_context(&mut self, context: &mut Context) -> Result<Resource, Error> {
+    let values = self.read_lsf_context(context)?;
+    let arena = Cursor::new(values).read_lsf_node_arena(context)?;
+    let header = context.header();
+    let mut resource = Resource::new_with_arena(arena);
+    resource.set_metadata(Metadata::from_packed_version(
+      header.engine_version(),
+      *header.version() >= Version::V5,
+    ));
+    Ok(resource)
   }
 
   fn read_lsf_header(&mut self, context: &mut Context) -> Result<(), Error> {
@@ -42,7 +141,7 @@ pub trait LsfReadExt: Read + Seek {
       return Err(Error::InvalidSignature(signature, SIGNATURE));
     }
 
-    let version: Version = self.read_u32::<LittleEndian>()?.into();
+    let version = Version::try_from(self.read_u32::<LittleEndian>()?)?;
     header.set_version(version);
 
     if version >= Version::V5 {
@@ -63,9 +162,9 @@ pub trait LsfReadExt: Read + Seek {
       let values_uncompressed_size = self.read_u32::<LittleEndian>()?;
       let values_size_on_disk = self.read_u32::<LittleEndian>()?;
       let compression_flags = self.read_u8()?;
-      let _unknown2 = self.read_u8()?;
-      let _unknown3 = self.read_u16::<LittleEndian>()?;
-      let has_sibling_data = self.read_u32::<LittleEndian>()?;
+      let unknown2 = self.read_u8()?;
+      let unknown3 = self.read_u16::<LittleEndian>()?;
+      let metadata_format = MetadataFormat::try_from(self.read_u32::<LittleEndian>()?)?;
 
       header.set_strings_uncompressed_size(strings_uncompressed_size);
       header.set_strings_size_on_disk(strings_size_on_disk);
@@ -76,11 +175,14 @@ pub trait LsfReadExt: Read + Seek {
       header.set_values_uncompressed_size(values_uncompressed_size);
       header.set_values_size_on_disk(values_size_on_disk);
       header.set_compression_flags(compression_flags);
-      header.set_has_sibling_data(has_sibling_data);
+      header.set_unknown2(unknown2);
+      header.set_unknown3(unknown3);
+      header.set_metadata_format(metadata_format);
     } else {
       let strings_uncompressed_size = self.read_u32::<LittleEndian>()?;
       let strings_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let _unknown1 = self.read_u64::<LittleEndian>()?;
+      let keys_uncompressed_size = self.read_u32::<LittleEndian>()?;
+      let keys_size_on_disk = self.read_u32::<LittleEndian>()?;
       let nodes_uncompressed_size = self.read_u32::<LittleEndian>()?;
       let nodes_size_on_disk = self.read_u32::<LittleEndian>()?;
       let attributes_uncompressed_size = self.read_u32::<LittleEndian>()?;
@@ -88,12 +190,14 @@ pub trait LsfReadExt: Read + Seek {
       let values_uncompressed_size = self.read_u32::<LittleEndian>()?;
       let values_size_on_disk = self.read_u32::<LittleEndian>()?;
       let compression_flags = self.read_u8()?;
-      let _unknown2 = self.read_u8()?;
-      let _unknown3 = self.read_u16::<LittleEndian>()?;
-      let has_sibling_data = self.read_u32::<LittleEndian>()?;
+      let unknown2 = self.read_u8()?;
+      let unknown3 = self.read_u16::<LittleEndian>()?;
+      let metadata_format = MetadataFormat::try_from(self.read_u32::<LittleEndian>()?)?;
 
       header.set_strings_uncompressed_size(strings_uncompressed_size);
       header.set_strings_size_on_disk(strings_size_on_disk);
+      header.set_keys_uncompressed_size(keys_uncompressed_size);
+      header.set_keys_size_on_disk(keys_size_on_disk);
       header.set_nodes_uncompressed_size(nodes_uncompressed_size);
       header.set_nodes_size_on_disk(nodes_size_on_disk);
       header.set_attributes_uncompressed_size(attributes_uncompressed_size);
@@ -101,21 +205,55 @@ pub trait LsfReadExt: Read + Seek {
       header.set_values_uncompressed_size(values_uncompressed_size);
       header.set_values_size_on_disk(values_size_on_disk);
       header.set_compression_flags(compression_flags);
-      header.set_has_sibling_data(has_sibling_data);
+      header.set_unknown2(unknown2);
+      header.set_unknown3(unknown3);
+      header.set_metadata_format(metadata_format);
     }
 
     Ok(())
   }
 
+  // Blocks with a size on disk of zero are stored uncompressed. The stored block is kept in the
+  // context if it captures the layout of the resource.
+  fn read_lsf_block(
+    &mut self,
+    context: &mut Context,
+    size_on_disk: u32,
+    uncompressed_size: u32,
+    compression_options: CompressionOptions,
+  ) -> Result<Vec<u8>, Error> {
+    if size_on_disk == 0 {
+      let uncompressed = self.read_bytes(uncompressed_size as usize)?;
+      if context.keep_blocks() {
+        context
+          .blocks_mut()
+          .push(Block::new_uncompressed(&uncompressed));
+      }
+      return Ok(uncompressed);
+    }
+    let stored = self.read_bytes(size_on_disk as usize)?;
+    let uncompressed = Cursor::new(&stored).read_decompressed(
+      size_on_disk as usize,
+      uncompressed_size as usize,
+      compression_options,
+    )?;
+    if context.keep_blocks() {
+      context.blocks_mut().push(Block::new(&uncompressed, stored));
+    }
+    Ok(uncompressed)
+  }
+
   fn read_lsf_strings(&mut self, context: &mut Context) -> Result<(), Error> {
     let header = context.header();
     let uncompressed_size = header.strings_uncompressed_size();
     let size_on_disk = header.strings_size_on_disk();
 
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
-      header.compression_flags().into(),
+    let compression_options = header.compression_flags().into();
+    let uncompressed = self.read_lsf_block(
+      context,
+      size_on_disk,
+      uncompressed_size,
+      compression_options,
     )?;
 
     let mut cursor = Cursor::new(uncompressed);
@@ -143,13 +281,14 @@ pub trait LsfReadExt: Read + Seek {
     let uncompressed_size = header.nodes_uncompressed_size();
     let size_on_disk = header.nodes_size_on_disk();
     let chunks_allowed = header.version() >= &Version::V2;
-    let has_sibling_data = header.version() >= &Version::V3 && header.has_sibling_data() == 1;
+    let has_sibling_data = header.has_sibling_data();
 
     let mut compression_options: CompressionOptions = header.compression_flags().into();
     compression_options.set_chunked(chunks_allowed);
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
+    let uncompressed = self.read_lsf_block(
+      context,
+      size_on_disk,
+      uncompressed_size,
       compression_options,
     )?;
 
@@ -189,13 +328,14 @@ pub trait LsfReadExt: Read + Seek {
     let uncompressed_size = header.attributes_uncompressed_size();
     let size_on_disk = header.attributes_size_on_disk();
     let chunks_allowed = header.version() >= &Version::V2;
-    let has_sibling_data = header.version() >= &Version::V3 && header.has_sibling_data() == 1;
+    let has_sibling_data = header.has_sibling_data();
 
     let mut compression_options: CompressionOptions = header.compression_flags().into();
     compression_options.set_chunked(chunks_allowed);
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
+    let uncompressed = self.read_lsf_block(
+      context,
+      size_on_disk,
+      uncompressed_size,
       compression_options,
     )?;
 
@@ -267,7 +407,18 @@ pub trait LsfReadExt: Read + Seek {
     Ok(())
   }
 
-  fn read_lsf_node_arena(&mut self, context: &mut Context) -> Result<Arena<Data>, Error> {
+  // Reads everything but the values of the attributes, which are returned as uncompressed block
+  fn read_lsf_context(&mut self, context: &mut Context) -> Result<Vec<u8>, Error> {
+    self.read_lsf_header(context)?;
+    self.read_lsf_strings(context)?;
+    self.read_lsf_node_infos(context)?;
+    self.read_lsf_attribute_infos(context)?;
+    let values = self.read_lsf_values(context)?;
+    self.read_lsf_keys(context)?;
+    Ok(values)
+  }
+
+  fn read_lsf_values(&mut self, context: &mut Context) -> Result<Vec<u8>, Error> {
     let header = context.header();
     let uncompressed_size = header.values_uncompressed_size();
     let size_on_disk = header.values_size_on_disk();
@@ -275,16 +426,19 @@ pub trait LsfReadExt: Read + Seek {
 
     let mut compression_options: CompressionOptions = header.compression_flags().into();
     compression_options.set_chunked(chunks_allowed);
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
+    self.read_lsf_block(
+      context,
+      size_on_disk,
+      uncompressed_size,
       compression_options,
-    )?;
+    )
+  }
 
-    let mut cursor = Cursor::new(uncompressed);
+  // Decodes all nodes, reading from the values block
+  fn read_lsf_node_arena(&mut self, context: &Context) -> Result<Arena<Data>, Error> {
     let mut node_arena: Arena<Data> = Arena::new();
     for node_info in context.node_infos() {
-      let node_data = cursor.read_lsf_node_data(node_info, context)?;
+      let node_data = self.read_lsf_node_data(node_info, context)?;
       if node_info.parent_index() != -1 {
         let parent_index = node_info.parent_index() as usize;
         node_arena.alloc(node_data, Some(parent_index));
@@ -296,6 +450,39 @@ pub trait LsfReadExt: Read + Seek {
     Ok(node_arena)
   }
 
+  // From v6 on, the values are followed by the names of the key attributes of nodes
+  fn read_lsf_keys(&mut self, context: &mut Context) -> Result<(), Error> {
+    let header = context.header();
+    if !header.has_keys() {
+      return Ok(());
+    }
+    let uncompressed_size = header.keys_uncompressed_size();
+    let size_on_disk = header.keys_size_on_disk();
+
+    let mut compression_options: CompressionOptions = header.compression_flags().into();
+    compression_options.set_chunked(true);
+    let uncompressed = self.read_lsf_block(
+      context,
+      size_on_disk,
+      uncompressed_size,
+      compression_options,
+    )?;
+
+    let mut cursor = Cursor::new(uncompressed);
+    while cursor.position() < uncompressed_size as u64 {
+      let node_index = cursor.read_u32::<LittleEndian>()?;
+      let name_hash_table_index = cursor.read_u32::<LittleEndian>()?;
+      let node_info = context
+        .nodes_infos_mut()
+        .get_mut(node_index as usize)
+        .ok_or(Error::InvalidNodeIndex(node_index as i32))?;
+      node_info.set_key_name_index((name_hash_table_index >> 16) as i32);
+      node_info.set_key_name_offset((name_hash_table_index & 0xffff) as i32);
+    }
+
+    Ok(())
+  }
+
   fn read_lsf_node_data(&mut self, node_info: &NodeInfo, context: &Context) -> Result<Data, Error> {
     let name = context
       .string_lists()
@@ -308,6 +495,18 @@ pub trait LsfReadExt: Read + Seek {
       ))?;
 
     let mut node_data = Data::new(name.to_owned());
+    if node_info.key_name_index() != -1 {
+      let key = context
+        .string_lists()
+        .get(node_info.key_name_index() as usize)
+        .ok_or(Error::InvalidStringIndex(node_info.key_name_index()))?
+        .get(node_info.key_name_offset() as usize)
+        .ok_or(Error::InvalidStringOffset(
+          node_info.key_name_index(),
+          node_info.key_name_offset(),
+        ))?;
+      node_data.set_key(Some(key.to_owned()));
+    }
     if node_info.first_attribute_index() == -1 {
       return Ok(node_data);
     }
@@ -433,3 +632,102 @@ pub trait LsfReadExt: Read + Seek {
 }
 
 impl<R: Read + Seek + ?Sized> LsfReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::WriteBytesExt;
+
+  use crate::util::arena::ArenaReader;
+
+  use super::*;
+
+  // 4.0.9.328
+  const ENGINE_VERSION: i64 = (4 << 55) | (9 << 31) | 328;
+
+  // A v7 file with a Root node holding Level = 12, built by hand so it doesn't depend on the
+  // writer. All blocks are stored uncompressed.
+  fn v7_fixture(metadata_format: u32) -> Vec<u8> {
+    let mut strings = vec![];
+    strings.write_u32::<LittleEndian>(1).unwrap();
+    strings.write_u16::<LittleEndian>(2).unwrap();
+    for string in ["Root", "Level"] {
+      strings
+        .write_u16::<LittleEndian>(string.len() as u16)
+        .unwrap();
+      strings.extend_from_slice(string.as_bytes());
+    }
+    let keys = vec![];
+    let mut nodes = vec![];
+    nodes.write_u32::<LittleEndian>(0).unwrap();
+    nodes.write_i32::<LittleEndian>(0).unwrap();
+    nodes.write_i32::<LittleEndian>(-1).unwrap();
+    let mut attributes = vec![];
+    attributes.write_u32::<LittleEndian>(1).unwrap();
+    attributes.write_u32::<LittleEndian>(4 | 4 << 6).unwrap();
+    attributes.write_i32::<LittleEndian>(0).unwrap();
+    let values = 12i32.to_le_bytes().to_vec();
+
+    let mut lsf = SIGNATURE.to_vec();
+    lsf.write_u32::<LittleEndian>(7).unwrap();
+    lsf.write_i64::<LittleEndian>(ENGINE_VERSION).unwrap();
+    for block in [&strings, &keys, &nodes, &attributes, &values] {
+      lsf.write_u32::<LittleEndian>(block.len() as u32).unwrap();
+      lsf.write_u32::<LittleEndian>(0).unwrap();
+    }
+    lsf.write_u8(0).unwrap();
+    lsf.write_u8(1).unwrap();
+    lsf.write_u16::<LittleEndian>(2).unwrap();
+    lsf.write_u32::<LittleEndian>(metadata_format).unwrap();
+    for block in [&strings, &nodes, &attributes, &values] {
+      lsf.extend_from_slice(block);
+    }
+    lsf
+  }
+
+  #[test]
+  fn test_read_lsf_v7() {
+    let lsf = v7_fixture(2);
+    let mut context = Context::new();
+    Cursor::new(&lsf).read_lsf_header(&mut context).unwrap();
+    let header = context.header();
+    assert_eq!(*header.version(), Version::V7);
+    assert_eq!(header.engine_version(), ENGINE_VERSION);
+    assert_eq!(header.strings_uncompressed_size(), 19);
+    assert_eq!(header.keys_uncompressed_size(), 0);
+    assert_eq!(header.values_uncompressed_size(), 4);
+    assert_eq!(header.unknown2(), 1);
+    assert_eq!(header.unknown3(), 2);
+    assert_eq!(header.metadata_format(), MetadataFormat::None2);
+    assert!(!header.has_sibling_data());
+    assert!(!header.has_keys());
+
+    let resource = Cursor::new(&lsf).read_lsf_resource().unwrap();
+    assert_eq!(resource.metadata().major_version(), 4);
+    assert_eq!(resource.metadata().revision(), 9);
+    assert_eq!(resource.metadata().build_number(), 328);
+    assert_eq!(resource.size(), 1);
+    assert_eq!(resource.value(0).name(), "Root");
+    assert_eq!(
+      resource.value(0).attributes().get("Level").unwrap().value(),
+      &Value::Int(12)
+    );
+  }
+
+  #[test]
+  fn test_read_lsf_invalid_metadata_format() {
+    assert!(matches!(
+      Cursor::new(&v7_fixture(3)).read_lsf_resource(),
+      Err(Error::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn test_read_lsf_invalid_version() {
+    let mut lsf = v7_fixture(0);
+    lsf[4..8].copy_from_slice(&8u32.to_le_bytes());
+    assert!(matches!(
+      Cursor::new(&lsf).read_lsf_resource(),
+      Err(Error::InvalidVersion(8))
+    ));
+  }
+}
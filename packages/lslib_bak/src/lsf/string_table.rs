@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+
+// Number of buckets the string table is written with
+pub const BUCKET_COUNT: usize = 0x200;
+
+pub type StringHasher = fn(&str) -> u32;
+
+// String.GetHashCode of the 64 bit .NET Framework over the UTF-16 code units of the name, the
+// default hasher. LSLib places names with it, and the game loads files written that way. It hasn't
+// been checked against tables of files the game wrote itself, misplaced_names can do that.
+pub fn dotnet_hash(name: &str) -> u32 {
+  let mut hash1: u32 = 5381;
+  let mut hash2: u32 = 5381;
+  let mut units = name.encode_utf16();
+  while let Some(unit) = units.next() {
+    hash1 = ((hash1 << 5).wrapping_add(hash1)) ^ unit as u32;
+    match units.next() {
+      Some(unit) => hash2 = ((hash2 << 5).wrapping_add(hash2)) ^ unit as u32,
+      None => break,
+    }
+  }
+  hash1.wrapping_add(hash2.wrapping_mul(1566083941))
+}
+
+// Folds a 32 bit hash into the 9 bit index of one of the 0x200 buckets
+pub fn fold_hash(hash: u32) -> usize {
+  ((hash & 0x1ff) ^ ((hash >> 9) & 0x1ff) ^ ((hash >> 18) & 0x1ff) ^ ((hash >> 27) & 0x1ff))
+    as usize
+}
+
+// Builds the string table of LSF files. Names are hashed into buckets and referenced by
+// (bucket, offset) handles, which stay valid while names are added since entries are only ever
+// appended to their bucket. See dotnet_hash for how names are placed by default.
+pub struct StringTable {
+  hasher: StringHasher,
+  string_lists: Vec<Vec<String>>,
+  handles: HashMap<String, (i32, i32)>,
+}
+
+impl StringTable {
+  pub fn new() -> Self {
+    Self::new_with_hasher(dotnet_hash)
+  }
+
+  pub fn new_with_hasher(hasher: StringHasher) -> Self {
+    Self {
+      hasher,
+      string_lists: vec![vec![]; BUCKET_COUNT],
+      handles: HashMap::new(),
+    }
+  }
+
+  // Keeps the placement of an existing table, e.g. one read from a file. New names are hashed into
+  // its buckets.
+  pub fn from_string_lists(string_lists: Vec<Vec<String>>) -> Self {
+    Self::from_string_lists_with_hasher(string_lists, dotnet_hash)
+  }
+
+  pub fn from_string_lists_with_hasher(
+    string_lists: Vec<Vec<String>>,
+    hasher: StringHasher,
+  ) -> Self {
+    if string_lists.is_empty() {
+      return Self::new_with_hasher(hasher);
+    }
+
+    let mut handles = HashMap::new();
+    for (name_index, string_list) in string_lists.iter().enumerate() {
+      for (name_offset, name) in string_list.iter().enumerate() {
+        handles
+          .entry(name.to_owned())
+          .or_insert((name_index as i32, name_offset as i32));
+      }
+    }
+    Self {
+      hasher,
+      string_lists,
+      handles,
+    }
+  }
+
+  pub fn string_lists(&self) -> &Vec<Vec<String>> {
+    &self.string_lists
+  }
+
+  pub fn into_string_lists(self) -> Vec<Vec<String>> {
+    self.string_lists
+  }
+
+  pub fn len(&self) -> usize {
+    self.handles.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.handles.is_empty()
+  }
+
+  // Bucket a name is added to
+  pub fn bucket(&self, name: &str) -> usize {
+    fold_hash((self.hasher)(name)) % self.string_lists.len()
+  }
+
+  pub fn get(&self, name: &str) -> Option<(i32, i32)> {
+    self.handles.get(name).copied()
+  }
+
+  pub fn name(&self, name_index: i32, name_offset: i32) -> Option<&str> {
+    self
+      .string_lists
+      .get(name_index as usize)?
+      .get(name_offset as usize)
+      .map(String::as_str)
+  }
+
+  // Returns the handle of a name, adding it to its bucket if it isn't in the table yet
+  pub fn insert(&mut self, name: &str) -> Result<(i32, i32), Error> {
+    if let Some(handle) = self.handles.get(name) {
+      return Ok(*handle);
+    }
+
+    let name_index = self.bucket(name);
+    let string_list = &mut self.string_lists[name_index];
+    if string_list.len() >= u16::MAX as usize || name.len() > u16::MAX as usize {
+      return Err(Error::Unsupported(format!(
+        "LSF string table entry: {}",
+        name
+      )));
+    }
+    let handle = (name_index as i32, string_list.len() as i32);
+    string_list.push(name.to_owned());
+    self.handles.insert(name.to_owned(), handle);
+    Ok(handle)
+  }
+
+  // Names of a table that aren't in the bucket the hasher puts them in. Checking the tables of real
+  // files shows whether a hasher matches the one they were written with.
+  pub fn misplaced_names(string_lists: &[Vec<String>], hasher: StringHasher) -> Vec<&str> {
+    string_lists
+      .iter()
+      .enumerate()
+      .flat_map(|(name_index, string_list)| {
+        string_list
+          .iter()
+          .filter(move |name| fold_hash(hasher(name)) % string_lists.len() != name_index)
+          .map(String::as_str)
+      })
+      .collect()
+  }
+}
+
+impl Default for StringTable {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_dotnet_hash() {
+    // String.Empty.GetHashCode() of the 64 bit .NET Framework
+    assert_eq!(dotnet_hash(""), 371857150);
+  }
+
+  #[test]
+  fn test_string_table() {
+    let mut table = StringTable::new();
+    let names = [
+      "Characters",
+      "Character",
+      "Name",
+      "Level",
+      "Inventory",
+      "Stats",
+    ];
+    let handles: Vec<_> = names
+      .iter()
+      .map(|name| table.insert(name).unwrap())
+      .collect();
+    for name in 0..1000 {
+      table.insert(&format!("Name{}", name)).unwrap();
+    }
+
+    assert_eq!(table.string_lists().len(), BUCKET_COUNT);
+    assert_eq!(table.len(), names.len() + 1000);
+    for (name, handle) in names.iter().zip(handles) {
+      assert_eq!(table.insert(name).unwrap(), handle);
+      assert_eq!(handle.0 as usize, fold_hash(dotnet_hash(name)));
+      assert_eq!(table.name(handle.0, handle.1), Some(*name));
+    }
+    assert!(StringTable::misplaced_names(table.string_lists(), dotnet_hash).is_empty());
+
+    // Existing placement is kept, even where the hash would choose another bucket
+    let mut string_lists = table.into_string_lists();
+    let (name_index, moved) = string_lists
+      .iter()
+      .enumerate()
+      .find(|(_, string_list)| !string_list.is_empty())
+      .map(|(name_index, string_list)| (name_index, string_list[0].clone()))
+      .unwrap();
+    string_lists[name_index].retain(|name| name != &moved);
+    string_lists[(name_index + 1) % BUCKET_COUNT].push(moved.clone());
+    assert_eq!(
+      StringTable::misplaced_names(&string_lists, dotnet_hash),
+      vec![moved.as_str()]
+    );
+    let table = StringTable::from_string_lists(string_lists);
+    assert_eq!(
+      table.get(&moved).unwrap().0 as usize,
+      (name_index + 1) % BUCKET_COUNT
+    );
+
+    let mut table = StringTable::from_string_lists(vec![vec!["Name".to_string()]]);
+    assert_eq!(table.insert("Name").unwrap(), (0, 0));
+    assert_eq!(table.insert("Level").unwrap(), (0, 1));
+  }
+}
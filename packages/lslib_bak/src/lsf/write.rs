@@ -1,474 +1,260 @@
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::{
+  collections::HashMap,
+  io::{Cursor, Write},
+};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 
 use crate::{
-  compression::{read::DecompressReadExt, CompressionOptions},
+  compression::{write::compress, CompressionOptions, Level, Method},
   error::Error,
   resource::{
-    node::{attribute::Attribute, data::Data},
-    value::{
-      read::ResourceValueReadExt, write::ResourceValueWriteExt, TranslatedFsStringArgument, Value,
-    },
+    metadata::Metadata,
+    value::{write::ResourceValueWriteExt, Value},
     Resource,
   },
-  util::{
-    arena::{Arena, ArenaWriter},
-    read::BinaryReadExt,
-    write::BinaryWriteExt,
-  },
+  util::arena::ArenaReader,
 };
 
 use super::{
   attribute::AttributeInfo,
   context::Context,
-  header::{Version, SIGNATURE},
+  header::{MetadataFormat, Version, SIGNATURE},
+  layout::Block,
   node::NodeInfo,
+  string_table::StringTable,
 };
 
-pub trait LsfWriteExt: Write + Seek {
-  fn read_lsf_resource(&mut self) -> Result<Resource, Error> {
-    let mut context = Context::new();
-    self.read_lsf_header(&mut context)?;
-    self.read_lsf_strings(&mut context)?;
-    self.read_lsf_node_infos(&mut context)?;
-    self.read_lsf_attribute_infos(&mut context)?;
-    let arena = self.read_lsf_node_arena(&mut context)?;
-    Ok(Resource::new_with_arena(arena))
-  }
-
-  fn read_lsf_header(&mut self, context: &mut Context) -> Result<(), Error> {
-    let header = context.header_mut();
-    let mut signature = [0u8; 4];
-    self.read_exact(&mut signature)?;
-    if signature != SIGNATURE {
-      return Err(Error::InvalidSignature(signature, SIGNATURE));
-    }
-
-    let version: Version = self.read_u32::<LittleEndian>()?.into();
-    header.set_version(version);
-
-    if version >= Version::V5 {
-      let engine_version = self.read_i64::<LittleEndian>()?;
-      header.set_engine_version(engine_version);
-    } else {
-      let engine_version = self.read_i32::<LittleEndian>()?;
-      header.set_engine_version(engine_version as i64);
-    }
+fn name_hash_table_index(name_index: i32, name_offset: i32) -> u32 {
+  ((name_index as u32) << 16) | (name_offset as u32 & 0xffff)
+}
 
-    if version < Version::V6 {
-      let strings_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let strings_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let nodes_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let nodes_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let attributes_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let attributes_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let values_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let values_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let compression_flags = self.read_u8()?;
-      let _unknown2 = self.read_u8()?;
-      let _unknown3 = self.read_u16::<LittleEndian>()?;
-      let has_sibling_data = self.read_u32::<LittleEndian>()?;
-
-      header.set_strings_uncompressed_size(strings_uncompressed_size);
-      header.set_strings_size_on_disk(strings_size_on_disk);
-      header.set_nodes_uncompressed_size(nodes_uncompressed_size);
-      header.set_nodes_size_on_disk(nodes_size_on_disk);
-      header.set_attributes_uncompressed_size(attributes_uncompressed_size);
-      header.set_attributes_size_on_disk(attributes_size_on_disk);
-      header.set_values_uncompressed_size(values_uncompressed_size);
-      header.set_values_size_on_disk(values_size_on_disk);
-      header.set_compression_flags(compression_flags);
-      header.set_has_sibling_data(has_sibling_data);
-    } else {
-      let strings_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let strings_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let _unknown1 = self.read_u64::<LittleEndian>()?;
-      let nodes_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let nodes_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let attributes_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let attributes_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let values_uncompressed_size = self.read_u32::<LittleEndian>()?;
-      let values_size_on_disk = self.read_u32::<LittleEndian>()?;
-      let compression_flags = self.read_u8()?;
-      let _unknown2 = self.read_u8()?;
-      let _unknown3 = self.read_u16::<LittleEndian>()?;
-      let has_sibling_data = self.read_u32::<LittleEndian>()?;
-
-      header.set_strings_uncompressed_size(strings_uncompressed_size);
-      header.set_strings_size_on_disk(strings_size_on_disk);
-      header.set_nodes_uncompressed_size(nodes_uncompressed_size);
-      header.set_nodes_size_on_disk(nodes_size_on_disk);
-      header.set_attributes_uncompressed_size(attributes_uncompressed_size);
-      header.set_attributes_size_on_disk(attributes_size_on_disk);
-      header.set_values_uncompressed_size(values_uncompressed_size);
-      header.set_values_size_on_disk(values_size_on_disk);
-      header.set_compression_flags(compression_flags);
-      header.set_has_sibling_data(has_sibling_data);
+// Compresses a block, uncompressed and empty blocks are stored with a size on disk of zero. A block
+// with the same contents as the one read from the file is written as it was stored.
+fn lsf_block(
+  bytes: Vec<u8>,
+  stored: Option<&Block>,
+  method: Method,
+  level: Level,
+  chunked: bool,
+) -> Result<(Vec<u8>, u32, u32), Error> {
+  let uncompressed_size = bytes.len() as u32;
+  if let Some(block) = stored.filter(|block| block.matches(&bytes)) {
+    if block.size_on_disk() == 0 {
+      return Ok((bytes, uncompressed_size, 0));
     }
-
-    Ok(())
+    return Ok((
+      block.stored().clone(),
+      uncompressed_size,
+      block.size_on_disk(),
+    ));
+  }
+  if method == Method::None || bytes.is_empty() {
+    return Ok((bytes, uncompressed_size, 0));
   }
 
-  fn read_lsf_strings(&mut self, context: &mut Context) -> Result<(), Error> {
-    let header = context.header();
-    let uncompressed_size = header.strings_uncompressed_size();
-    let size_on_disk = header.strings_size_on_disk();
-
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
-      header.compression_flags().into(),
-    )?;
-
-    let mut cursor = Cursor::new(uncompressed);
-    let hash_entry_count = cursor.read_u32::<LittleEndian>()?;
-    context
-      .string_lists_mut()
-      .reserve(hash_entry_count as usize);
-
-    for _ in 0..hash_entry_count {
-      let string_count = cursor.read_u16::<LittleEndian>()?;
-      let mut string_list: Vec<String> = Vec::with_capacity(string_count as usize);
-      for _ in 0..string_count {
-        let string_length = cursor.read_u16::<LittleEndian>()?;
-        let string = cursor.read_utf8_string(string_length as usize)?;
-        string_list.push(string);
-      }
-      context.string_lists_mut().push(string_list);
-    }
+  let mut options = CompressionOptions::new(method, level);
+  options.set_chunked(chunked);
+  let compressed = compress(&bytes, &options)?;
+  let size_on_disk = compressed.len() as u32;
+  Ok((compressed, uncompressed_size, size_on_disk))
+}
 
-    Ok(())
+pub trait LsfWriteExt: Write {
+  // Writes the resource with uncompressed blocks. Nodes carry sibling data from v3 on.
+  fn write_lsf_resource(&mut self, resource: &Resource, version: Version) -> Result<(), Error> {
+    self.write_lsf_resource_compressed(resource, version, Method::None, Level::Default)
   }
 
-  fn read_lsf_node_infos(&mut self, context: &mut Context) -> Result<(), Error> {
-    let header = context.header();
-    let uncompressed_size = header.nodes_uncompressed_size();
-    let size_on_disk = header.nodes_size_on_disk();
-    let chunks_allowed = header.version() >= &Version::V2;
-    let has_sibling_data = header.version() >= &Version::V3 && header.has_sibling_data() == 1;
-
-    let mut compression_options: CompressionOptions = header.compression_flags().into();
-    compression_options.set_chunked(chunks_allowed);
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
-      compression_options,
-    )?;
-
-    let mut cursor = Cursor::new(uncompressed);
-    while cursor.position() < uncompressed_size as u64 {
-      let mut node = NodeInfo::new();
-      if has_sibling_data {
-        let name_hash_table_index = cursor.read_u32::<LittleEndian>()?;
-        let parent_index = cursor.read_i32::<LittleEndian>()?;
-        let _next_sibling_index = cursor.read_i32::<LittleEndian>()?;
-        let first_attribute_index = cursor.read_i32::<LittleEndian>()?;
-        let name_index = (name_hash_table_index >> 16) as i32;
-        let name_offset = (name_hash_table_index & 0xffff) as i32;
-        node.set_name_index(name_index);
-        node.set_name_offset(name_offset);
-        node.set_parent_index(parent_index);
-        node.set_first_attribute_index(first_attribute_index);
-      } else {
-        let name_hash_table_index = cursor.read_u32::<LittleEndian>()?;
-        let first_attribute_index = cursor.read_i32::<LittleEndian>()?;
-        let parent_index = cursor.read_i32::<LittleEndian>()?;
-        let name_index = (name_hash_table_index >> 16) as i32;
-        let name_offset = (name_hash_table_index & 0xffff) as i32;
-        node.set_name_index(name_index);
-        node.set_name_offset(name_offset);
-        node.set_first_attribute_index(first_attribute_index);
-        node.set_parent_index(parent_index);
+  // Writes the resource with the version and compression of the LSF file it was read from, so an
+  // unmodified resource gives the same bytes. Other resources are written as uncompressed v6.
+  fn write_lsf_resource_with_layout(&mut self, resource: &Resource) -> Result<(), Error> {
+    match resource.lsf_layout() {
+      Some(layout) => {
+        let options: CompressionOptions = layout.compression_flags().into();
+        self.write_lsf_resource_compressed(
+          resource,
+          *layout.version(),
+          *options.method(),
+          *options.level(),
+        )
       }
-      context.nodes_infos_mut().push(node);
+      None => self.write_lsf_resource(resource, Version::V6),
     }
-
-    Ok(())
   }
 
-  fn read_lsf_attribute_infos(&mut self, context: &mut Context) -> Result<(), Error> {
-    let header = context.header();
-    let uncompressed_size = header.attributes_uncompressed_size();
-    let size_on_disk = header.attributes_size_on_disk();
-    let chunks_allowed = header.version() >= &Version::V2;
-    let has_sibling_data = header.version() >= &Version::V3 && header.has_sibling_data() == 1;
-
-    let mut compression_options: CompressionOptions = header.compression_flags().into();
-    compression_options.set_chunked(chunks_allowed);
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
-      compression_options,
-    )?;
-
-    let mut cursor = Cursor::new(uncompressed);
-    if has_sibling_data {
-      while cursor.position() < uncompressed_size as u64 {
-        let mut attribute = AttributeInfo::new();
-        let name_hash_table_index = cursor.read_u32::<LittleEndian>()?;
-        let type_and_length = cursor.read_u32::<LittleEndian>()?;
-        let next_attribute_index = cursor.read_i32::<LittleEndian>()?;
-        let offset = cursor.read_u32::<LittleEndian>()?;
-        let name_index = (name_hash_table_index >> 16) as i32;
-        let name_offset = (name_hash_table_index & 0xffff) as i32;
-        let type_id = type_and_length & 0x3f;
-        let length = type_and_length >> 6;
-        attribute.set_name_index(name_index);
-        attribute.set_name_offset(name_offset);
-        attribute.set_type_id(type_id);
-        attribute.set_length(length);
-        attribute.set_data_offset(offset);
-        attribute.set_next_attribute_index(next_attribute_index);
-
-        context.attribute_infos_mut().push(attribute);
-      }
+  // The string table is compressed as a single block, the other blocks use LZ4 frames from v2 on.
+  // The layout of a resource read from LSF is kept as far as the version and compression allow.
+  fn write_lsf_resource_compressed(
+    &mut self,
+    resource: &Resource,
+    version: Version,
+    method: Method,
+    level: Level,
+  ) -> Result<(), Error> {
+    let mut context = Context::new();
+    let is_64_bit = version >= Version::V5;
+    let header = context.header_mut();
+    header.set_version(version);
+    header.set_engine_version(resource.metadata().packed_version(is_64_bit));
+    header.set_metadata_format(if version >= Version::V3 {
+      MetadataFormat::KeysAndAdjacency
     } else {
-      let mut refs: Vec<i32> = Vec::new();
-      let mut data_offset = 0u32;
-      let mut index = 0i32;
-      while cursor.position() < uncompressed_size as u64 {
-        let mut attribute_info = AttributeInfo::new();
-        let name_hash_table_index = cursor.read_u32::<LittleEndian>()?;
-        let type_and_length = cursor.read_u32::<LittleEndian>()?;
-        let node_index = cursor.read_i32::<LittleEndian>()?;
-        let name_index = (name_hash_table_index >> 16) as i32;
-        let name_offset = (name_hash_table_index & 0xffff) as i32;
-        let type_id = type_and_length & 0x3f;
-        let length = type_and_length >> 6;
-        attribute_info.set_name_index(name_index);
-        attribute_info.set_name_offset(name_offset);
-        attribute_info.set_type_id(type_id);
-        attribute_info.set_length(length);
-        attribute_info.set_data_offset(data_offset);
-
-        let current_node_index: usize = (node_index + 1) as usize;
-        if refs.len() > current_node_index {
-          let attr_ref = refs[current_node_index];
-          if attr_ref != -1 {
-            context
-              .attribute_infos_mut()
-              .get_mut(attr_ref as usize)
-              .unwrap()
-              .set_next_attribute_index(index);
-          }
-          refs[current_node_index] = index;
-        } else {
-          while refs.len() < current_node_index {
-            refs.push(-1);
-          }
-
-          refs.push(index);
-        }
-
-        data_offset += length;
-        context.attribute_infos_mut().push(attribute_info);
-        index += 1;
+      MetadataFormat::None
+    });
+    header.set_compression_flags(match method {
+      Method::None => 0,
+      method => CompressionOptions::new(method, level).into(),
+    });
+
+    let mut blocks: &[Block] = &[];
+    if let Some(layout) = resource
+      .lsf_layout()
+      .filter(|layout| *layout.version() == version)
+    {
+      // The packed engine version can have bits that aren't part of the metadata
+      let metadata = Metadata::from_packed_version(layout.engine_version(), is_64_bit);
+      if metadata.packed_version(is_64_bit) == header.engine_version() {
+        header.set_engine_version(layout.engine_version());
+      }
+      header.set_metadata_format(layout.metadata_format());
+      header.set_unknown2(layout.unknown2());
+      header.set_unknown3(layout.unknown3());
+
+      // Stored blocks can only be reused with the compression they were stored with
+      let options: CompressionOptions = layout.compression_flags().into();
+      if *options.method() == method && (method == Method::None || *options.level() == level) {
+        header.set_compression_flags(layout.compression_flags());
+        blocks = layout.blocks();
       }
     }
 
-    Ok(())
-  }
-
-  fn read_lsf_node_arena(&mut self, context: &mut Context) -> Result<Arena<Data>, Error> {
-    let header = context.header();
-    let uncompressed_size = header.values_uncompressed_size();
-    let size_on_disk = header.values_size_on_disk();
-    let chunks_allowed = header.version() >= &Version::V2;
-
-    let mut compression_options: CompressionOptions = header.compression_flags().into();
-    compression_options.set_chunked(chunks_allowed);
-    let uncompressed = self.read_decompressed(
-      size_on_disk as usize,
-      uncompressed_size as usize,
-      compression_options,
+    let chunked = version >= Version::V2;
+    let values = lsf_node_infos(resource, &mut context)?;
+    let (strings, strings_uncompressed_size, strings_size_on_disk) = lsf_block(
+      lsf_strings_block(&context)?,
+      blocks.first(),
+      method,
+      level,
+      false,
     )?;
+    let (nodes, nodes_uncompressed_size, nodes_size_on_disk) = lsf_block(
+      lsf_nodes_block(&context)?,
+      blocks.get(1),
+      method,
+      level,
+      chunked,
+    )?;
+    let (attributes, attributes_uncompressed_size, attributes_size_on_disk) = lsf_block(
+      lsf_attributes_block(&context)?,
+      blocks.get(2),
+      method,
+      level,
+      chunked,
+    )?;
+    let (values, values_uncompressed_size, values_size_on_disk) =
+      lsf_block(values, blocks.get(3), method, level, chunked)?;
+    let (keys, keys_uncompressed_size, keys_size_on_disk) = match context.header().has_keys() {
+      true => lsf_block(
+        lsf_keys_block(&context)?,
+        blocks.get(4),
+        method,
+        level,
+        true,
+      )?,
+      false => (vec![], 0, 0),
+    };
 
-    let mut cursor = Cursor::new(uncompressed);
-    let mut node_arena: Arena<Data> = Arena::new();
-    for node_info in context.node_infos() {
-      let node_data = cursor.read_lsf_node_data(node_info, context)?;
-      if node_info.parent_index() != -1 {
-        let parent_index = node_info.parent_index() as usize;
-        node_arena.alloc(node_data, Some(parent_index));
-      } else {
-        node_arena.alloc(node_data, None);
-      };
-    }
-
-    Ok(node_arena)
+    let header = context.header_mut();
+    header.set_strings_uncompressed_size(strings_uncompressed_size);
+    header.set_strings_size_on_disk(strings_size_on_disk);
+    header.set_nodes_uncompressed_size(nodes_uncompressed_size);
+    header.set_nodes_size_on_disk(nodes_size_on_disk);
+    header.set_attributes_uncompressed_size(attributes_uncompressed_size);
+    header.set_attributes_size_on_disk(attributes_size_on_disk);
+    header.set_values_uncompressed_size(values_uncompressed_size);
+    header.set_values_size_on_disk(values_size_on_disk);
+    header.set_keys_uncompressed_size(keys_uncompressed_size);
+    header.set_keys_size_on_disk(keys_size_on_disk);
+
+    self.write_lsf_header(&context)?;
+    self.write_all(&strings)?;
+    self.write_all(&nodes)?;
+    self.write_all(&attributes)?;
+    self.write_all(&values)?;
+    self.write_all(&keys)?;
+    Ok(())
   }
 
-  fn read_lsf_node_data(&mut self, node_info: &NodeInfo, context: &Context) -> Result<Data, Error> {
-    let name = context
-      .string_lists()
-      .get(node_info.name_index() as usize)
-      .ok_or(Error::InvalidStringIndex(node_info.name_index()))?
-      .get(node_info.name_offset() as usize)
-      .ok_or(Error::InvalidStringOffset(
-        node_info.name_index(),
-        node_info.name_offset(),
-      ))?;
-
-    let mut node_data = Data::new(name.to_owned());
-    if node_info.first_attribute_index() == -1 {
-      return Ok(node_data);
-    }
-
-    let mut attribute_info = context
-      .attribute_infos()
-      .get(node_info.first_attribute_index() as usize)
-      .ok_or(Error::InvalidAttributeIndex(
-        node_info.first_attribute_index(),
-      ))?;
-    loop {
-      self.seek(SeekFrom::Start(attribute_info.data_offset() as u64))?;
-      let attribute = self.read_lsf_attribute(attribute_info, context)?;
-      let attribute_name = context
-        .string_lists()
-        .get(attribute_info.name_index() as usize)
-        .ok_or(Error::InvalidStringIndex(attribute_info.name_index()))?
-        .get(attribute_info.name_offset() as usize)
-        .ok_or(Error::InvalidStringOffset(
-          attribute_info.name_index(),
-          attribute_info.name_offset(),
-        ))?;
-      node_data
-        .attributes_mut()
-        .insert(attribute_name.to_owned(), attribute);
-
-      if attribute_info.next_attribute_index() == -1 {
-        break;
-      }
-
-      attribute_info = context
-        .attribute_infos()
-        .get(attribute_info.next_attribute_index() as usize)
-        .ok_or(Error::InvalidAttributeIndex(
-          attribute_info.next_attribute_index(),
-        ))?;
+  fn write_lsf_header(&mut self, context: &Context) -> Result<(), Error> {
+    let header = context.header();
+    let version = *header.version();
+    self.write_all(&SIGNATURE)?;
+    self.write_u32::<LittleEndian>(version as u32)?;
+    if version >= Version::V5 {
+      self.write_i64::<LittleEndian>(header.engine_version())?;
+    } else {
+      self.write_i32::<LittleEndian>(header.engine_version() as i32)?;
     }
-    Ok(node_data)
-  }
 
-  fn read_lsf_attribute(
-    &mut self,
-    attribute_info: &AttributeInfo,
-    context: &Context,
-  ) -> Result<Attribute, Error> {
-    let data = self.read_lsf_attribute_value(
-      attribute_info.length() as usize,
-      attribute_info.type_id(),
-      context.header().version(),
-    )?;
-    Ok(Attribute::new_value(data))
-  }
-
-  fn write_lsf_attribute(
-    &mut self,
-    name: &str,
-    attribute: &Attribute,
-    context: &mut Context,
-  ) -> Result<(), Error> {
-    let attribute_info = context.attribute_infos_mut().last_mut().unwrap();
-    let name_hash_table_index =
-      context
-        .string_lists()
-        .iter()
-        .enumerate()
-        .find_map(|(index, string_list)| {
-          string_list.iter().enumerate().find_map(|(offset, string)| {
-            if string == name {
-              Some(((index as u32) << 16) | (offset as u32))
-            } else {
-              None
-            }
-          })
-        });
-    if let Some(name_hash_table_index) = name_hash_table_index {
-      attribute_info.set_name_index((name_hash_table_index >> 16) as i32);
-      attribute_info.set_name_offset((name_hash_table_index & 0xffff) as i32);
-    } else {
-      let name_index = context.string_lists().len() as u32;
-      let name_offset = context.string_lists().last().unwrap().len() as u32;
-      attribute_info.set_name_index(name_index as i32);
-      attribute_info.set_name_offset(name_offset as i32);
-      context
-        .string_lists_mut()
-        .last_mut()
-        .unwrap()
-        .push(name.to_owned());
+    self.write_u32::<LittleEndian>(header.strings_uncompressed_size())?;
+    self.write_u32::<LittleEndian>(header.strings_size_on_disk())?;
+    if version >= Version::V6 {
+      self.write_u32::<LittleEndian>(header.keys_uncompressed_size())?;
+      self.write_u32::<LittleEndian>(header.keys_size_on_disk())?;
     }
-    attribute_info.set_type_id(attribute.value().into());
-    attribute_info.set_length(attribute.value().size());
-    attribute_info.set_data_offset(context.values_size_on_disk());
-    context
-      .set_values_size_on_disk(context.values_size_on_disk() + attribute.value().size() as u32);
-    self.write_lsf_attribute_value(
-      attribute.value().clone(),
-      attribute.value().size(),
-      context.header().version(),
-    )?;
+    self.write_u32::<LittleEndian>(header.nodes_uncompressed_size())?;
+    self.write_u32::<LittleEndian>(header.nodes_size_on_disk())?;
+    self.write_u32::<LittleEndian>(header.attributes_uncompressed_size())?;
+    self.write_u32::<LittleEndian>(header.attributes_size_on_disk())?;
+    self.write_u32::<LittleEndian>(header.values_uncompressed_size())?;
+    self.write_u32::<LittleEndian>(header.values_size_on_disk())?;
+    self.write_u8(header.compression_flags())?;
+    self.write_u8(header.unknown2())?;
+    self.write_u16::<LittleEndian>(header.unknown3())?;
+    self.write_u32::<LittleEndian>(header.metadata_format() as u32)?;
     Ok(())
   }
 
   fn write_lsf_attribute_value(
     &mut self,
-    value: Value,
-    size: usize,
+    value: &Value,
     lsf_version: &Version,
   ) -> Result<(), Error> {
     match value {
-      Value::String(string_value) => {
-        self.write_utf8_string(&string_value, size)?;
-      }
-      Value::Path(path_value) => {
-        self.write_utf8_string(&path_value, size)?;
-      }
-      Value::FixedString(fixed_string_value) => {
-        self.write_utf8_string(&fixed_string_value, size)?;
-      }
-      Value::LsString(ls_string_value) => {
-        self.write_utf8_string(&ls_string_value, size)?;
-      }
-      Value::ScratchBuffer(scratch_buffer_value) => {
-        self.write_bytes(&scratch_buffer_value)?;
+      Value::String(string)
+      | Value::Path(string)
+      | Value::FixedString(string)
+      | Value::LsString(string)
+      | Value::WString(string)
+      | Value::LswString(string) => {
+        self.write_all(string.as_bytes())?;
+        self.write_u8(0)?;
       }
+      Value::ScratchBuffer(bytes) => self.write_all(bytes)?,
       Value::TranslatedString {
         version,
         value,
         handle,
       } => {
         if lsf_version >= &Version::V4 {
-          self.write_u16::<LittleEndian>(version)?;
+          self.write_u16::<LittleEndian>(*version)?;
         } else {
-          self.write_i32::<LittleEndian>(value.len() as i32)?;
-          self.write_utf8_string(&value, value.len())?;
+          self.write_lsf_string_with_length(value)?;
         }
-
-        self.write_i32::<LittleEndian>(handle.len() as i32)?;
-        self.write_utf8_string(&handle, handle.len())?;
-      }
-      Value::WString(wstring_value) => {
-        self.write_utf8_string(&wstring_value, size)?;
-      }
-      Value::LswString(lsw_string_value) => {
-        self.write_utf8_string(&lsw_string_value, size)?;
+        self.write_lsf_string_with_length(handle)?;
       }
       Value::TranslatedFsString { .. } => {
-        self.write_lsf_translated_fs_string(value, lsf_version)?;
-      }
-      _ => {
-        self.write_resource_value(value, size)?;
+        self.write_lsf_translated_fs_string(value, lsf_version)?
       }
+      value => self.write_resource_value(value.clone(), value.length())?,
     }
-
     Ok(())
   }
 
   fn write_lsf_translated_fs_string(
     &mut self,
-    value: Value,
+    value: &Value,
     lsf_version: &Version,
   ) -> Result<(), Error> {
     let (version, value, handle, arguments) = match value {
@@ -477,32 +263,497 @@ pub trait LsfWriteExt: Write + Seek {
         value,
         handle,
         arguments,
-      } => (version, value, handle, arguments),
-      _ => return Err(Error::InvalidTypeId(value.into())),
+      } => (
+        *version,
+        value.as_str(),
+        handle.as_str(),
+        arguments.as_slice(),
+      ),
+      Value::TranslatedString {
+        version,
+        value,
+        handle,
+      } => (*version, value.as_str(), handle.as_str(), &[][..]),
+      value => return Err(Error::InvalidTypeId(value.type_id())),
     };
 
     if lsf_version >= &Version::V4 {
       self.write_u16::<LittleEndian>(version)?;
     } else {
-      self.write_i32::<LittleEndian>(value.len() as i32)?;
-      self.write_utf8_string(&value, value.len())?;
+      self.write_lsf_string_with_length(value)?;
     }
-
-    self.write_i32::<LittleEndian>(handle.len() as i32)?;
-    self.write_utf8_string(&handle, handle.len())?;
-
+    self.write_lsf_string_with_length(handle)?;
     self.write_i32::<LittleEndian>(arguments.len() as i32)?;
-
     for argument in arguments {
-      self.write_i32::<LittleEndian>(argument.key.len() as i32)?;
-      self.write_utf8_string(&argument.key, argument.key.len())?;
-      self.write_lsf_translated_fs_string(argument.string)?;
-      self.write_i32::<LittleEndian>(argument.value.len() as i32)?;
-      self.write_utf8_string(&argument.value, argument.value.len())?;
+      self.write_lsf_string_with_length(&argument.key)?;
+      self.write_lsf_translated_fs_string(&argument.string, lsf_version)?;
+      self.write_lsf_string_with_length(&argument.value)?;
     }
+    Ok(())
+  }
 
+  // Null terminated string, prefixed with its length including the terminator
+  fn write_lsf_string_with_length(&mut self, string: &str) -> Result<(), Error> {
+    self.write_i32::<LittleEndian>(string.len() as i32 + 1)?;
+    self.write_all(string.as_bytes())?;
+    self.write_u8(0)?;
     Ok(())
   }
 }
 
-impl<R: Write + Seek + ?Sized> LsfWriteExt for R {}
+impl<W: Write + ?Sized> LsfWriteExt for W {}
+
+// Fills the string table, node and attribute infos of the context and returns the values block.
+// Parents have to precede their children, which always holds for the order nodes were allocated in.
+// For resources read from LSF, that's the order of the file.
+fn lsf_node_infos(resource: &Resource, context: &mut Context) -> Result<Vec<u8>, Error> {
+  let version = *context.header().version();
+  let layout = resource.lsf_layout();
+  // Tables read from a file are kept, new names are hashed into their buckets
+  let mut string_table = match layout {
+    Some(layout) => StringTable::from_string_lists(layout.string_lists().clone()),
+    None => StringTable::new(),
+  };
+  let mut values = Cursor::new(vec![]);
+
+  for index in 0..resource.size() {
+    let data = resource.value(index);
+    let (name_index, name_offset) = string_table.insert(data.name())?;
+    let mut node_info = NodeInfo::new();
+    node_info.set_name_index(name_index);
+    node_info.set_name_offset(name_offset);
+    node_info.set_parent_index(match resource.parent_index(index) {
+      Some(parent_index) => parent_index as i32,
+      None => -1,
+    });
+    if let Some(key) = data.key().filter(|_| context.header().has_keys()) {
+      let (key_name_index, key_name_offset) = string_table.insert(key)?;
+      node_info.set_key_name_index(key_name_index);
+      node_info.set_key_name_offset(key_name_offset);
+    }
+
+    // Attributes are written in the order of the layout, new ones and all attributes of resources
+    // without layout in name order, so the output doesn't depend on hashing
+    let mut attributes: Vec<_> = data.attributes().iter().collect();
+    attributes.sort_by_key(|(name, _)| *name);
+    if let Some(names) = layout.and_then(|layout| layout.attribute_names(index)) {
+      attributes.sort_by_key(|(name, _)| {
+        names
+          .iter()
+          .position(|layout_name| layout_name == name)
+          .unwrap_or(names.len())
+      });
+    }
+    if !attributes.is_empty() {
+      node_info.set_first_attribute_index(context.attribute_infos().len() as i32);
+    }
+    for (position, (name, attribute)) in attributes.iter().enumerate() {
+      let (name_index, name_offset) = string_table.insert(name)?;
+      let data_offset = values.position();
+      values.write_lsf_attribute_value(attribute.value(), &version)?;
+      let length = values.position() - data_offset;
+
+      let mut attribute_info = AttributeInfo::new();
+      attribute_info.set_name_index(name_index);
+      attribute_info.set_name_offset(name_offset);
+      attribute_info.set_type_id(attribute.value().type_id());
+      attribute_info.set_length(length as u32);
+      attribute_info.set_data_offset(data_offset as u32);
+      if position + 1 < attributes.len() {
+        attribute_info.set_next_attribute_index(context.attribute_infos().len() as i32 + 1);
+      }
+      context.attribute_infos_mut().push(attribute_info);
+    }
+
+    context.nodes_infos_mut().push(node_info);
+  }
+
+  *context.string_lists_mut() = string_table.into_string_lists();
+  Ok(values.into_inner())
+}
+
+fn lsf_strings_block(context: &Context) -> Result<Vec<u8>, Error> {
+  let mut cursor = Cursor::new(vec![]);
+  cursor.write_u32::<LittleEndian>(context.string_lists().len() as u32)?;
+  for string_list in context.string_lists() {
+    cursor.write_u16::<LittleEndian>(string_list.len() as u16)?;
+    for string in string_list {
+      cursor.write_u16::<LittleEndian>(string.len() as u16)?;
+      cursor.write_all(string.as_bytes())?;
+    }
+  }
+  Ok(cursor.into_inner())
+}
+
+fn lsf_nodes_block(context: &Context) -> Result<Vec<u8>, Error> {
+  let node_infos = context.node_infos();
+  let mut cursor = Cursor::new(vec![]);
+  if context.header().has_sibling_data() {
+    // Every node points to the next node with the same parent
+    let mut next_sibling_indexes = vec![-1; node_infos.len()];
+    let mut last_children: HashMap<i32, usize> = HashMap::new();
+    for (index, node_info) in node_infos.iter().enumerate() {
+      if let Some(previous) = last_children.insert(node_info.parent_index(), index) {
+        next_sibling_indexes[previous] = index as i32;
+      }
+    }
+
+    for (node_info, next_sibling_index) in node_infos.iter().zip(next_sibling_indexes) {
+      cursor.write_u32::<LittleEndian>(name_hash_table_index(
+        node_info.name_index(),
+        node_info.name_offset(),
+      ))?;
+      cursor.write_i32::<LittleEndian>(node_info.parent_index())?;
+      cursor.write_i32::<LittleEndian>(next_sibling_index)?;
+      cursor.write_i32::<LittleEndian>(node_info.first_attribute_index())?;
+    }
+  } else {
+    for node_info in node_infos {
+      cursor.write_u32::<LittleEndian>(name_hash_table_index(
+        node_info.name_index(),
+        node_info.name_offset(),
+      ))?;
+      cursor.write_i32::<LittleEndian>(node_info.first_attribute_index())?;
+      cursor.write_i32::<LittleEndian>(node_info.parent_index())?;
+    }
+  }
+  Ok(cursor.into_inner())
+}
+
+fn lsf_keys_block(context: &Context) -> Result<Vec<u8>, Error> {
+  let mut cursor = Cursor::new(vec![]);
+  for (node_index, node_info) in context.node_infos().iter().enumerate() {
+    if node_info.key_name_index() != -1 {
+      cursor.write_u32::<LittleEndian>(node_index as u32)?;
+      cursor.write_u32::<LittleEndian>(name_hash_table_index(
+        node_info.key_name_index(),
+        node_info.key_name_offset(),
+      ))?;
+    }
+  }
+  Ok(cursor.into_inner())
+}
+
+fn lsf_attributes_block(context: &Context) -> Result<Vec<u8>, Error> {
+  let mut cursor = Cursor::new(vec![]);
+  let has_sibling_data = context.header().has_sibling_data();
+  for (node_index, node_info) in context.node_infos().iter().enumerate() {
+    let mut attribute_index = node_info.first_attribute_index();
+    while attribute_index != -1 {
+      let attribute_info = &context.attribute_infos()[attribute_index as usize];
+      cursor.write_u32::<LittleEndian>(name_hash_table_index(
+        attribute_info.name_index(),
+        attribute_info.name_offset(),
+      ))?;
+      cursor.write_u32::<LittleEndian>(attribute_info.type_id() | attribute_info.length() << 6)?;
+      if has_sibling_data {
+        cursor.write_i32::<LittleEndian>(attribute_info.next_attribute_index())?;
+        cursor.write_u32::<LittleEndian>(attribute_info.data_offset())?;
+      } else {
+        // Without sibling data, attributes refer to their node and the values follow each other
+        cursor.write_i32::<LittleEndian>(node_index as i32)?;
+      }
+      attribute_index = attribute_info.next_attribute_index();
+    }
+  }
+  Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    lsb::read::LsbReadExt,
+    lsf::{
+      read::LsfReadExt,
+      string_table::{dotnet_hash, BUCKET_COUNT},
+    },
+    resource::{
+      metadata::Metadata,
+      node::{
+        attribute::{Attribute, AttributeMap},
+        data::Data,
+      },
+      value::TranslatedFsStringArgument,
+    },
+    util::arena::ArenaWriter,
+  };
+
+  use super::*;
+
+  fn fixture() -> Resource {
+    let mut resource = Resource::new();
+    let mut metadata = Metadata::new();
+    metadata.set_major_version(3);
+    metadata.set_minor_version(6);
+    metadata.set_revision(9);
+    metadata.set_build_number(1);
+    resource.set_metadata(metadata);
+
+    let root = resource.alloc(Data::new("Characters".to_string()), None);
+    for name in ["Ifan", "Fane"] {
+      let mut attributes = AttributeMap::new();
+      attributes.insert(
+        "Name".to_string(),
+        Attribute::new_value(Value::LsString(name.to_string())),
+      );
+      attributes.insert("Level".to_string(), Attribute::new_value(Value::Int(12)));
+      attributes.insert(
+        "Position".to_string(),
+        Attribute::new_value(Value::Vec3([1.0, 2.0, 3.0])),
+      );
+      attributes.insert(
+        "Title".to_string(),
+        Attribute::new_value(Value::TranslatedFsString {
+          version: 0,
+          value: "".to_string(),
+          handle: "h1".to_string(),
+          arguments: vec![TranslatedFsStringArgument {
+            key: "Name".to_string(),
+            string: Value::TranslatedFsString {
+              version: 0,
+              value: "".to_string(),
+              handle: "h2".to_string(),
+              arguments: vec![],
+            },
+            value: name.to_string(),
+          }],
+        }),
+      );
+      let character = resource.alloc(
+        Data::new_with_attributes("Character".to_string(), attributes),
+        Some(root),
+      );
+      resource.alloc(Data::new("Inventory".to_string()), Some(character));
+    }
+    resource.alloc(Data::new("Globals".to_string()), None);
+    resource
+  }
+
+  fn assert_resources_eq(actual: &Resource, expected: &Resource) {
+    assert_eq!(actual.size(), expected.size());
+    assert_eq!(actual.root_indexes(), expected.root_indexes());
+    for index in 0..expected.size() {
+      assert_eq!(actual.value(index), expected.value(index));
+      assert_eq!(actual.parent_index(index), expected.parent_index(index));
+      assert_eq!(actual.child_indexes(index), expected.child_indexes(index));
+    }
+  }
+
+  const VERSIONS: [Version; 7] = [
+    Version::V1,
+    Version::V2,
+    Version::V3,
+    Version::V4,
+    Version::V5,
+    Version::V6,
+    Version::V7,
+  ];
+
+  const METHODS: [Method; 4] = [Method::None, Method::Zlib, Method::Lz4, Method::Zstd];
+
+  #[test]
+  fn test_write_lsf_resource() {
+    let resource = fixture();
+    for version in VERSIONS {
+      for method in METHODS {
+        let mut writer = Cursor::new(vec![]);
+        writer
+          .write_lsf_resource_compressed(&resource, version, method, Level::Default)
+          .unwrap();
+        writer.set_position(0);
+        let read_resource = writer.read_lsf_resource_with_layout().unwrap();
+
+        assert_eq!(read_resource.root_indexes().len(), 2);
+        assert_eq!(read_resource.metadata().major_version(), 3);
+        assert_eq!(read_resource.metadata().build_number(), 1);
+        let string_lists = read_resource.lsf_layout().unwrap().string_lists();
+        assert_eq!(string_lists.len(), BUCKET_COUNT);
+        assert!(StringTable::misplaced_names(string_lists, dotnet_hash).is_empty());
+        assert_resources_eq(&read_resource, &resource);
+      }
+    }
+  }
+
+  #[test]
+  fn test_lsf_keys() {
+    let mut resource = fixture();
+    for index in [1, 3] {
+      resource.value_mut(index).set_key(Some("Name".to_string()));
+    }
+    for version in VERSIONS {
+      for method in METHODS {
+        let mut lsf = vec![];
+        lsf
+          .write_lsf_resource_compressed(&resource, version, method, Level::Default)
+          .unwrap();
+        let read_resource = Cursor::new(&lsf).read_lsf_resource().unwrap();
+        if version >= Version::V6 {
+          assert_resources_eq(&read_resource, &resource);
+        } else {
+          // Older versions have no keys
+          assert_eq!(read_resource.value(1).key(), None);
+          assert_eq!(read_resource.value(1).name(), "Character");
+        }
+      }
+    }
+  }
+
+  // v7 files are written like v6, only the version field differs
+  #[test]
+  fn test_write_lsf_v7() {
+    let mut resource = fixture();
+    resource.value_mut(1).set_key(Some("Name".to_string()));
+    for method in METHODS {
+      let mut v6 = vec![];
+      v6.write_lsf_resource_compressed(&resource, Version::V6, method, Level::Default)
+        .unwrap();
+      let mut v7 = vec![];
+      v7.write_lsf_resource_compressed(&resource, Version::V7, method, Level::Default)
+        .unwrap();
+      assert_eq!(v7[4..8], 7u32.to_le_bytes());
+      assert_eq!(v7[..4], v6[..4]);
+      assert_eq!(v7[8..], v6[8..]);
+
+      let read_resource = Cursor::new(&v7).read_lsf_resource_with_layout().unwrap();
+      assert_eq!(*read_resource.lsf_layout().unwrap().version(), Version::V7);
+      assert_resources_eq(&read_resource, &resource);
+    }
+  }
+
+  // read -> write -> read keeps the resource, and writing it again gives the same bytes
+  #[test]
+  fn test_lsf_round_trip() {
+    let lsb_files: [&[u8]; 2] = [
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/profile.lsb"),
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/UILayout.lsb"),
+    ];
+    for lsb in lsb_files {
+      let lsb_resource = Cursor::new(lsb).read_lsb_resource().unwrap();
+      for version in VERSIONS {
+        for method in METHODS {
+          let mut lsf = vec![];
+          lsf
+            .write_lsf_resource_compressed(&lsb_resource, version, method, Level::Default)
+            .unwrap();
+          let resource = Cursor::new(&lsf).read_lsf_resource_with_layout().unwrap();
+
+          let mut rewritten = vec![];
+          rewritten
+            .write_lsf_resource_compressed(&resource, version, method, Level::Default)
+            .unwrap();
+          assert_eq!(rewritten, lsf);
+          let read_resource = Cursor::new(&rewritten).read_lsf_resource().unwrap();
+          assert_resources_eq(&read_resource, &resource);
+          assert_eq!(read_resource.size(), lsb_resource.size());
+        }
+      }
+    }
+  }
+
+  // A layout the writer wouldn't produce by itself survives read -> write, including blocks that
+  // were compressed differently than this crate would
+  #[test]
+  fn test_lsf_layout_round_trip() {
+    let lsb =
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/profile.lsb");
+    let lsb_resource = Cursor::new(lsb).read_lsb_resource().unwrap();
+    let mut lsf = vec![];
+    lsf
+      .write_lsf_resource_compressed(&lsb_resource, Version::V6, Method::Zlib, Level::Max)
+      .unwrap();
+    let plain_resource = Cursor::new(&lsf).read_lsf_resource().unwrap();
+    assert!(plain_resource.lsf_layout().is_none());
+    let mut resource = Cursor::new(&lsf).read_lsf_resource_with_layout().unwrap();
+    let layout = resource.lsf_layout_mut().unwrap();
+    // Handles are rebuilt below, once the names have moved
+    let attribute_names: Vec<Vec<String>> = (0..layout.attribute_handles().len())
+      .map(|index| {
+        let names = layout.attribute_names(index).unwrap();
+        names.into_iter().rev().map(str::to_owned).collect()
+      })
+      .collect();
+    let names: Vec<String> = layout.string_lists().concat();
+    *layout.string_lists_mut() = vec![vec![], vec!["Unused".to_string()], vec![]];
+    for (index, name) in names.into_iter().enumerate() {
+      layout.string_lists_mut()[index % 3].push(name);
+    }
+    let string_table = StringTable::from_string_lists(layout.string_lists().clone());
+    *layout.attribute_handles_mut() = attribute_names
+      .iter()
+      .map(|names| {
+        names
+          .iter()
+          .map(|name| string_table.get(name).unwrap())
+          .collect()
+      })
+      .collect();
+    layout.set_unknown2(1);
+    layout.set_unknown3(2);
+    let mut foreign = vec![];
+    foreign.write_lsf_resource_with_layout(&resource).unwrap();
+    // Claim the blocks were compressed with the default level
+    let compression_flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+    foreign[56] = compression_flags;
+
+    let mut resource = Cursor::new(&foreign)
+      .read_lsf_resource_with_layout()
+      .unwrap();
+    let layout = resource.lsf_layout().unwrap();
+    assert_eq!(layout.string_lists()[1][0], "Unused");
+    assert_eq!(layout.unknown2(), 1);
+    let mut rewritten = vec![];
+    rewritten.write_lsf_resource_with_layout(&resource).unwrap();
+    assert_eq!(rewritten, foreign);
+    let mut rewritten = vec![];
+    rewritten
+      .write_lsf_resource_compressed(&resource, Version::V6, Method::Zlib, Level::Default)
+      .unwrap();
+    assert_eq!(rewritten, foreign);
+
+    // Without its layout, the resource is written the way this crate lays it out
+    let layout = resource.lsf_layout().cloned();
+    resource.set_lsf_layout(None);
+    let mut rewritten = vec![];
+    rewritten
+      .write_lsf_resource_compressed(&resource, Version::V6, Method::Zlib, Level::Default)
+      .unwrap();
+    assert_ne!(rewritten, foreign);
+    assert_resources_eq(
+      &Cursor::new(&rewritten).read_lsf_resource().unwrap(),
+      &resource,
+    );
+
+    // Changes are written, unchanged blocks are kept as they were
+    resource.set_lsf_layout(layout);
+    let (index, name) = (0..resource.size())
+      .find_map(|index| {
+        let data = resource.value(index);
+        data
+          .attributes()
+          .iter()
+          .find(|(_, attribute)| matches!(attribute.value(), Value::Int(_)))
+          .map(|(name, _)| (index, name.to_owned()))
+      })
+      .unwrap();
+    resource
+      .value_mut(index)
+      .attributes_mut()
+      .get_mut(&name)
+      .unwrap()
+      .set_value(Value::Int(-1));
+    let mut rewritten = vec![];
+    rewritten.write_lsf_resource_with_layout(&resource).unwrap();
+    // Strings, nodes and attributes follow the 64 byte header
+    let unchanged_size: usize = resource.lsf_layout().unwrap().blocks()[..3]
+      .iter()
+      .map(|block| block.size_on_disk() as usize)
+      .sum();
+    assert_eq!(
+      rewritten[64..64 + unchanged_size],
+      foreign[64..64 + unchanged_size]
+    );
+    assert_ne!(rewritten, foreign);
+    let read_resource = Cursor::new(&rewritten).read_lsf_resource().unwrap();
+    assert_resources_eq(&read_resource, &resource);
+  }
+}
@@ -0,0 +1,145 @@
+use std::{
+  fmt::{Debug, Formatter},
+  future::Future,
+  path::{Path, PathBuf},
+};
+
+use tokio::{
+  io::{AsyncRead, AsyncSeek},
+  sync::Mutex,
+};
+
+use crate::error::Error;
+
+use super::{
+  file::File,
+  header::Header,
+  package::{part_path, Package},
+  read::async_read::{decompress_lsv_file_async, read_lsv_stored_bytes_async, LsvAsyncReadExt},
+};
+
+// Async counterpart of PackageHandle. Files are read through &self, so several of them can be
+// fetched concurrently. Every part is locked only while reading the stored bytes of a file.
+pub struct AsyncPackageHandle<I> {
+  package: Package,
+  path: PathBuf,
+  streams: Vec<Mutex<I>>,
+}
+
+impl<I> AsyncPackageHandle<I> {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn package(&self) -> &Package {
+    &self.package
+  }
+
+  pub fn header(&self) -> &Header {
+    self.package.header()
+  }
+
+  pub fn file(&self, path: &Path) -> Option<&File> {
+    self.package.file(path)
+  }
+
+  pub fn into_package(self) -> Package {
+    self.package
+  }
+}
+
+impl<I> Debug for AsyncPackageHandle<I> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!("{:?}", self.path))
+  }
+}
+
+impl<I: AsyncRead + AsyncSeek + Unpin + Send> AsyncPackageHandle<I> {
+  pub async fn open<F, O>(path: &Path, create_reader: F) -> Result<AsyncPackageHandle<I>, Error>
+  where
+    F: Fn(PathBuf) -> O,
+    O: Future<Output = Result<I, Error>>,
+  {
+    let mut package = Package::default();
+    let mut main_stream = create_reader(path.to_owned()).await?;
+    main_stream.read_lsv_package_async(&mut package).await?;
+
+    let mut streams = Vec::with_capacity(package.header().part_count() as usize);
+    streams.push(Mutex::new(main_stream));
+    for part in 1..package.header().part_count() {
+      streams.push(Mutex::new(create_reader(part_path(path, part)).await?));
+    }
+
+    Ok(AsyncPackageHandle {
+      package,
+      path: path.to_owned(),
+      streams,
+    })
+  }
+
+  // Reads the uncompressed contents of a file, they aren't cached in the file table
+  pub async fn file_contents(&self, path: &Path) -> Result<Vec<u8>, Error> {
+    let file = self
+      .package
+      .file(path)
+      .ok_or(Error::FileNotFound(path.to_string_lossy().into_owned()))?;
+    let stream = self
+      .streams
+      .get(file.part_index() as usize)
+      .ok_or(Error::InvalidFileTable)?;
+    let stored_bytes = {
+      let mut stream = stream.lock().await;
+      read_lsv_stored_bytes_async(&mut *stream, self.header(), file).await?
+    };
+    decompress_lsv_file_async(stored_bytes, self.header(), file).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use crate::compression::{CompressionOptions, Level, Method};
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_async_package_handle() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let path = dir.join("Textures.pak");
+
+    let mut package = Package::default();
+    let flags: u8 = CompressionOptions::new(Method::Lz4, Level::Default).into();
+    for (index, name) in ["a.dds", "b.dds", "c.dds", "d.dds"].iter().enumerate() {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(name));
+      file.set_flags(flags as u32);
+      file.set_contents(Some(vec![index as u8; 100]));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    let mut writer = Package::create_file(&path).unwrap();
+    writer.set_max_part_size(Some(64));
+    writer.write(&mut package).unwrap();
+    drop(writer);
+
+    let handle = Arc::new(Package::open_file_async(&path).await.unwrap());
+    assert!(handle.header().part_count() > 1);
+    let tasks: Vec<_> = ["d.dds", "a.dds", "c.dds", "b.dds"]
+      .into_iter()
+      .map(|name| {
+        let handle = handle.clone();
+        tokio::spawn(async move { handle.file_contents(Path::new(name)).await })
+      })
+      .collect();
+    for (task, index) in tasks.into_iter().zip([3u8, 0, 2, 1]) {
+      assert_eq!(task.await.unwrap().unwrap(), vec![index; 100]);
+    }
+    assert!(matches!(
+      handle.file_contents(Path::new("e.dds")).await,
+      Err(Error::FileNotFound(_))
+    ));
+  }
+}
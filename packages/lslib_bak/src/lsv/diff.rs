@@ -0,0 +1,328 @@
+use std::{
+  collections::BTreeSet,
+  io::{Read, Seek},
+  path::{Path, PathBuf},
+};
+
+use crc32fast::hash;
+
+use crate::{
+  error::Error,
+  resource::diff::{diff_resources, ResourceChange},
+};
+
+use super::{extract::ResourceFormat, file::File, package::PackageHandle};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryChange {
+  Added,
+  Removed,
+  Changed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntrySummary {
+  size: u32,
+  crc: Option<u32>,
+}
+
+impl EntrySummary {
+  // Uncompressed size of the file
+  pub fn size(&self) -> u32 {
+    self.size
+  }
+
+  // CRC of the uncompressed contents, only computed for files found in both packages
+  pub fn crc(&self) -> Option<u32> {
+    self.crc
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryDiff {
+  path: PathBuf,
+  change: EntryChange,
+  old: Option<EntrySummary>,
+  new: Option<EntrySummary>,
+  resource_changes: Vec<ResourceChange>,
+}
+
+impl EntryDiff {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn change(&self) -> EntryChange {
+    self.change
+  }
+
+  pub fn old_summary(&self) -> Option<&EntrySummary> {
+    self.old.as_ref()
+  }
+
+  pub fn new_summary(&self) -> Option<&EntrySummary> {
+    self.new.as_ref()
+  }
+
+  // Differences of changed LSF and LSB files, if resources are compared
+  pub fn resource_changes(&self) -> &Vec<ResourceChange> {
+    &self.resource_changes
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageDiff {
+  entries: Vec<EntryDiff>,
+}
+
+impl PackageDiff {
+  // Added, removed and changed files in path order
+  pub fn entries(&self) -> &Vec<EntryDiff> {
+    &self.entries
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+  compare_resources: bool,
+}
+
+impl DiffOptions {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn compare_resources(&self) -> bool {
+    self.compare_resources
+  }
+
+  // Parses changed LSF and LSB files and compares their nodes and attributes
+  pub fn set_compare_resources(&mut self, compare_resources: bool) {
+    self.compare_resources = compare_resources;
+  }
+}
+
+// Uncompressed files are stored without an uncompressed size
+fn file_size(file: &File) -> u32 {
+  if file.is_compressed() {
+    file.uncompressed_size()
+  } else {
+    file.size_on_disk()
+  }
+}
+
+fn read_contents<I: Read + Seek>(
+  handle: &mut PackageHandle<I>,
+  path: &Path,
+) -> Result<Vec<u8>, Error> {
+  let mut contents = vec![];
+  handle.file_stream(path)?.read_to_end(&mut contents)?;
+  Ok(contents)
+}
+
+// Compares the files of two packages. Files found in both are compared by size and the CRC of
+// their uncompressed contents, so recompressing a file doesn't count as a change.
+pub fn diff_lsv_packages<I: Read + Seek, J: Read + Seek>(
+  old: &mut PackageHandle<I>,
+  new: &mut PackageHandle<J>,
+  options: &DiffOptions,
+) -> Result<PackageDiff, Error> {
+  let paths: BTreeSet<PathBuf> = old
+    .files()
+    .keys()
+    .chain(new.files().keys())
+    .cloned()
+    .collect();
+
+  let mut entries = vec![];
+  for path in paths {
+    let (old_file, new_file) = match (old.file(&path), new.file(&path)) {
+      (Some(old_file), Some(new_file)) => (old_file, new_file),
+      (Some(old_file), None) => {
+        entries.push(EntryDiff {
+          old: Some(EntrySummary {
+            size: file_size(old_file),
+            crc: None,
+          }),
+          path,
+          change: EntryChange::Removed,
+          new: None,
+          resource_changes: vec![],
+        });
+        continue;
+      }
+      (None, Some(new_file)) => {
+        entries.push(EntryDiff {
+          new: Some(EntrySummary {
+            size: file_size(new_file),
+            crc: None,
+          }),
+          path,
+          change: EntryChange::Added,
+          old: None,
+          resource_changes: vec![],
+        });
+        continue;
+      }
+      (None, None) => unreachable!(),
+    };
+
+    // Identical stored bytes don't need to be decompressed
+    if old_file.flags() == new_file.flags()
+      && old_file.uncompressed_size() == new_file.uncompressed_size()
+      && old.header().version().has_crc()
+      && new.header().version().has_crc()
+      && old_file.crc() == new_file.crc()
+    {
+      continue;
+    }
+
+    let old_contents = read_contents(old, &path)?;
+    let new_contents = read_contents(new, &path)?;
+    let old_crc = hash(&old_contents);
+    let new_crc = hash(&new_contents);
+    if old_contents.len() == new_contents.len() && old_crc == new_crc {
+      continue;
+    }
+
+    let resource_changes = match ResourceFormat::from_path(&path) {
+      Some(format) if options.compare_resources() => diff_resources(
+        &format.read_resource(&old_contents)?,
+        &format.read_resource(&new_contents)?,
+      ),
+      _ => vec![],
+    };
+    entries.push(EntryDiff {
+      path,
+      change: EntryChange::Changed,
+      old: Some(EntrySummary {
+        size: old_contents.len() as u32,
+        crc: Some(old_crc),
+      }),
+      new: Some(EntrySummary {
+        size: new_contents.len() as u32,
+        crc: Some(new_crc),
+      }),
+      resource_changes,
+    });
+  }
+
+  Ok(PackageDiff { entries })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    compression::{CompressionOptions, Level, Method},
+    lsf::{header::Version, write::LsfWriteExt},
+    lsv::package::Package,
+    resource::{
+      node::{attribute::Attribute, data::Data},
+      value::Value,
+      Resource,
+    },
+    util::arena::ArenaWriter,
+  };
+
+  use super::*;
+
+  fn globals(gold: i32) -> Vec<u8> {
+    let mut resource = Resource::new();
+    let root = resource.alloc(Data::new("root".to_string()), None);
+    let player = resource.alloc(Data::new("Player".to_string()), Some(root));
+    resource
+      .value_mut(player)
+      .attributes_mut()
+      .insert("Gold".to_string(), Attribute::new_value(Value::Int(gold)));
+    let mut contents = vec![];
+    contents.write_lsf_resource(&resource, Version::V3).unwrap();
+    contents
+  }
+
+  fn create_package(path: &Path, files: Vec<(&str, Method, Vec<u8>)>) {
+    let mut package = Package::default();
+    for (name, method, contents) in files {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(name));
+      let flags: u8 = CompressionOptions::new(method, Level::Default).into();
+      file.set_flags(if method == Method::None {
+        0
+      } else {
+        flags as u32
+      });
+      file.set_contents(Some(contents));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    Package::create_file(path)
+      .unwrap()
+      .write(&mut package)
+      .unwrap();
+  }
+
+  #[test]
+  fn test_diff_lsv_packages() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let old_path = dir.join("Old.lsv");
+    let new_path = dir.join("New.lsv");
+    create_package(
+      &old_path,
+      vec![
+        ("Globals.lsf", Method::Lz4, globals(10)),
+        ("Meta.txt", Method::Zlib, b"meta meta meta".to_vec()),
+        ("Removed.txt", Method::None, b"removed".to_vec()),
+      ],
+    );
+    create_package(
+      &new_path,
+      vec![
+        ("Added.txt", Method::None, b"added".to_vec()),
+        ("Globals.lsf", Method::Lz4, globals(25)),
+        // Recompressed, but the same contents
+        ("Meta.txt", Method::None, b"meta meta meta".to_vec()),
+      ],
+    );
+
+    let mut old = Package::open_file(&old_path).unwrap();
+    let mut new = Package::open_file(&new_path).unwrap();
+    let diff = diff_lsv_packages(&mut old, &mut new, &DiffOptions::new()).unwrap();
+    let changes: Vec<(&Path, EntryChange)> = diff
+      .entries()
+      .iter()
+      .map(|entry| (entry.path(), entry.change()))
+      .collect();
+    assert_eq!(
+      changes,
+      vec![
+        (Path::new("Added.txt"), EntryChange::Added),
+        (Path::new("Globals.lsf"), EntryChange::Changed),
+        (Path::new("Removed.txt"), EntryChange::Removed),
+      ]
+    );
+    assert!(diff.entries()[1].resource_changes().is_empty());
+    assert_eq!(diff.entries()[2].old_summary().unwrap().size(), 7);
+
+    let mut options = DiffOptions::new();
+    options.set_compare_resources(true);
+    let diff = old.diff(&mut new, &options).unwrap();
+    assert_eq!(
+      diff.entries()[1].resource_changes(),
+      &vec![ResourceChange::AttributeChanged(
+        "/root/Player".to_string(),
+        "Gold".to_string(),
+        Value::Int(10),
+        Value::Int(25)
+      )]
+    );
+    assert!(old
+      .diff(&mut Package::open_file(&old_path).unwrap(), &options)
+      .unwrap()
+      .is_empty());
+  }
+}
@@ -0,0 +1,307 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  compression::{CompressionOptions, Level, Method},
+  error::Error,
+};
+
+use super::{
+  file::File,
+  package::{part_path, Package, PackageHandle},
+};
+
+// Package paths always use forward slashes
+fn package_path(path: &Path) -> PathBuf {
+  PathBuf::from(path.to_string_lossy().replace('\\', "/"))
+}
+
+fn path_string(path: &Path) -> String {
+  path.to_string_lossy().into_owned()
+}
+
+// Collects changes to an opened package. Nothing is written until the edit is committed, which
+// rewrites the package next to the original and renames it over it.
+pub struct PackageEdit<'a> {
+  handle: &'a mut PackageHandle<fs::File>,
+  // Added or replaced files with their contents, None for removed files
+  changes: BTreeMap<PathBuf, Option<File>>,
+  method: Method,
+  level: Level,
+}
+
+impl<'a> PackageEdit<'a> {
+  pub fn new(handle: &'a mut PackageHandle<fs::File>) -> Self {
+    Self {
+      handle,
+      changes: BTreeMap::new(),
+      method: Method::Lz4,
+      level: Level::Default,
+    }
+  }
+
+  // Compression of added files, replaced files keep their compression
+  pub fn method(&self) -> Method {
+    self.method
+  }
+
+  pub fn set_method(&mut self, method: Method) {
+    self.method = method;
+  }
+
+  pub fn level(&self) -> Level {
+    self.level
+  }
+
+  pub fn set_level(&mut self, level: Level) {
+    self.level = level;
+  }
+
+  pub fn changes(&self) -> &BTreeMap<PathBuf, Option<File>> {
+    &self.changes
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  // Whether the package contains the file once the edit is committed
+  pub fn contains(&self, path: &Path) -> bool {
+    let path = package_path(path);
+    match self.changes.get(&path) {
+      Some(change) => change.is_some(),
+      None => self.handle.file(&path).is_some(),
+    }
+  }
+
+  pub fn add(&mut self, path: &Path, contents: Vec<u8>) -> Result<(), Error> {
+    if self.contains(path) {
+      return Err(Error::FileExists(path_string(path)));
+    }
+
+    let flags: u8 = match self.method {
+      Method::None => 0,
+      method => CompressionOptions::new(method, self.level).into(),
+    };
+    self.insert(path, flags as u32, contents);
+    Ok(())
+  }
+
+  pub fn replace(&mut self, path: &Path, contents: Vec<u8>) -> Result<(), Error> {
+    if !self.contains(path) {
+      return Err(Error::FileNotFound(path_string(path)));
+    }
+
+    let path = package_path(path);
+    let flags = match self.changes.get(&path) {
+      Some(Some(file)) => file.flags(),
+      _ => self.handle.file(&path).unwrap().flags(),
+    };
+    self.insert(&path, flags, contents);
+    Ok(())
+  }
+
+  pub fn remove(&mut self, path: &Path) -> Result<(), Error> {
+    if !self.contains(path) {
+      return Err(Error::FileNotFound(path_string(path)));
+    }
+
+    let path = package_path(path);
+    if self.handle.file(&path).is_some() {
+      self.changes.insert(path, None);
+    } else {
+      // The file has only been added in this edit
+      self.changes.remove(&path);
+    }
+    Ok(())
+  }
+
+  fn insert(&mut self, path: &Path, flags: u32, contents: Vec<u8>) {
+    let path = package_path(path);
+    let mut file = File::new();
+    file.set_path(path.clone());
+    file.set_flags(flags);
+    file.set_contents(Some(contents));
+    self.changes.insert(path, Some(file));
+  }
+
+  // Writes the edited package to temporary files in the same directory and renames them over the
+  // original. Single-file packages are replaced by one rename, so they are never left half written.
+  // The parts of multi-part packages can't be switched at once: they are renamed before the main
+  // archive that references them, so a failure while renaming leaves new parts next to the old
+  // main archive. Unchanged files are copied as stored, only added and replaced files are
+  // compressed. The handle is reopened on the new package afterwards.
+  pub fn commit(self) -> Result<(), Error> {
+    let PackageEdit {
+      handle, changes, ..
+    } = self;
+    let path = handle.path().to_owned();
+    let part_count = handle.header().part_count();
+
+    // Multi-part packages are split again at the size of their largest part
+    let max_part_size = if part_count > 1 {
+      let mut max_part_size = 0;
+      for part in 0..part_count {
+        max_part_size = max_part_size.max(fs::metadata(part_path(&path, part))?.len());
+      }
+      Some(max_part_size)
+    } else {
+      None
+    };
+
+    let mut package = Package::default();
+    *package.header_mut() = *handle.header();
+    // Solid archives are decompressed as a whole when opened, so their files are written from
+    // the contents instead
+    let is_solid = handle.header().is_solid();
+    let file_paths: Vec<PathBuf> = handle.files().keys().cloned().collect();
+    for file_path in file_paths {
+      if changes.contains_key(&file_path) {
+        continue;
+      }
+      let original = handle.file_mut(&file_path).unwrap();
+      let mut file = File::new();
+      file.set_path(file_path.clone());
+      file.set_flags(original.flags());
+      if is_solid {
+        file.set_contents(original.take_contents());
+      } else {
+        file.set_offset(original.offset());
+        file.set_size_on_disk(original.size_on_disk());
+        file.set_uncompressed_size(original.uncompressed_size());
+        file.set_part_index(original.part_index());
+        file.set_crc(original.crc());
+      }
+      package.file_table_mut().insert(file_path, file);
+    }
+    for (file_path, file) in changes {
+      if let Some(file) = file {
+        package.file_table_mut().insert(file_path, file);
+      }
+    }
+
+    let file_name = path
+      .file_name()
+      .ok_or(Error::InvalidPath(path_string(&path)))?;
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let result = Package::create_file(&temp_path).and_then(|mut writer| {
+      writer.set_max_part_size(max_part_size);
+      writer.write_copying_stored(&mut package, &mut |file, stream, md5_context| {
+        handle.copy_stored(file, stream, md5_context)
+      })
+    });
+    if let Err(error) = result {
+      for part in 0..package.header().part_count().max(1) {
+        let _ = fs::remove_file(part_path(&temp_path, part));
+      }
+      return Err(error);
+    }
+
+    // The main archive goes last, it references the parts
+    let new_part_count = package.header().part_count().max(1);
+    for part in (0..new_part_count).rev() {
+      fs::rename(part_path(&temp_path, part), part_path(&path, part))?;
+    }
+    for part in new_part_count..part_count {
+      fs::remove_file(part_path(&path, part))?;
+    }
+
+    *handle = Package::open_file(&path)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::lsv::write::{lsv_archive_hash, sorted_file_paths};
+
+  use super::*;
+
+  fn create_package(path: &Path) {
+    let mut package = Package::default();
+    let flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+    for name in ["Globals.lsf", "Meta.lsx", "Stats.txt"] {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(name));
+      file.set_flags(flags as u32);
+      file.set_contents(Some(format!("{} {} {}", name, name, name).into_bytes()));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    Package::create_file(path)
+      .unwrap()
+      .write(&mut package)
+      .unwrap();
+  }
+
+  #[test]
+  fn test_edit_package() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let path = dir.join("Save.lsv");
+    create_package(&path);
+
+    let mut handle = Package::open_file(&path).unwrap();
+    let meta = handle.file(Path::new("Meta.lsx")).unwrap();
+    let meta_stored = (meta.crc(), meta.size_on_disk(), meta.uncompressed_size());
+    let mut edit = handle.edit();
+    edit
+      .replace(Path::new("Globals.lsf"), b"modified".to_vec())
+      .unwrap();
+    edit.remove(Path::new("Stats.txt")).unwrap();
+    edit.set_method(Method::None);
+    edit
+      .add(Path::new("Mods\\New.txt"), b"new".to_vec())
+      .unwrap();
+    assert!(matches!(
+      edit.add(Path::new("Meta.lsx"), vec![]),
+      Err(Error::FileExists(_))
+    ));
+    assert!(matches!(
+      edit.remove(Path::new("Stats.txt")),
+      Err(Error::FileNotFound(_))
+    ));
+    edit.commit().unwrap();
+    assert!(!dir.join(".Save.lsv.tmp").exists());
+
+    for handle in [&mut handle, &mut Package::open_file(&path).unwrap()] {
+      assert_eq!(handle.files().len(), 3);
+      assert!(handle.file(Path::new("Stats.txt")).is_none());
+      let globals = handle.file(Path::new("Globals.lsf")).unwrap();
+      assert_eq!(globals.compression_method(), Method::Zlib);
+      assert_eq!(
+        handle.file_contents(Path::new("Globals.lsf")).unwrap(),
+        b"modified"
+      );
+      assert_eq!(
+        handle.file_contents(Path::new("Meta.lsx")).unwrap(),
+        b"Meta.lsx Meta.lsx Meta.lsx"
+      );
+      let new_file = handle.file(Path::new("Mods/New.txt")).unwrap();
+      assert_eq!(new_file.compression_method(), Method::None);
+      assert_eq!(
+        handle.file_contents(Path::new("Mods/New.txt")).unwrap(),
+        b"new"
+      );
+    }
+    assert!(handle.verify().unwrap().is_ok());
+
+    // Unchanged files are copied as stored and still count towards the archive hash
+    let meta = handle.file(Path::new("Meta.lsx")).unwrap();
+    assert_eq!(
+      (meta.crc(), meta.size_on_disk(), meta.uncompressed_size()),
+      meta_stored
+    );
+    handle.load_contents().unwrap();
+    let md5 = lsv_archive_hash(
+      sorted_file_paths(handle.package())
+        .iter()
+        .map(|file_path| handle.file(file_path).unwrap().contents().as_ref().unwrap()),
+    );
+    assert_eq!(handle.header().md5(), md5);
+  }
+}
@@ -0,0 +1,618 @@
+use std::{
+  collections::HashSet,
+  fs, io,
+  io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+  path::{Component, Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Mutex,
+  },
+};
+
+use crc32fast::hash;
+use globset::{GlobBuilder, GlobMatcher};
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::{
+  compression::{read::decompress_reader, CompressionOptions},
+  error::Error,
+  lsb::read::LsbReadExt,
+  lsf::read::LsfReadExt,
+  lsx::write::LsxWriteExt,
+  resource::Resource,
+  util::read::BinaryReadExt,
+};
+
+use super::{file::File, header::Header, package::Package};
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+  Glob(GlobMatcher),
+  Regex(Regex),
+}
+
+impl Pattern {
+  // Globs match whole paths, * stays within a directory while ** spans directories
+  pub fn glob(pattern: &str) -> Result<Self, Error> {
+    let glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+    Ok(Self::Glob(glob.compile_matcher()))
+  }
+
+  // Regular expressions match anywhere in the path unless anchored
+  pub fn regex(pattern: &str) -> Result<Self, Error> {
+    Ok(Self::Regex(Regex::new(pattern)?))
+  }
+
+  pub fn is_match(&self, path: &Path) -> bool {
+    // Package paths always use forward slashes
+    let path = path.to_string_lossy().replace('\\', "/");
+    match self {
+      Self::Glob(matcher) => matcher.is_match(&path),
+      Self::Regex(regex) => regex.is_match(&path),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+  Lsx,
+  Json,
+  Yaml,
+}
+
+impl TextFormat {
+  pub fn from_extension(extension: &str) -> Option<Self> {
+    match extension.to_lowercase().as_str() {
+      "lsx" => Some(Self::Lsx),
+      "json" => Some(Self::Json),
+      "yaml" | "yml" => Some(Self::Yaml),
+      _ => None,
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      Self::Lsx => "lsx",
+      Self::Json => "json",
+      Self::Yaml => "yaml",
+    }
+  }
+
+  // LSX replaces the binary format, so it takes over the extension (globals.lsf -> globals.lsx).
+  // JSON and YAML are appended to keep the original format visible (globals.lsf.json).
+  pub fn text_path(&self, path: &Path) -> PathBuf {
+    match self {
+      Self::Lsx => path.with_extension(self.extension()),
+      _ => {
+        let mut text_path = path.as_os_str().to_owned();
+        text_path.push(".");
+        text_path.push(self.extension());
+        PathBuf::from(text_path)
+      }
+    }
+  }
+
+  pub fn write_resource<W: Write>(&self, writer: &mut W, resource: &Resource) -> Result<(), Error> {
+    match self {
+      Self::Lsx => writer.write_lsx_resource(resource)?,
+      Self::Json => serde_json::to_writer_pretty(&mut *writer, resource)?,
+      Self::Yaml => serde_yaml::to_writer(&mut *writer, resource)?,
+    }
+    Ok(())
+  }
+}
+
+// Binary resource formats that can be converted to text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceFormat {
+  Lsf,
+  Lsb,
+}
+
+impl ResourceFormat {
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+      "lsf" => Some(Self::Lsf),
+      "lsb" => Some(Self::Lsb),
+      _ => None,
+    }
+  }
+
+  pub fn read_resource(&self, contents: &[u8]) -> Result<Resource, Error> {
+    let mut cursor = Cursor::new(contents);
+    match self {
+      Self::Lsf => cursor.read_lsf_resource(),
+      Self::Lsb => cursor.read_lsb_resource(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnpackEntry {
+  path: PathBuf,
+  target_path: PathBuf,
+  conversion: Option<(ResourceFormat, TextFormat, PathBuf)>,
+  keep_binary: bool,
+}
+
+impl UnpackEntry {
+  // Path of the file in the package
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  // Path of the unpacked file, relative to the target directory
+  pub fn target_path(&self) -> &Path {
+    &self.target_path
+  }
+
+  // Path of the converted text file, relative to the target directory
+  pub fn text_path(&self) -> Option<&Path> {
+    self
+      .conversion
+      .as_ref()
+      .map(|(_, _, text_path)| text_path.as_path())
+  }
+
+  // Whether the file is written as stored in the package
+  pub fn writes_binary(&self) -> bool {
+    self.conversion.is_none() || self.keep_binary
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UnpackOptions {
+  includes: Vec<Pattern>,
+  excludes: Vec<Pattern>,
+  rewrites: Vec<(PathBuf, PathBuf)>,
+  flatten: bool,
+  dry_run: bool,
+  convert: Option<TextFormat>,
+  keep_binary: bool,
+}
+
+impl UnpackOptions {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn includes(&self) -> &Vec<Pattern> {
+    &self.includes
+  }
+
+  // Once any include pattern is added, only files matching one of them are unpacked
+  pub fn add_include(&mut self, pattern: Pattern) {
+    self.includes.push(pattern);
+  }
+
+  pub fn excludes(&self) -> &Vec<Pattern> {
+    &self.excludes
+  }
+
+  // Files matching any exclude pattern are skipped, even if they are included
+  pub fn add_exclude(&mut self, pattern: Pattern) {
+    self.excludes.push(pattern);
+  }
+
+  pub fn rewrites(&self) -> &Vec<(PathBuf, PathBuf)> {
+    &self.rewrites
+  }
+
+  // Replaces the leading directories `from` with `to` in the target path. Only the first matching rewrite is applied.
+  pub fn add_rewrite(&mut self, from: &Path, to: &Path) {
+    self.rewrites.push((from.to_owned(), to.to_owned()));
+  }
+
+  pub fn flatten(&self) -> bool {
+    self.flatten
+  }
+
+  // Unpacks all files directly into the target directory, after rewrites have been applied
+  pub fn set_flatten(&mut self, flatten: bool) {
+    self.flatten = flatten;
+  }
+
+  pub fn dry_run(&self) -> bool {
+    self.dry_run
+  }
+
+  // Only selects the files without writing anything
+  pub fn set_dry_run(&mut self, dry_run: bool) {
+    self.dry_run = dry_run;
+  }
+
+  pub fn convert(&self) -> Option<TextFormat> {
+    self.convert
+  }
+
+  // Converts LSF and LSB resources to the given text format instead of writing them as they are
+  pub fn set_convert(&mut self, convert: Option<TextFormat>) {
+    self.convert = convert;
+  }
+
+  pub fn keep_binary(&self) -> bool {
+    self.keep_binary
+  }
+
+  // Writes converted resources next to the binary instead of replacing it
+  pub fn set_keep_binary(&mut self, keep_binary: bool) {
+    self.keep_binary = keep_binary;
+  }
+
+  pub fn is_selected(&self, path: &Path) -> bool {
+    (self.includes.is_empty() || self.includes.iter().any(|pattern| pattern.is_match(path)))
+      && !self.excludes.iter().any(|pattern| pattern.is_match(path))
+  }
+
+  pub fn target_path(&self, path: &Path) -> PathBuf {
+    let mut target_path = path.to_owned();
+    for (from, to) in &self.rewrites {
+      if let Ok(rest) = path.strip_prefix(from) {
+        target_path = to.join(rest);
+        break;
+      }
+    }
+    if self.flatten {
+      if let Some(file_name) = target_path.file_name() {
+        target_path = PathBuf::from(file_name);
+      }
+    }
+    target_path
+  }
+
+  // Selects the files to unpack in the given order. Target paths must stay within the target
+  // directory and may not collide (e.g. when flattening).
+  pub fn select<'a, I: IntoIterator<Item = &'a PathBuf>>(
+    &self,
+    file_paths: I,
+  ) -> Result<Vec<UnpackEntry>, Error> {
+    let mut entries = vec![];
+    let mut target_paths = HashSet::new();
+    for file_path in file_paths {
+      if !self.is_selected(file_path) {
+        continue;
+      }
+
+      let target_path = self.target_path(file_path);
+      let conversion = match (ResourceFormat::from_path(file_path), self.convert) {
+        (Some(resource_format), Some(text_format)) => Some((
+          resource_format,
+          text_format,
+          text_format.text_path(&target_path),
+        )),
+        _ => None,
+      };
+      let entry = UnpackEntry {
+        path: file_path.to_owned(),
+        target_path,
+        conversion,
+        keep_binary: self.keep_binary,
+      };
+
+      let mut written_paths = entry.text_path().into_iter().collect::<Vec<_>>();
+      if entry.writes_binary() {
+        written_paths.push(entry.target_path());
+      }
+      for written_path in written_paths {
+        let is_relative = written_path
+          .components()
+          .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !is_relative || !target_paths.insert(written_path.to_owned()) {
+          return Err(Error::InvalidPath(
+            written_path.to_string_lossy().into_owned(),
+          ));
+        }
+      }
+      entries.push(entry);
+    }
+    Ok(entries)
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnpackProgress {
+  path: PathBuf,
+  files_done: usize,
+  file_count: usize,
+  bytes_done: u64,
+  total_bytes: u64,
+}
+
+impl UnpackProgress {
+  // The file that has just been written
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn files_done(&self) -> usize {
+    self.files_done
+  }
+
+  pub fn file_count(&self) -> usize {
+    self.file_count
+  }
+
+  // Uncompressed bytes written so far
+  pub fn bytes_done(&self) -> u64 {
+    self.bytes_done
+  }
+
+  pub fn total_bytes(&self) -> u64 {
+    self.total_bytes
+  }
+}
+
+fn uncompressed_size(file: &File) -> u64 {
+  if file.is_compressed() {
+    file.uncompressed_size() as u64
+  } else {
+    file.size_on_disk() as u64
+  }
+}
+
+// Reads the stored bytes of a file. The readers are shared by all workers, so only one of them
+// reads at a time while the others decompress and write.
+fn read_lsv_file_bytes<R: Read + Seek>(
+  readers: &Mutex<&mut [R]>,
+  header: &Header,
+  file: &File,
+) -> Result<Vec<u8>, Error> {
+  let mut readers = readers.lock().unwrap();
+  let reader = readers
+    .get_mut(file.part_index() as usize)
+    .ok_or(Error::InvalidFileTable)?;
+  reader.seek(SeekFrom::Start(file.offset()))?;
+  let bytes = reader.read_bytes(file.size_on_disk() as usize)?;
+  drop(readers);
+
+  if header.version().has_crc() {
+    let crc = hash(&bytes);
+    if crc != file.crc() {
+      return Err(Error::CrcMismatch(file.crc(), crc));
+    }
+  }
+  Ok(bytes)
+}
+
+fn create_target_file(target_path: &Path) -> Result<fs::File, Error> {
+  let file_dir = target_path.parent().ok_or(Error::InvalidPath(
+    target_path.to_string_lossy().into_owned(),
+  ))?;
+  fs::create_dir_all(file_dir)?;
+  Ok(fs::File::create(target_path)?)
+}
+
+// Extracts the given files into their target paths below target_dir. Files are decompressed and written in parallel and
+// never cached in the file table, so memory only depends on the size of the files in flight.
+// Entries with a conversion are parsed and written as text instead of (or next to) the binary.
+// The progress callback is invoked from the worker threads after every written file.
+pub fn extract_lsv_files<R: Read + Seek + Send>(
+  readers: &mut [R],
+  package: &Package,
+  entries: &[UnpackEntry],
+  target_dir: &Path,
+  progress: &(dyn Fn(&UnpackProgress) + Sync),
+) -> Result<(), Error> {
+  let mut files = Vec::with_capacity(entries.len());
+  for entry in entries {
+    let file = package.file(entry.path()).ok_or(Error::FileNotFound(
+      entry.path().to_string_lossy().into_owned(),
+    ))?;
+    files.push((file, entry));
+  }
+
+  let file_count = files.len();
+  let total_bytes = files.iter().map(|(file, _)| uncompressed_size(file)).sum();
+  let files_done = AtomicUsize::new(0);
+  let bytes_done = AtomicU64::new(0);
+  let is_solid = package.header().is_solid();
+  let readers = Mutex::new(readers);
+
+  files.par_iter().try_for_each(|(file, entry)| {
+    // Solid archives are decompressed as a whole while reading the file table
+    let mut reader: Box<dyn Read> = if is_solid {
+      let contents = file
+        .contents()
+        .as_ref()
+        .ok_or(Error::FileEmpty(file.path().to_string_lossy().into_owned()))?;
+      Box::new(contents.as_slice())
+    } else {
+      let bytes = read_lsv_file_bytes(&readers, package.header(), file)?;
+      let compression_options: CompressionOptions = file.compression_method().into();
+      decompress_reader(
+        Cursor::new(bytes),
+        file.uncompressed_size() as usize,
+        compression_options,
+      )?
+    };
+
+    let written = match &entry.conversion {
+      // Resources have to be parsed as a whole, so they are buffered in memory
+      Some((resource_format, text_format, text_path)) => {
+        let mut contents = vec![];
+        reader.read_to_end(&mut contents)?;
+        let resource = resource_format.read_resource(&contents)?;
+        let mut text_file = BufWriter::new(create_target_file(&target_dir.join(text_path))?);
+        text_format.write_resource(&mut text_file, &resource)?;
+        text_file.flush()?;
+        if entry.writes_binary() {
+          let mut file_handle = create_target_file(&target_dir.join(entry.target_path()))?;
+          file_handle.write_all(&contents)?;
+        }
+        contents.len() as u64
+      }
+      None => {
+        let mut file_handle = create_target_file(&target_dir.join(entry.target_path()))?;
+        io::copy(&mut reader, &mut file_handle)?
+      }
+    };
+
+    progress(&UnpackProgress {
+      path: file.path().to_owned(),
+      files_done: files_done.fetch_add(1, Ordering::Relaxed) + 1,
+      file_count,
+      bytes_done: bytes_done.fetch_add(written, Ordering::Relaxed) + written,
+      total_bytes,
+    });
+    Ok(())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::AtomicBool;
+
+  use crate::{
+    compression::{Level, Method},
+    lsv::{header::Version, write::LsvWriteExt},
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_extract_lsv_files() {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    let flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+    let mut file_paths = vec![];
+    for index in 0..16 {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(format!("Textures/{}.dds", index)));
+      file.set_flags(flags as u32);
+      file.set_contents(Some(vec![index as u8; 1000]));
+      file_paths.push(file.path().to_owned());
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    let mut writer = Cursor::new(vec![]);
+    writer.write_lsv_package(&mut package).unwrap();
+
+    let entries = UnpackOptions::new().select(&file_paths).unwrap();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let target_dir = temp_dir.path();
+    let completed = AtomicBool::new(false);
+    extract_lsv_files(&mut [writer], &package, &entries, target_dir, &|progress| {
+      assert_eq!(progress.file_count(), 16);
+      assert_eq!(progress.total_bytes(), 16000);
+      if progress.files_done() == 16 {
+        assert_eq!(progress.bytes_done(), 16000);
+        completed.store(true, Ordering::Relaxed);
+      }
+    })
+    .unwrap();
+    assert!(completed.load(Ordering::Relaxed));
+
+    for (index, file_path) in file_paths.iter().enumerate() {
+      let contents = fs::read(target_dir.join(file_path)).unwrap();
+      assert_eq!(contents, vec![index as u8; 1000]);
+    }
+  }
+
+  #[test]
+  fn test_extract_lsv_files_converted() {
+    let lsb =
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/UILayout.lsb");
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    let mut file = File::new();
+    file.set_path(PathBuf::from("Profile/UILayout.lsb"));
+    file.set_contents(Some(lsb.to_vec()));
+    let file_paths = vec![file.path().to_owned()];
+    package
+      .file_table_mut()
+      .insert(file.path().to_owned(), file);
+    let mut writer = Cursor::new(vec![]);
+    writer.write_lsv_package(&mut package).unwrap();
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let target_dir = temp_dir.path();
+    for (text_format, keep_binary, text_path) in [
+      (TextFormat::Lsx, false, "Profile/UILayout.lsx"),
+      (TextFormat::Json, true, "Profile/UILayout.lsb.json"),
+    ] {
+      let mut options = UnpackOptions::new();
+      options.set_convert(Some(text_format));
+      options.set_keep_binary(keep_binary);
+      let entries = options.select(&file_paths).unwrap();
+      assert_eq!(entries[0].text_path(), Some(Path::new(text_path)));
+      assert_eq!(entries[0].writes_binary(), keep_binary);
+      extract_lsv_files(
+        &mut [writer.clone()],
+        &package,
+        &entries,
+        target_dir,
+        &|_| {},
+      )
+      .unwrap();
+      assert!(target_dir.join(text_path).is_file());
+    }
+
+    let lsx = fs::read_to_string(target_dir.join("Profile/UILayout.lsx")).unwrap();
+    assert!(lsx.starts_with("<?xml"));
+    assert!(lsx.contains("<region id="));
+    let json = fs::read_to_string(target_dir.join("Profile/UILayout.lsb.json")).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(!value["roots"].as_array().unwrap().is_empty());
+    assert_eq!(
+      fs::read(target_dir.join("Profile/UILayout.lsb")).unwrap(),
+      lsb.to_vec()
+    );
+  }
+
+  #[test]
+  fn test_unpack_options_select() {
+    let file_paths: Vec<PathBuf> = [
+      "Public/Shared/Stats.txt",
+      "Public/Shared/Levels/Cyseal.lsf",
+      "Mods/Shared/meta.lsx",
+      "Localization/English.loca",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+
+    let mut options = UnpackOptions::new();
+    options.add_include(Pattern::glob("Public/**/*.lsf").unwrap());
+    options.add_include(Pattern::regex(r"\.lsx$").unwrap());
+    options.add_exclude(Pattern::glob("Mods/*/meta.*").unwrap());
+    let entries = options.select(&file_paths).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+      entries[0].path(),
+      Path::new("Public/Shared/Levels/Cyseal.lsf")
+    );
+    assert_eq!(entries[0].target_path(), entries[0].path());
+
+    // * doesn't cross directories
+    let mut options = UnpackOptions::new();
+    options.add_include(Pattern::glob("Public/*.txt").unwrap());
+    assert!(options.select(&file_paths).unwrap().is_empty());
+
+    let mut options = UnpackOptions::new();
+    options.add_rewrite(Path::new("Public/Shared"), Path::new("Shared"));
+    let entries = options.select(&file_paths).unwrap();
+    assert_eq!(
+      entries[1].target_path(),
+      Path::new("Shared/Levels/Cyseal.lsf")
+    );
+    assert_eq!(
+      entries[3].target_path(),
+      Path::new("Localization/English.loca")
+    );
+
+    options.set_flatten(true);
+    let entries = options.select(&file_paths).unwrap();
+    assert_eq!(entries[1].target_path(), Path::new("Cyseal.lsf"));
+
+    // Target paths may neither collide nor leave the target directory
+    assert!(options
+      .select(&[PathBuf::from("a/x.txt"), PathBuf::from("b/x.txt")])
+      .is_err());
+    let mut options = UnpackOptions::new();
+    options.add_rewrite(Path::new("Mods"), Path::new(".."));
+    assert!(options.select(&file_paths).is_err());
+  }
+}
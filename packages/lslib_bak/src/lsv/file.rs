@@ -9,7 +9,7 @@ use crate::compression::Method;
 #[derive(Default)]
 pub struct File {
   path: PathBuf,
-  offset: u32,
+  offset: u64,
   size_on_disk: u32,
   uncompressed_size: u32,
   part_index: u32,
@@ -31,11 +31,11 @@ impl File {
     self.path = name;
   }
 
-  pub fn offset(&self) -> u32 {
+  pub fn offset(&self) -> u64 {
     self.offset
   }
 
-  pub fn set_offset(&mut self, offset: u32) {
+  pub fn set_offset(&mut self, offset: u64) {
     self.offset = offset;
   }
 
@@ -87,6 +87,10 @@ impl File {
     self.contents = contents;
   }
 
+  pub fn take_contents(&mut self) -> Option<Vec<u8>> {
+    self.contents.take()
+  }
+
   pub fn clear_contents(&mut self) {
     self.contents = None;
   }
@@ -99,6 +103,7 @@ impl File {
     match self.flags & 0x0f {
       1 => Method::Zlib,
       2 => Method::Lz4,
+      3 => Method::Zstd,
       _ => Method::None,
     }
   }
@@ -2,7 +2,7 @@ pub type Signature = [u8; 4];
 
 pub const SIGNATURE: Signature = [0x4c, 0x53, 0x50, 0x4b]; // "LSPK"
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
 #[repr(i32)]
 pub enum Version {
   V7 = 7,   // D:OS 1
@@ -11,6 +11,7 @@ pub enum Version {
   V13 = 13, // D:OS 2 DE
   V15 = 15, // BG3 EA
   V16 = 16, // BG3 EA Patch4
+  V18 = 18, // BG3 Release
 }
 
 impl From<i32> for Version {
@@ -22,6 +23,7 @@ impl From<i32> for Version {
       13 => Version::V13,
       15 => Version::V15,
       16 => Version::V16,
+      18 => Version::V18,
       _ => panic!("Invalid package version: {}", version),
     }
   }
@@ -39,18 +41,37 @@ impl Default for Version {
   }
 }
 
+impl Version {
+  // The file tables of v7, v9 and v18 don't store CRCs
+  pub fn has_crc(&self) -> bool {
+    matches!(
+      self,
+      Version::V10 | Version::V13 | Version::V15 | Version::V16
+    )
+  }
+
+  // The header stores the archive hash since v13
+  pub fn has_md5(&self) -> bool {
+    *self >= Version::V13
+  }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Header {
   version: Version,
   // The number of parts this package consists of.
   part_count: u16,
   // Offset of the file table in the main archive
-  file_table_offset: u32,
+  file_table_offset: u64,
   file_table_size: u32,
+  // Offset of the file data in the main archive (v7 - v10). File offsets in part 0 are relative to it.
+  data_offset: u32,
   // Package flags bitmask. Allowed values are in the PackageFlags enumeration.
   flags: u8,
   // Load priority. Packages with higher priority are loaded later (i.e. they override earlier packages).
   priority: u8,
+  // MD5 over the contents of all files (v13+), with every byte incremented by one. Zero if not present.
+  md5: [u8; 16],
 }
 
 impl Header {
@@ -82,11 +103,11 @@ impl Header {
     self.part_count = part_count;
   }
 
-  pub fn file_table_offset(&self) -> u32 {
+  pub fn file_table_offset(&self) -> u64 {
     self.file_table_offset
   }
 
-  pub fn set_file_table_offset(&mut self, file_table_offset: u32) {
+  pub fn set_file_table_offset(&mut self, file_table_offset: u64) {
     self.file_table_offset = file_table_offset;
   }
 
@@ -98,6 +119,14 @@ impl Header {
     self.file_table_size = file_table_size;
   }
 
+  pub fn data_offset(&self) -> u32 {
+    self.data_offset
+  }
+
+  pub fn set_data_offset(&mut self, data_offset: u32) {
+    self.data_offset = data_offset;
+  }
+
   pub fn flags(&self) -> u8 {
     self.flags
   }
@@ -114,6 +143,14 @@ impl Header {
     self.priority = priority;
   }
 
+  pub fn md5(&self) -> [u8; 16] {
+    self.md5
+  }
+
+  pub fn set_md5(&mut self, md5: [u8; 16]) {
+    self.md5 = md5;
+  }
+
   pub fn is_solid(&self) -> bool {
     self.flags & Header::FLAGS_SOLID != 0
   }
@@ -126,8 +163,10 @@ impl Default for Header {
       part_count: 0,
       file_table_offset: 0,
       file_table_size: 0,
+      data_offset: 0,
       flags: Header::FLAGS_NONE,
       priority: 0,
+      md5: [0; 16],
     }
   }
 }
@@ -1,4 +1,12 @@
+pub mod async_package;
+pub mod diff;
+pub mod edit;
+pub mod extract;
 pub mod file;
 pub mod header;
+pub mod pack;
 pub mod package;
+pub mod package_set;
 pub mod read;
+pub mod verify;
+pub mod write;
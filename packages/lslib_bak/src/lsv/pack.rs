@@ -0,0 +1,245 @@
+use std::{
+  fs,
+  io::Cursor,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  compression::{CompressionOptions, Level, Method},
+  error::Error,
+  lsf::{self, write::LsfWriteExt},
+  lsx::read::LsxReadExt,
+};
+
+use super::{
+  file::File,
+  header::{Header, Version},
+  package::Package,
+  package_set::collect_file_paths,
+};
+
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+  version: Version,
+  method: Method,
+  level: Level,
+  priority: u8,
+  flags: u8,
+  convert_lsx: bool,
+  lsf_version: Option<lsf::header::Version>,
+}
+
+impl Default for PackOptions {
+  fn default() -> Self {
+    Self {
+      version: Default::default(),
+      method: Method::Lz4,
+      level: Level::Default,
+      priority: 0,
+      flags: Header::FLAGS_NONE,
+      convert_lsx: false,
+      lsf_version: None,
+    }
+  }
+}
+
+impl PackOptions {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn version(&self) -> Version {
+    self.version
+  }
+
+  pub fn set_version(&mut self, version: Version) {
+    self.version = version;
+  }
+
+  pub fn method(&self) -> Method {
+    self.method
+  }
+
+  pub fn set_method(&mut self, method: Method) {
+    self.method = method;
+  }
+
+  pub fn level(&self) -> Level {
+    self.level
+  }
+
+  pub fn set_level(&mut self, level: Level) {
+    self.level = level;
+  }
+
+  pub fn priority(&self) -> u8 {
+    self.priority
+  }
+
+  pub fn set_priority(&mut self, priority: u8) {
+    self.priority = priority;
+  }
+
+  // Package flags, see the Header::FLAGS_* constants
+  pub fn flags(&self) -> u8 {
+    self.flags
+  }
+
+  pub fn set_flags(&mut self, flags: u8) {
+    self.flags = flags;
+  }
+
+  pub fn is_solid(&self) -> bool {
+    self.flags & Header::FLAGS_SOLID != 0
+  }
+
+  // Solid packages compress all files into a single LZ4 frame (v13 only)
+  pub fn set_solid(&mut self, solid: bool) {
+    if solid {
+      self.flags |= Header::FLAGS_SOLID;
+    } else {
+      self.flags &= !Header::FLAGS_SOLID;
+    }
+  }
+
+  pub fn convert_lsx(&self) -> bool {
+    self.convert_lsx
+  }
+
+  // Converts LSX resources to LSF, the packed files take the .lsf extension
+  pub fn set_convert_lsx(&mut self, convert_lsx: bool) {
+    self.convert_lsx = convert_lsx;
+  }
+
+  // The LSF version converted resources are written with. Unless set, it's the version the game
+  // of the package version uses.
+  pub fn lsf_version(&self) -> lsf::header::Version {
+    self.lsf_version.unwrap_or(match self.version {
+      Version::V7 | Version::V9 => lsf::header::Version::V1,
+      Version::V10 => lsf::header::Version::V2,
+      Version::V13 => lsf::header::Version::V3,
+      Version::V15 => lsf::header::Version::V4,
+      Version::V16 => lsf::header::Version::V5,
+      Version::V18 => lsf::header::Version::V6,
+    })
+  }
+
+  pub fn set_lsf_version(&mut self, lsf_version: Option<lsf::header::Version>) {
+    self.lsf_version = lsf_version;
+  }
+}
+
+fn is_lsx(path: &Path) -> bool {
+  path
+    .extension()
+    .map(|extension| extension.eq_ignore_ascii_case("lsx"))
+    .unwrap_or(false)
+}
+
+// Builds a package from all files below a directory, with the contents loaded. The package is
+// written with Package::create_file or LsvWriteExt::write_lsv_package.
+pub fn pack_directory(dir: &Path, options: &PackOptions) -> Result<Package, Error> {
+  let mut file_paths = vec![];
+  collect_file_paths(dir, Path::new(""), &mut file_paths)?;
+  file_paths.sort();
+
+  let mut package = Package::default();
+  let header = package.header_mut();
+  header.set_version(options.version());
+  header.set_priority(options.priority());
+  header.set_flags(options.flags());
+
+  let flags: u8 = match options.method() {
+    Method::None => 0,
+    method => CompressionOptions::new(method, options.level()).into(),
+  };
+
+  for file_path in file_paths {
+    let mut contents = fs::read(dir.join(&file_path))?;
+    let mut path = file_path;
+    if options.convert_lsx() && is_lsx(&path) {
+      let resource = Cursor::new(&contents).read_lsx_resource()?;
+      contents = vec![];
+      contents.write_lsf_resource(&resource, options.lsf_version())?;
+      path.set_extension("lsf");
+    }
+
+    // Converted resources may collide with binaries of the same name
+    if package.file(&path).is_some() {
+      return Err(Error::InvalidPath(path.to_string_lossy().into_owned()));
+    }
+
+    let mut file = File::new();
+    file.set_path(PathBuf::from(path.to_string_lossy().replace('\\', "/")));
+    file.set_flags(flags as u32);
+    file.set_contents(Some(contents));
+    package
+      .file_table_mut()
+      .insert(file.path().to_owned(), file);
+  }
+
+  Ok(package)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    lsv::package::Transform,
+    resource::{node::data::Kind, value::Value},
+    util::arena::ArenaReader,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_pack_directory() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let source_dir = dir.join("Mod");
+    fs::create_dir_all(source_dir.join("Mods/Test")).unwrap();
+    fs::write(source_dir.join("Stats.txt"), "new entry \"Test\"").unwrap();
+    fs::copy(
+      "../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/modsettings.lsx",
+      source_dir.join("Mods/Test/meta.lsx"),
+    )
+    .unwrap();
+
+    let mut options = PackOptions::new();
+    options.set_version(Version::V13);
+    options.set_method(Method::Zlib);
+    options.set_priority(3);
+    options.set_convert_lsx(true);
+    let mut package = pack_directory(&source_dir, &options).unwrap();
+    assert_eq!(package.files().len(), 2);
+
+    let path = dir.join("Test.pak");
+    Package::create_file(&path)
+      .unwrap()
+      .write(&mut package)
+      .unwrap();
+
+    let mut handle = Package::open_file(&path).unwrap();
+    assert_eq!(handle.header().priority(), 3);
+    let file = handle.file(Path::new("Stats.txt")).unwrap();
+    assert_eq!(file.compression_method(), Method::Zlib);
+    assert_eq!(
+      handle.file_contents(Path::new("Stats.txt")).unwrap(),
+      &b"new entry \"Test\"".to_vec()
+    );
+
+    let resource = handle.lsf_file(Path::new("Mods/Test/meta.lsf")).unwrap();
+    let root = resource.root_indexes()[0];
+    assert_eq!(resource.value(root).kind(), &Kind::Element);
+    assert_eq!(resource.value(root).name(), "root");
+    let module = resource.child_indexes(resource.child_indexes(root)[0])[0];
+    assert_eq!(
+      resource
+        .value(module)
+        .attributes()
+        .get("Name")
+        .unwrap()
+        .value(),
+      &Value::FixedString("Main".to_string())
+    );
+  }
+}
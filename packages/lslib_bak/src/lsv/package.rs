@@ -6,14 +6,22 @@ use std::{fmt::Formatter, path::Path};
 
 use std::fs;
 
+use memmap2::Mmap;
+
 use crate::error::Error;
 use crate::lsb::read::LsbReadExt;
-use crate::lsf::read::LsfReadExt;
+use crate::lsf::{lazy::LazyResource, read::LsfReadExt};
 use crate::resource::Resource;
 
+use super::async_package::AsyncPackageHandle;
+use super::diff::{diff_lsv_packages, DiffOptions, PackageDiff};
+use super::edit::PackageEdit;
+use super::extract::{extract_lsv_files, UnpackEntry, UnpackOptions, UnpackProgress};
 use super::file::{File, FileTable};
 use super::header::Header;
-use super::read::{read_lsv_file_contents, LsvReadExt};
+use super::read::{copy_lsv_file_stored, read_lsv_file_contents, read_lsv_file_stream, LsvReadExt};
+use super::verify::{verify_lsv_package, VerifyReport};
+use super::write::{sorted_file_paths, write_lsv_package_parts, CopyStored, CreatePart};
 
 pub type FileMap = HashMap<String, File>;
 
@@ -28,6 +36,27 @@ impl Package {
     PackageHandle::open(path, |path| Ok(fs::File::open(path)?))
   }
 
+  pub async fn open_file_async(path: &Path) -> Result<AsyncPackageHandle<tokio::fs::File>, Error> {
+    AsyncPackageHandle::open(path, |path| async move {
+      Ok(tokio::fs::File::open(path).await?)
+    })
+    .await
+  }
+
+  // Maps the package and its parts into memory instead of reading them through file handles
+  pub fn open_mapped_file(path: &Path) -> Result<PackageHandle<Cursor<Mmap>>, Error> {
+    PackageHandle::open(path, |path| {
+      let file = fs::File::open(path)?;
+      // Safety: The package must not be modified by other processes while it's mapped
+      let mmap = unsafe { Mmap::map(&file)? };
+      Ok(Cursor::new(mmap))
+    })
+  }
+
+  pub fn create_file(path: &Path) -> Result<PackageWriter<fs::File>, Error> {
+    PackageWriter::create(path, |path| Ok(fs::File::create(path)?))
+  }
+
   pub fn header(&self) -> &Header {
     &self.header
   }
@@ -76,21 +105,23 @@ pub struct PackageHandle<I> {
   streams: Vec<I>,
 }
 
-fn part_path(path: &Path, part: u16) -> PathBuf {
-  path.parent().unwrap().join(format!(
-    "{}_{}{}",
-    path.file_name().unwrap().to_str().unwrap(),
-    part,
-    path.extension().unwrap().to_str().unwrap()
-  ))
+// Parts following the main archive are named like the game does, e.g. Textures.pak, Textures_1.pak, Textures_2.pak
+pub fn part_path(path: &Path, part: u16) -> PathBuf {
+  if part == 0 {
+    return path.to_owned();
+  }
+
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  let file_name = match path.extension() {
+    Some(extension) => format!("{}_{}.{}", stem, part, extension.to_string_lossy()),
+    None => format!("{}_{}", stem, part),
+  };
+  path.with_file_name(file_name)
 }
 
-fn part_paths(path: &Path, size: u16) -> Vec<PathBuf> {
-  let mut part_files: Vec<PathBuf> = Vec::with_capacity(size as usize);
-  for index in 0..size {
-    part_files.push(part_path(path, index))
-  }
-  part_files
+// Paths of all parts except the main archive
+fn part_paths(path: &Path, part_count: u16) -> Vec<PathBuf> {
+  (1..part_count).map(|part| part_path(path, part)).collect()
 }
 
 impl<I> PackageHandle<I> {
@@ -151,17 +182,13 @@ impl<I: Read + Seek> PackageHandle<I> {
     create_reader: F,
   ) -> Result<PackageHandle<I>, Error> {
     let mut package = Package::default();
-    let dir = path
-      .parent()
-      .ok_or(Error::InvalidPath(path.to_str().unwrap().to_owned()))?;
-    fs::create_dir_all(dir)?;
     let mut main_buffer = create_reader(path)?;
     main_buffer.read_lsv_package(&mut package)?;
     let header = package.header();
 
     // Create all buffers, including opening all related part files
     let mut streams: Vec<I> = Vec::with_capacity(header.part_count() as usize);
-    let package_part_paths = part_paths(&path, header.part_count() - 1);
+    let package_part_paths = part_paths(path, header.part_count());
     streams.insert(0, main_buffer);
     for (index, part_path) in package_part_paths.iter().enumerate() {
       let part_buffer = create_reader(part_path)?;
@@ -176,9 +203,61 @@ impl<I: Read + Seek> PackageHandle<I> {
     Ok(package)
   }
 
+  pub fn load_contents(&mut self) -> Result<(), Error> {
+    let file_paths: Vec<PathBuf> = self.package.files().keys().cloned().collect();
+    for file_path in file_paths {
+      self.file_contents(&file_path)?;
+    }
+    Ok(())
+  }
+
+  // Checks the integrity of all files and the archive hash, see verify_lsv_package
+  pub fn verify(&mut self) -> Result<VerifyReport, Error> {
+    verify_lsv_package(&mut self.streams, &self.package)
+  }
+
+  // Compares this package with a newer one, see diff_lsv_packages
+  pub fn diff<J: Read + Seek>(
+    &mut self,
+    other: &mut PackageHandle<J>,
+    options: &DiffOptions,
+  ) -> Result<PackageDiff, Error> {
+    diff_lsv_packages(self, other, options)
+  }
+
+  pub fn into_package(self) -> Package {
+    self.package
+  }
+
+  // Copies the stored bytes of a file of this package, see copy_lsv_v13_file_stored
+  pub fn copy_stored(
+    &mut self,
+    file: &File,
+    writer: &mut dyn Write,
+    md5_context: &mut md5::Context,
+  ) -> Result<(), Error> {
+    copy_lsv_file_stored(
+      &mut self.streams,
+      &self.package.header,
+      file,
+      writer,
+      md5_context,
+    )
+  }
+
+  // Streams the uncompressed contents of a file without caching them in the file table
+  pub fn file_stream(&mut self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+    let file = self
+      .package
+      .file_table
+      .file(path)
+      .ok_or(Error::FileNotFound(path.to_string_lossy().into_owned()))?;
+    read_lsv_file_stream(&mut self.streams, &self.package.header, file)
+  }
+
   pub fn file_contents(&mut self, path: &Path) -> Result<&Vec<u8>, Error> {
     if self.package.file_table.file(path).is_none() {
-      return Err(Error::FileNotFound(path.to_str().unwrap().to_owned()));
+      return Err(Error::FileNotFound(path.to_string_lossy().into_owned()));
     }
 
     if let None = self.package.file_table.file(path).unwrap().contents() {
@@ -195,29 +274,178 @@ impl<I: Read + Seek> PackageHandle<I> {
   }
 }
 
-pub trait Unpack {
-  fn unpack(&mut self, target_dir: &Path) -> Result<(), Error>;
+impl PackageHandle<fs::File> {
+  // Starts an edit session, see PackageEdit::commit
+  pub fn edit(&mut self) -> PackageEdit<'_> {
+    PackageEdit::new(self)
+  }
 }
 
-impl<I: Read + Seek> Unpack for PackageHandle<I> {
-  fn unpack(&mut self, target_dir: &Path) -> Result<(), Error> {
-    let file_paths: Vec<PathBuf> = self.package.files().keys().cloned().collect();
-    for file_path in file_paths {
-      let target_path = target_dir.join(&file_path);
-      let contents = self.file_contents(&file_path)?;
-      let file_dir = target_path
-        .parent()
-        .ok_or(Error::InvalidPath(target_path.to_str().unwrap().to_owned()))?;
-      fs::create_dir_all(file_dir)?;
-      let mut file_handle = fs::File::create(target_path)?;
-      file_handle.write_all(contents)?;
+impl<T: AsRef<[u8]>> PackageHandle<Cursor<T>> {
+  // Borrows the contents of an uncompressed file directly from the underlying buffer (e.g. a
+  // memory map). Returns None for compressed files and solid archives, use file_stream for them.
+  pub fn file_slice(&self, path: &Path) -> Result<Option<&[u8]>, Error> {
+    let file = self
+      .package
+      .file_table
+      .file(path)
+      .ok_or(Error::FileNotFound(path.to_string_lossy().into_owned()))?;
+    if file.is_compressed() || self.package.header.is_solid() {
+      return Ok(None);
+    }
+
+    let buffer = self
+      .streams
+      .get(file.part_index() as usize)
+      .ok_or(Error::InvalidFileTable)?
+      .get_ref()
+      .as_ref();
+    let start = file.offset() as usize;
+    let end = start + file.size_on_disk() as usize;
+    if end > buffer.len() {
+      return Err(Error::InvalidFileTable);
+    }
+    Ok(Some(&buffer[start..end]))
+  }
+}
+
+// Part files are opened through create_writer, which defaults to a plain function so the writer
+// type can be named
+pub struct PackageWriter<O, F = fn(&Path) -> Result<O, Error>> {
+  path: PathBuf,
+  streams: Vec<O>,
+  create_writer: F,
+  max_part_size: Option<u64>,
+}
+
+impl<O, F> PackageWriter<O, F> {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn max_part_size(&self) -> Option<u64> {
+    self.max_part_size
+  }
+
+  // Once set, files are split across part files (e.g. Textures_1.pak) after the given amount of bytes
+  pub fn set_max_part_size(&mut self, max_part_size: Option<u64>) {
+    self.max_part_size = max_part_size;
+  }
+
+  pub fn into_streams(self) -> Vec<O> {
+    self.streams
+  }
+}
+
+impl<O, F> Debug for PackageWriter<O, F> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!("{:?}", self.path))
+  }
+}
+
+impl<O: Write + Seek, F: Fn(&Path) -> Result<O, Error>> PackageWriter<O, F> {
+  pub fn create(path: &Path, create_writer: F) -> Result<PackageWriter<O, F>, Error> {
+    let dir = path
+      .parent()
+      .ok_or(Error::InvalidPath(path.to_string_lossy().into_owned()))?;
+    fs::create_dir_all(dir)?;
+    let stream = create_writer(path)?;
+    Ok(PackageWriter {
+      path: path.to_owned(),
+      streams: vec![stream],
+      create_writer,
+      max_part_size: None,
+    })
+  }
+
+  // Writes all files of the package, which need to have their contents loaded.
+  // Offsets, sizes and CRCs of the file table are updated to match the written archive.
+  pub fn write(&mut self, package: &mut Package) -> Result<(), Error> {
+    self.write_files(package, None)
+  }
+
+  // Like write, but files without contents are copied through copy_stored instead of being
+  // compressed, see PackageEdit::commit
+  pub fn write_copying_stored(
+    &mut self,
+    package: &mut Package,
+    copy_stored: CopyStored<'_>,
+  ) -> Result<(), Error> {
+    self.write_files(package, Some(copy_stored))
+  }
+
+  fn write_files(
+    &mut self,
+    package: &mut Package,
+    copy_stored: Option<CopyStored<'_>>,
+  ) -> Result<(), Error> {
+    let PackageWriter {
+      path,
+      streams,
+      create_writer,
+      max_part_size,
+    } = self;
+    streams.truncate(1);
+
+    let mut create_part = |part| create_writer(&part_path(path, part));
+    let split =
+      max_part_size.map(|max_part_size| (max_part_size, &mut create_part as CreatePart<'_, O>));
+    write_lsv_package_parts(streams, package, split, copy_stored)?;
+
+    for stream in streams.iter_mut() {
+      stream.flush()?;
     }
     Ok(())
   }
 }
 
+pub trait Unpack {
+  fn unpack(&mut self, target_dir: &Path) -> Result<(), Error> {
+    self.unpack_with_progress(target_dir, &|_| {})
+  }
+
+  fn unpack_with_progress(
+    &mut self,
+    target_dir: &Path,
+    progress: &(dyn Fn(&UnpackProgress) + Sync),
+  ) -> Result<(), Error> {
+    self.unpack_with_options(target_dir, &UnpackOptions::default(), progress)?;
+    Ok(())
+  }
+
+  // Unpacks the files selected by the options and returns them in path order
+  fn unpack_with_options(
+    &mut self,
+    target_dir: &Path,
+    options: &UnpackOptions,
+    progress: &(dyn Fn(&UnpackProgress) + Sync),
+  ) -> Result<Vec<UnpackEntry>, Error>;
+}
+
+impl<I: Read + Seek + Send> Unpack for PackageHandle<I> {
+  fn unpack_with_options(
+    &mut self,
+    target_dir: &Path,
+    options: &UnpackOptions,
+    progress: &(dyn Fn(&UnpackProgress) + Sync),
+  ) -> Result<Vec<UnpackEntry>, Error> {
+    let entries = options.select(&sorted_file_paths(&self.package))?;
+    if !options.dry_run() {
+      extract_lsv_files(
+        &mut self.streams,
+        &self.package,
+        &entries,
+        target_dir,
+        progress,
+      )?;
+    }
+    Ok(entries)
+  }
+}
+
 pub trait Transform {
   fn lsf_file(&mut self, path: &Path) -> Result<Resource, Error>;
+  fn lazy_lsf_file(&mut self, path: &Path) -> Result<LazyResource, Error>;
   fn lsb_file(&mut self, path: &Path) -> Result<Resource, Error>;
 }
 
@@ -228,9 +456,128 @@ impl<I: Read + Seek> Transform for PackageHandle<I> {
     Ok(cursor.read_lsf_resource()?)
   }
 
+  fn lazy_lsf_file(&mut self, path: &Path) -> Result<LazyResource, Error> {
+    let contents = self.file_contents(path)?;
+    let mut cursor = Cursor::new(contents);
+    cursor.read_lsf_lazy_resource()
+  }
+
   fn lsb_file(&mut self, path: &Path) -> Result<Resource, Error> {
     let contents = self.file_contents(path)?;
     let mut cursor = Cursor::new(contents);
     Ok(cursor.read_lsb_resource()?)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    compression::{CompressionOptions, Level, Method},
+    lsv::header::Version,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_part_path() {
+    let path = Path::new("Data/Textures.pak");
+    assert_eq!(part_path(path, 0), PathBuf::from("Data/Textures.pak"));
+    assert_eq!(part_path(path, 1), PathBuf::from("Data/Textures_1.pak"));
+    assert_eq!(part_path(path, 12), PathBuf::from("Data/Textures_12.pak"));
+    assert_eq!(
+      part_path(Path::new("Textures"), 2),
+      PathBuf::from("Textures_2")
+    );
+  }
+
+  #[test]
+  fn test_write_multi_part_package() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let path = dir.join("Textures.pak");
+
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    let flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+    for (index, name) in ["a.dds", "b.dds", "c.dds"].iter().enumerate() {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(name));
+      // Flip between compressed and uncompressed files
+      file.set_flags(if index % 2 == 0 { 0 } else { flags as u32 });
+      file.set_contents(Some(vec![index as u8; 100]));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+
+    let mut writer = Package::create_file(&path).unwrap();
+    writer.set_max_part_size(Some(64));
+    writer.write(&mut package).unwrap();
+    drop(writer);
+    assert_eq!(package.header().part_count(), 3);
+    assert!(part_path(&path, 1).exists());
+    assert!(part_path(&path, 2).exists());
+
+    let mut handle = Package::open_file(&path).unwrap();
+    assert_eq!(handle.header().part_count(), 3);
+    for (file_path, file) in package.files() {
+      assert_eq!(
+        handle.file(file_path).unwrap().part_index(),
+        file.part_index()
+      );
+      let contents = handle.file_contents(file_path).unwrap();
+      assert_eq!(contents, file.contents().as_ref().unwrap());
+    }
+  }
+
+  #[test]
+  fn test_open_mapped_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let path = dir.join("Shared.pak");
+
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    package
+      .header_mut()
+      .set_flags(Header::FLAGS_ALLOW_MEMORY_MAPPING);
+    for method in [Method::None, Method::Zlib, Method::Lz4] {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(format!("{:?}.txt", method)));
+      let flags: u8 = CompressionOptions::new(method, Level::Default).into();
+      file.set_flags(if method == Method::None {
+        0
+      } else {
+        flags as u32
+      });
+      file.set_contents(Some(
+        format!("{:?} {:?} {:?}", method, method, method).into_bytes(),
+      ));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    Package::create_file(&path)
+      .unwrap()
+      .write(&mut package)
+      .unwrap();
+
+    let mut handle = Package::open_mapped_file(&path).unwrap();
+    let plain = handle.file_slice(Path::new("None.txt")).unwrap().unwrap();
+    assert_eq!(plain, b"None None None");
+    assert_eq!(
+      handle.file(Path::new("None.txt")).unwrap().offset() % 0x40,
+      0
+    );
+    assert!(handle.file_slice(Path::new("Zlib.txt")).unwrap().is_none());
+
+    let target_dir = dir.join("unpacked");
+    handle.unpack(&target_dir).unwrap();
+    for (file_path, file) in package.files() {
+      // Unpacking doesn't cache the files
+      assert!(handle.file(file_path).unwrap().contents().is_none());
+      let contents = fs::read(target_dir.join(file_path)).unwrap();
+      assert_eq!(&contents, file.contents().as_ref().unwrap());
+    }
+  }
+}
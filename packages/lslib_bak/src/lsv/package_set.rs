@@ -0,0 +1,295 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt::{Debug, Formatter},
+  fs,
+  io::{Read, Seek},
+  path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+
+use super::package::PackageHandle;
+
+pub enum MountSource<I> {
+  Package(PackageHandle<I>),
+  // Loose files, relative to the mounted directory
+  Directory(Vec<PathBuf>),
+}
+
+pub struct Mount<I> {
+  path: PathBuf,
+  priority: u8,
+  source: MountSource<I>,
+  // Normalized paths of all files, mapped to the path as stored in the source
+  lookup: HashMap<String, PathBuf>,
+}
+
+impl<I> Mount<I> {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn priority(&self) -> u8 {
+    self.priority
+  }
+
+  pub fn source(&self) -> &MountSource<I> {
+    &self.source
+  }
+
+  pub fn file_paths(&self) -> Vec<&PathBuf> {
+    match &self.source {
+      MountSource::Package(handle) => handle.files().keys().collect(),
+      MountSource::Directory(file_paths) => file_paths.iter().collect(),
+    }
+  }
+
+  pub fn contains(&self, path: &Path) -> bool {
+    self.lookup.contains_key(&normalize_path(path))
+  }
+
+  // Path of a file as stored in the source, which may differ in case and separators
+  pub fn find(&self, path: &Path) -> Option<&PathBuf> {
+    self.lookup.get(&normalize_path(path))
+  }
+
+  fn new(path: PathBuf, priority: u8, source: MountSource<I>) -> Self {
+    let mut mount = Mount {
+      path,
+      priority,
+      source,
+      lookup: HashMap::new(),
+    };
+    mount.lookup = mount
+      .file_paths()
+      .into_iter()
+      .map(|file_path| (normalize_path(file_path), file_path.to_owned()))
+      .collect();
+    mount
+  }
+}
+
+// Paths are looked up like the game does, ignoring case and separator style
+pub fn normalize_path(path: &Path) -> String {
+  path.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+impl<I> Debug for Mount<I> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Mount")
+      .field("path", &self.path)
+      .field("priority", &self.priority)
+      .finish()
+  }
+}
+
+// A layered view over multiple packages and loose directories. Like in the game, mounts are
+// loaded by ascending priority (and mount order for equal priorities), so when multiple
+// mounts contain the same path, the one loaded last wins.
+#[derive(Debug)]
+pub struct PackageSet<I> {
+  mounts: Vec<Mount<I>>,
+}
+
+impl<I> Default for PackageSet<I> {
+  fn default() -> Self {
+    Self { mounts: vec![] }
+  }
+}
+
+impl<I> PackageSet<I> {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn mounts(&self) -> &Vec<Mount<I>> {
+    &self.mounts
+  }
+
+  pub fn mount(&self, index: usize) -> Option<&Mount<I>> {
+    self.mounts.get(index)
+  }
+
+  // Mounts a package with the priority stored in its header and returns the mount index
+  pub fn mount_package(&mut self, handle: PackageHandle<I>) -> usize {
+    self.mounts.push(Mount::new(
+      handle.path().to_owned(),
+      handle.header().priority(),
+      MountSource::Package(handle),
+    ));
+    self.mounts.len() - 1
+  }
+
+  // Mounts all files below a directory and returns the mount index
+  pub fn mount_directory(&mut self, path: &Path, priority: u8) -> Result<usize, Error> {
+    let mut file_paths = vec![];
+    collect_file_paths(path, Path::new(""), &mut file_paths)?;
+    file_paths.sort();
+    self.mounts.push(Mount::new(
+      path.to_owned(),
+      priority,
+      MountSource::Directory(file_paths),
+    ));
+    Ok(self.mounts.len() - 1)
+  }
+
+  // Mount indices in the order the game would load them
+  pub fn load_order(&self) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..self.mounts.len()).collect();
+    indices.sort_by_key(|index| self.mounts[*index].priority);
+    indices
+  }
+
+  // Index of the mount that supplies the given path
+  pub fn resolve(&self, path: &Path) -> Option<usize> {
+    let key = normalize_path(path);
+    self
+      .load_order()
+      .into_iter()
+      .rev()
+      .find(|index| self.mounts[*index].lookup.contains_key(&key))
+  }
+
+  // Every path with the indices of all mounts containing it, in load order.
+  // The last index supplies the file, all others are overridden. Paths that only differ in case or
+  // separators are listed under the one loaded first.
+  pub fn sources(&self) -> BTreeMap<PathBuf, Vec<usize>> {
+    let mut file_paths: HashMap<&String, &PathBuf> = HashMap::new();
+    let mut sources: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+    for index in self.load_order() {
+      for (key, file_path) in &self.mounts[index].lookup {
+        let file_path = *file_paths.entry(key).or_insert(file_path);
+        sources.entry(file_path.to_owned()).or_default().push(index);
+      }
+    }
+    sources
+  }
+
+  // The effective file tree, every path with the index of the mount supplying it
+  pub fn file_tree(&self) -> BTreeMap<PathBuf, usize> {
+    self
+      .sources()
+      .into_iter()
+      .map(|(file_path, indices)| (file_path, *indices.last().unwrap()))
+      .collect()
+  }
+}
+
+impl<I: Read + Seek> PackageSet<I> {
+  pub fn file_contents(&mut self, path: &Path) -> Result<Vec<u8>, Error> {
+    let index = self
+      .resolve(path)
+      .ok_or(Error::FileNotFound(path.to_string_lossy().into_owned()))?;
+    let mount = &mut self.mounts[index];
+    let file_path = mount.lookup[&normalize_path(path)].clone();
+    match &mut mount.source {
+      MountSource::Package(handle) => Ok(handle.file_contents(&file_path)?.clone()),
+      MountSource::Directory(_) => Ok(fs::read(mount.path.join(file_path))?),
+    }
+  }
+}
+
+pub(crate) fn collect_file_paths(
+  root: &Path,
+  relative_dir: &Path,
+  file_paths: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+  for entry in fs::read_dir(root.join(relative_dir))? {
+    let entry = entry?;
+    let relative_path = relative_dir.join(entry.file_name());
+    if entry.file_type()?.is_dir() {
+      collect_file_paths(root, &relative_path, file_paths)?;
+    } else {
+      file_paths.push(relative_path);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::lsv::{file::File, header::Version, package::Package};
+
+  use super::*;
+
+  fn write_package(path: &Path, priority: u8, files: &[(&str, &str)]) {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    package.header_mut().set_priority(priority);
+    for (file_path, contents) in files {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(file_path));
+      file.set_contents(Some(contents.as_bytes().to_vec()));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    Package::create_file(path)
+      .unwrap()
+      .write(&mut package)
+      .unwrap();
+  }
+
+  #[test]
+  fn test_package_set() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let patch_path = dir.join("Patch.pak");
+    let main_path = dir.join("Main.pak");
+    let loose_path = dir.join("Data");
+    write_package(
+      &patch_path,
+      10,
+      &[("Stats.txt", "patched"), ("Patch.txt", "patch")],
+    );
+    write_package(
+      &main_path,
+      0,
+      &[("Stats.txt", "main"), ("Main.txt", "main")],
+    );
+    fs::create_dir_all(loose_path.join("Mods")).unwrap();
+    fs::write(loose_path.join("Mods/Main.txt"), "loose").unwrap();
+    fs::write(loose_path.join("Patch.txt"), "loose").unwrap();
+
+    let mut package_set = PackageSet::new();
+    // Mount order doesn't matter, the priority does
+    let patch = package_set.mount_package(Package::open_file(&patch_path).unwrap());
+    let main = package_set.mount_package(Package::open_file(&main_path).unwrap());
+    let loose = package_set.mount_directory(&loose_path, 5).unwrap();
+    assert_eq!(package_set.load_order(), vec![main, loose, patch]);
+
+    assert_eq!(package_set.resolve(Path::new("Stats.txt")), Some(patch));
+    assert_eq!(package_set.resolve(Path::new("Main.txt")), Some(main));
+    assert_eq!(package_set.resolve(Path::new("Mods/Main.txt")), Some(loose));
+    assert_eq!(package_set.resolve(Path::new("Missing.txt")), None);
+    // Lookups ignore case and separator style
+    assert_eq!(package_set.resolve(Path::new("stats.TXT")), Some(patch));
+    assert_eq!(
+      package_set.resolve(Path::new("MODS\\main.txt")),
+      Some(loose)
+    );
+    assert!(package_set
+      .mount(main)
+      .unwrap()
+      .contains(Path::new("main.txt")));
+
+    let file_tree = package_set.file_tree();
+    assert_eq!(file_tree.len(), 4);
+    assert_eq!(file_tree[Path::new("Patch.txt")], patch);
+    assert_eq!(
+      package_set.sources()[Path::new("Stats.txt")],
+      vec![main, patch]
+    );
+
+    let contents = package_set.file_contents(Path::new("Stats.txt")).unwrap();
+    assert_eq!(contents, b"patched".to_vec());
+    let contents = package_set
+      .file_contents(Path::new("Mods/Main.txt"))
+      .unwrap();
+    assert_eq!(contents, b"loose".to_vec());
+    let contents = package_set
+      .file_contents(Path::new("mods\\main.TXT"))
+      .unwrap();
+    assert_eq!(contents, b"loose".to_vec());
+  }
+}
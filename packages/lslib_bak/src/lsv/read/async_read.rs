@@ -0,0 +1,260 @@
+use std::{
+  collections::BTreeMap,
+  fmt::{self, Display, Formatter},
+  future::Future,
+  io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom},
+};
+
+use tokio::{
+  io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt},
+  task::spawn_blocking,
+};
+
+use crate::{
+  compression::read::DecompressReadExt,
+  error::Error,
+  lsv::{file::File, header::Header, package::Package},
+};
+
+use super::{v13::file_compression_options, LsvReadExt};
+
+// Smallest amount of bytes fetched at once while reading the header and file table
+const MIN_FETCH_SIZE: u64 = 64 * 1024;
+
+// Raised by SparseReader for bytes that haven't been fetched yet
+#[derive(Debug)]
+struct MissingRange(u64);
+
+impl Display for MissingRange {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "Bytes at offset {} haven't been fetched", self.0)
+  }
+}
+
+impl std::error::Error for MissingRange {}
+
+// The chunks of a package fetched so far. It lets the blocking readers parse the header and file
+// table, every read outside of the chunks fails with MissingRange and is fetched before retrying.
+struct SparseReader {
+  len: u64,
+  position: u64,
+  chunks: BTreeMap<u64, Vec<u8>>,
+}
+
+impl SparseReader {
+  fn new(len: u64) -> Self {
+    Self {
+      len,
+      position: 0,
+      chunks: BTreeMap::new(),
+    }
+  }
+
+  fn fetched_size(&self) -> u64 {
+    self.chunks.values().map(|chunk| chunk.len() as u64).sum()
+  }
+}
+
+impl Read for SparseReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.position >= self.len || buf.is_empty() {
+      return Ok(0);
+    }
+
+    let (start, chunk) = match self.chunks.range(..=self.position).next_back() {
+      Some((&start, chunk)) if self.position < start + chunk.len() as u64 => (start, chunk),
+      _ => return Err(io::Error::other(MissingRange(self.position))),
+    };
+    let chunk_offset = (self.position - start) as usize;
+    let size = buf.len().min(chunk.len() - chunk_offset);
+    buf[..size].copy_from_slice(&chunk[chunk_offset..chunk_offset + size]);
+    self.position += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for SparseReader {
+  fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+    let position = match pos {
+      SeekFrom::Start(offset) => Some(offset),
+      SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+    };
+    self.position = position.ok_or(io::Error::new(
+      ErrorKind::InvalidInput,
+      "Seek before the start of the package",
+    ))?;
+    Ok(self.position)
+  }
+}
+
+fn missing_range(error: &Error) -> Option<u64> {
+  match error {
+    Error::Io(error) => error
+      .get_ref()
+      .and_then(|inner| inner.downcast_ref::<MissingRange>())
+      .map(|missing_range| missing_range.0),
+    _ => None,
+  }
+}
+
+pub trait LsvAsyncReadExt: AsyncRead + AsyncSeek + Unpin + Send {
+  // Reads the header and file table like LsvReadExt::read_lsv_package. The blocking parsers run
+  // on the bytes fetched so far, each missing range is fetched with growing chunk sizes.
+  // Every retry parses the header and file table again from the start. A parse never gets further
+  // than the bytes fetched so far and every chunk is at least as large as all previous ones, so
+  // the number of retries grows logarithmically with the file table size and all parses together
+  // read about twice the fetched bytes.
+  fn read_lsv_package_async<'a>(
+    &'a mut self,
+    package: &'a mut Package,
+  ) -> impl Future<Output = Result<(), Error>> + Send + 'a {
+    async move {
+      let len = self.seek(SeekFrom::End(0)).await?;
+      let mut reader = SparseReader::new(len);
+      loop {
+        let mut parsed_package = Package::default();
+        reader.seek(SeekFrom::Start(0))?;
+        let error = match reader.read_lsv_package(&mut parsed_package) {
+          Ok(()) => {
+            *package = parsed_package;
+            return Ok(());
+          }
+          Err(error) => error,
+        };
+
+        let offset = missing_range(&error).ok_or(error)?;
+        let size = MIN_FETCH_SIZE.max(reader.fetched_size()).min(len - offset);
+        let mut chunk = vec![0; size as usize];
+        self.seek(SeekFrom::Start(offset)).await?;
+        self.read_exact(&mut chunk).await?;
+        reader.chunks.insert(offset, chunk);
+      }
+    }
+  }
+
+  // Reads the uncompressed contents of a file of this part
+  fn read_lsv_file_contents_async<'a>(
+    &'a mut self,
+    header: &'a Header,
+    file: &'a File,
+  ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send + 'a {
+    async move {
+      let stored_bytes = read_lsv_stored_bytes_async(self, header, file).await?;
+      decompress_lsv_file_async(stored_bytes, header, file).await
+    }
+  }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send + ?Sized> LsvAsyncReadExt for R {}
+
+// Reads the bytes of a file as stored in its part
+pub(crate) async fn read_lsv_stored_bytes_async<R: AsyncRead + AsyncSeek + Unpin + ?Sized>(
+  reader: &mut R,
+  header: &Header,
+  file: &File,
+) -> Result<Vec<u8>, Error> {
+  // Solid archives are decompressed as a whole while reading the file table
+  if header.is_solid() {
+    return file
+      .contents()
+      .clone()
+      .ok_or(Error::FileEmpty(file.path().to_string_lossy().into_owned()));
+  }
+
+  let mut stored_bytes = vec![0; file.size_on_disk() as usize];
+  reader.seek(SeekFrom::Start(file.offset())).await?;
+  reader.read_exact(&mut stored_bytes).await?;
+  Ok(stored_bytes)
+}
+
+// Decompresses the stored bytes of a file, solid archives already hold the uncompressed contents.
+// Decompression runs on the blocking thread pool, so large files don't stall the runtime.
+pub(crate) async fn decompress_lsv_file_async(
+  stored_bytes: Vec<u8>,
+  header: &Header,
+  file: &File,
+) -> Result<Vec<u8>, Error> {
+  if !file.is_compressed() || header.is_solid() {
+    return Ok(stored_bytes);
+  }
+
+  let size_on_disk = file.size_on_disk() as usize;
+  let uncompressed_size = file.uncompressed_size() as usize;
+  let compression_options = file_compression_options(header, file);
+  spawn_blocking(move || {
+    Cursor::new(stored_bytes).read_decompressed(
+      size_on_disk,
+      uncompressed_size,
+      compression_options,
+    )
+  })
+  .await
+  .map_err(io::Error::from)?
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use crate::{
+    compression::{CompressionOptions, Level, Method},
+    lsv::{header::Version, write::LsvWriteExt},
+  };
+
+  use super::*;
+
+  fn package_bytes(version: Version, file_count: usize) -> Vec<u8> {
+    let mut package = Package::default();
+    package.header_mut().set_version(version);
+    let flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+    for index in 0..file_count {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(format!("Public/File{}.txt", index)));
+      file.set_flags(flags as u32);
+      file.set_contents(Some(
+        format!("File {} of {}", index, file_count).into_bytes(),
+      ));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+    let mut writer = Cursor::new(vec![]);
+    writer.write_lsv_package(&mut package).unwrap();
+    writer.into_inner()
+  }
+
+  #[tokio::test]
+  async fn test_read_lsv_package_async() {
+    // Large enough for the file table to need several fetches
+    for version in [Version::V13, Version::V16, Version::V18] {
+      let mut reader = Cursor::new(package_bytes(version, 2000));
+      let mut package = Package::default();
+      reader.read_lsv_package_async(&mut package).await.unwrap();
+      assert_eq!(package.header().version(), version);
+      assert_eq!(package.files().len(), 2000);
+
+      let file = package.file(Path::new("Public/File1234.txt")).unwrap();
+      let contents = reader
+        .read_lsv_file_contents_async(package.header(), file)
+        .await
+        .unwrap();
+      assert_eq!(contents, b"File 1234 of 2000");
+    }
+  }
+
+  #[test]
+  fn test_sparse_reader() {
+    let mut reader = SparseReader::new(8);
+    reader.chunks.insert(2, vec![2, 3, 4]);
+    reader.chunks.insert(5, vec![5]);
+    let mut buf = [0; 4];
+    reader.seek(SeekFrom::Start(2)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [2, 3, 4, 5]);
+    let error = Error::from(reader.read_exact(&mut buf).unwrap_err());
+    assert_eq!(missing_range(&error), Some(6));
+    reader.seek(SeekFrom::End(0)).unwrap();
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+  }
+}
@@ -1,6 +1,11 @@
+pub mod async_read;
+mod v10;
 mod v13;
+mod v15;
+mod v18;
+mod v7;
 
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
@@ -9,7 +14,15 @@ use crate::{
   lsv::header::{Header, Version},
 };
 
-use self::v13::{read_lsv_v13_file_contents, LsvV13ReadExt};
+use self::{
+  v10::LsvV10ReadExt,
+  v13::{
+    copy_lsv_v13_file_stored, read_lsv_v13_file_contents, read_lsv_v13_file_stream, LsvV13ReadExt,
+  },
+  v15::LsvV15ReadExt,
+  v18::LsvV18ReadExt,
+  v7::LsvV7ReadExt,
+};
 
 use super::{file::File, header::SIGNATURE, package::Package};
 
@@ -18,13 +31,27 @@ pub fn read_lsv_file_contents<R: Read + Seek>(
   header: &Header,
   file: &File,
 ) -> Result<Vec<u8>, Error> {
-  match header.version() {
-    Version::V13 => read_lsv_v13_file_contents(readers, header, file),
-    _ => todo!(
-      "implement package reader for version {:?}",
-      header.version()
-    ),
-  }
+  // v7 - v10 offsets are made absolute when reading the file table, so all versions are read like v13
+  read_lsv_v13_file_contents(readers, header, file)
+}
+
+pub fn read_lsv_file_stream<'a, R: Read + Seek>(
+  readers: &'a mut [R],
+  header: &Header,
+  file: &'a File,
+) -> Result<Box<dyn Read + 'a>, Error> {
+  read_lsv_v13_file_stream(readers, header, file)
+}
+
+// See copy_lsv_v13_file_stored
+pub fn copy_lsv_file_stored<R: Read + Seek>(
+  readers: &mut [R],
+  header: &Header,
+  file: &File,
+  writer: &mut dyn Write,
+  md5_context: &mut md5::Context,
+) -> Result<(), Error> {
+  copy_lsv_v13_file_stored(readers, header, file, writer, md5_context)
 }
 
 pub trait LsvReadExt: Read + Seek {
@@ -43,15 +70,21 @@ pub trait LsvReadExt: Read + Seek {
       return Ok(());
     }
 
-    // Check for v10, v15, v16
+    // Check for v10, v15, v16, v18
     self.seek(SeekFrom::Start(0))?;
     let signature: [u8; 4] = self.read_u32::<LittleEndian>()?.to_le_bytes();
     if SIGNATURE == signature {
       let version = self.read_i32::<LittleEndian>()?;
       match version {
-        10 => todo!("implement v10 package reader"),
-        15 => todo!("implement v15 package reader"),
-        16 => todo!("implement v16 package reader"),
+        10 => {
+          self.read_lsv_v10_header(header)?;
+          return Ok(());
+        }
+        // v18 shares the v16 header layout
+        15 | 16 | 18 => {
+          self.read_lsv_v15_header(header)?;
+          return Ok(());
+        }
         _ => return Err(Error::InvalidVersion(version)),
       }
     }
@@ -60,18 +93,18 @@ pub trait LsvReadExt: Read + Seek {
     self.seek(SeekFrom::Start(0))?;
     let version = self.read_i32::<LittleEndian>()?;
     match version {
-      7 | 9 => todo!("implement v16 package reader"),
-      _ => return Err(Error::InvalidVersion(version)),
+      7 | 9 => self.read_lsv_v7_header(header),
+      _ => Err(Error::InvalidVersion(version)),
     }
   }
 
   fn read_lsv_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
     match package.header().version() {
+      Version::V7 | Version::V9 => self.read_lsv_v7_file_table(package),
+      Version::V10 => self.read_lsv_v10_file_table(package),
       Version::V13 => self.read_lsv_v13_file_table(package),
-      _ => todo!(
-        "implement package reader for version {:?}",
-        package.header().version()
-      ),
+      Version::V15 | Version::V16 => self.read_lsv_v15_file_table(package),
+      Version::V18 => self.read_lsv_v18_file_table(package),
     }?;
     Ok(())
   }
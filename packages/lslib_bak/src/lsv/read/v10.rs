@@ -0,0 +1,182 @@
+use std::{
+  io::{Cursor, Read, Seek, SeekFrom},
+  path::PathBuf,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use lz4_flex::decompress;
+
+use crate::{
+  error::Error,
+  lsv::{file::File, header::Header, package::Package},
+  util::read::BinaryReadExt,
+};
+
+// Signature (4) + version (4) + data offset (4) + file table size (4) + part count (2) + flags (1) + priority (1)
+const HEADER_SIZE: u32 = 20;
+
+pub trait LsvV10ReadExt: Read + Seek {
+  fn read_lsv_v10_header(&mut self, header: &mut Header) -> Result<(), Error> {
+    // Skip the signature, it has already been checked
+    self.seek(SeekFrom::Start(4))?;
+
+    let version = self.read_u32::<LittleEndian>()?;
+    let data_offset = self.read_u32::<LittleEndian>()?;
+    let file_table_size = self.read_u32::<LittleEndian>()?;
+    let part_count = self.read_u16::<LittleEndian>()?;
+    let flags = self.read_u8()?;
+    let priority = self.read_u8()?;
+
+    header.set_version(version.into());
+    header.set_part_count(part_count);
+    // The file table directly follows the header
+    header.set_file_table_offset(HEADER_SIZE as u64);
+    header.set_file_table_size(file_table_size);
+    header.set_data_offset(data_offset);
+    header.set_flags(flags);
+    header.set_priority(priority);
+
+    Ok(())
+  }
+
+  fn read_lsv_v10_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
+    let data_offset = package.header().data_offset();
+    self.seek(SeekFrom::Start(package.header().file_table_offset()))?;
+    let file_count = self.read_i32::<LittleEndian>()?;
+    let compressed_bytes = self.read_bytes(package.header().file_table_size() as usize - 4)?;
+    let uncompressed_size = (256 + 6 * 4) * file_count as usize;
+    let uncompressed_bytes = decompress(&compressed_bytes, uncompressed_size)?;
+
+    let mut cursor = Cursor::new(&uncompressed_bytes);
+
+    package.files_mut().reserve(file_count as usize);
+
+    for _ in 0..file_count {
+      let path = cursor.read_utf8_string(256)?;
+      let offset = cursor.read_u32::<LittleEndian>()?;
+      let size_on_disk = cursor.read_u32::<LittleEndian>()?;
+      let uncompressed_size = cursor.read_u32::<LittleEndian>()?;
+      let part_index = cursor.read_u32::<LittleEndian>()?;
+      let flags = cursor.read_u32::<LittleEndian>()?;
+      let crc = cursor.read_u32::<LittleEndian>()?;
+
+      let mut file = File::new();
+      file.set_path(PathBuf::from(path));
+      // Offsets in the main archive are relative to the data section
+      if part_index == 0 {
+        file.set_offset((offset + data_offset) as u64);
+      } else {
+        file.set_offset(offset as u64);
+      }
+      file.set_size_on_disk(size_on_disk);
+      file.set_uncompressed_size(uncompressed_size);
+      file.set_part_index(part_index);
+      file.set_flags(flags);
+      file.set_crc(crc);
+      package.files_mut().insert(file.path().to_owned(), file);
+    }
+
+    Ok(())
+  }
+}
+
+impl<R: Read + Seek + ?Sized> LsvV10ReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Write, path::Path};
+
+  use byteorder::WriteBytesExt;
+  use crc32fast::hash;
+  use lz4_flex::compress;
+
+  use crate::{
+    lsv::{
+      header::{Version, SIGNATURE},
+      read::{read_lsv_file_contents, LsvReadExt},
+    },
+    util::write::BinaryWriteExt,
+  };
+
+  use super::*;
+
+  // Builds a single-part v10 package containing an uncompressed and an LZ4 compressed file
+  fn v10_fixture() -> Vec<u8> {
+    let plain = b"Hello, World!".to_vec();
+    let packed = b"Compressed contents, compressed contents, compressed contents".to_vec();
+    let packed_bytes = compress(&packed);
+    let data = [plain.clone(), packed_bytes.clone()].concat();
+
+    let mut entries = Cursor::new(vec![]);
+    for (path, offset, size_on_disk, uncompressed_size, flags, crc) in [
+      ("Globals/plain.txt", 0, plain.len(), 0, 0, 0),
+      (
+        "Globals/packed.txt",
+        plain.len(),
+        packed_bytes.len(),
+        packed.len(),
+        0x22,
+        hash(&packed_bytes),
+      ),
+    ] {
+      entries.write_utf8_string(path, 256).unwrap();
+      entries.write_u32::<LittleEndian>(offset as u32).unwrap();
+      entries.write_u32::<LittleEndian>(size_on_disk as u32).unwrap();
+      entries.write_u32::<LittleEndian>(uncompressed_size as u32).unwrap();
+      entries.write_u32::<LittleEndian>(0).unwrap();
+      entries.write_u32::<LittleEndian>(flags).unwrap();
+      entries.write_u32::<LittleEndian>(crc).unwrap();
+    }
+    let file_table = compress(entries.get_ref());
+    let data_offset = HEADER_SIZE + 4 + file_table.len() as u32;
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_all(&SIGNATURE).unwrap();
+    writer.write_u32::<LittleEndian>(10).unwrap();
+    writer.write_u32::<LittleEndian>(data_offset).unwrap();
+    writer.write_u32::<LittleEndian>(4 + file_table.len() as u32).unwrap();
+    writer.write_u16::<LittleEndian>(1).unwrap();
+    writer.write_u8(Header::FLAGS_PRELOAD).unwrap();
+    writer.write_u8(3).unwrap();
+    writer.write_i32::<LittleEndian>(2).unwrap();
+    writer.write_all(&file_table).unwrap();
+    writer.write_all(&data).unwrap();
+    writer.into_inner()
+  }
+
+  #[test]
+  fn test_read_lsv_v10_header() {
+    let mut reader = Cursor::new(v10_fixture());
+    let mut header = Header::new();
+    reader.read_lsv_header(&mut header).unwrap();
+    assert_eq!(header.version(), Version::V10);
+    assert_eq!(header.part_count(), 1);
+    assert_eq!(header.file_table_offset(), HEADER_SIZE as u64);
+    assert_eq!(header.flags(), Header::FLAGS_PRELOAD);
+    assert_eq!(header.priority(), 3);
+  }
+
+  #[test]
+  fn test_read_lsv_v10_package() {
+    let mut reader = Cursor::new(v10_fixture());
+    let mut package = Package::default();
+    reader.read_lsv_package(&mut package).unwrap();
+    assert_eq!(package.files().len(), 2);
+
+    let plain = package.file(Path::new("Globals/plain.txt")).unwrap();
+    assert!(!plain.is_compressed());
+    assert_eq!(plain.offset(), package.header().data_offset() as u64);
+
+    let mut readers = vec![reader];
+    let contents = read_lsv_file_contents(&mut readers, package.header(), plain).unwrap();
+    assert_eq!(contents, b"Hello, World!".to_vec());
+
+    let packed = package.file(Path::new("Globals/packed.txt")).unwrap();
+    assert!(packed.is_compressed());
+    let contents = read_lsv_file_contents(&mut readers, package.header(), packed).unwrap();
+    assert_eq!(
+      contents,
+      b"Compressed contents, compressed contents, compressed contents".to_vec()
+    );
+  }
+}
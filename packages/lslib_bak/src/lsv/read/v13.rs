@@ -1,5 +1,5 @@
 use std::{
-  io::{Cursor, Read, Seek, SeekFrom},
+  io::{self, Cursor, Read, Seek, SeekFrom, Write},
   path::PathBuf,
 };
 
@@ -7,12 +7,23 @@ use byteorder::{LittleEndian, ReadBytesExt};
 use lz4_flex::decompress;
 
 use crate::{
-  compression::{read::DecompressReadExt, CompressionOptions, Method},
+  compression::{
+    read::{decompress_reader, DecompressReadExt},
+    CompressionOptions, Method,
+  },
   error::Error,
   lsv::{file::File, header::Header, package::Package},
   util::read::BinaryReadExt,
 };
 
+pub(crate) fn file_compression_options(header: &Header, file: &File) -> CompressionOptions {
+  let mut compression_options: CompressionOptions = file.compression_method().into();
+  if header.version().has_crc() {
+    compression_options.set_compressed_crc(Some(file.crc()));
+  }
+  compression_options
+}
+
 pub fn read_lsv_v13_file_contents<R: Read + Seek>(
   readers: &mut Vec<R>,
   header: &Header,
@@ -21,13 +32,17 @@ pub fn read_lsv_v13_file_contents<R: Read + Seek>(
   let contents = {
     if file.size_on_disk() > 0x7fffffff {
       return Err(Error::FileTooLarge(
-        file.path().to_str().unwrap().to_owned(),
-        file.size_on_disk(),
+        file.path().to_string_lossy().into_owned(),
+        file.size_on_disk() as u64,
       ));
     }
 
-    if header.is_solid() && file.contents().is_none() {
-      return Err(Error::FileEmpty(file.path().to_str().unwrap().to_owned()));
+    // Solid archives are decompressed as a whole while reading the file table
+    if header.is_solid() {
+      return file
+        .contents()
+        .clone()
+        .ok_or(Error::FileEmpty(file.path().to_string_lossy().into_owned()));
     }
 
     let reader = readers
@@ -35,18 +50,16 @@ pub fn read_lsv_v13_file_contents<R: Read + Seek>(
       .ok_or(Error::InvalidFileTable)?;
 
     if file.is_compressed() {
-      reader.seek(SeekFrom::Start(file.offset() as u64))?;
+      reader.seek(SeekFrom::Start(file.offset()))?;
 
-      let mut compression_options: CompressionOptions = file.compression_method().into();
-      compression_options.set_compressed_crc(Some(file.crc()));
       let uncompressed_bytes = reader.read_decompressed(
         file.size_on_disk() as usize,
         file.uncompressed_size() as usize,
-        compression_options,
+        file_compression_options(header, file),
       )?;
       Ok::<Vec<u8>, Error>(uncompressed_bytes)
     } else {
-      reader.seek(SeekFrom::Start(file.offset() as u64))?;
+      reader.seek(SeekFrom::Start(file.offset()))?;
       let uncompressed_bytes = reader.read_bytes(file.size_on_disk() as usize)?;
       Ok(uncompressed_bytes)
     }?
@@ -55,6 +68,77 @@ pub fn read_lsv_v13_file_contents<R: Read + Seek>(
   Ok(contents)
 }
 
+// Streams the uncompressed contents of a file from its part, see decompress_reader
+pub fn read_lsv_v13_file_stream<'a, R: Read + Seek>(
+  readers: &'a mut [R],
+  header: &Header,
+  file: &'a File,
+) -> Result<Box<dyn Read + 'a>, Error> {
+  // Solid archives are decompressed as a whole while reading the file table
+  if header.is_solid() {
+    let contents = file
+      .contents()
+      .as_ref()
+      .ok_or(Error::FileEmpty(file.path().to_string_lossy().into_owned()))?;
+    return Ok(Box::new(contents.as_slice()));
+  }
+
+  let reader = readers
+    .get_mut(file.part_index() as usize)
+    .ok_or(Error::InvalidFileTable)?;
+  reader.seek(SeekFrom::Start(file.offset()))?;
+  let compressed_reader = reader.take(file.size_on_disk() as u64);
+  decompress_reader(
+    compressed_reader,
+    file.uncompressed_size() as usize,
+    file_compression_options(header, file),
+  )
+}
+
+// Copies the stored bytes of a file from its part to the writer. The bytes are decompressed on the
+// way to check their CRC and to feed the uncompressed contents into md5_context.
+pub fn copy_lsv_v13_file_stored<R: Read + Seek>(
+  readers: &mut [R],
+  header: &Header,
+  file: &File,
+  writer: &mut dyn Write,
+  md5_context: &mut md5::Context,
+) -> Result<(), Error> {
+  let reader = readers
+    .get_mut(file.part_index() as usize)
+    .ok_or(Error::InvalidFileTable)?;
+  reader.seek(SeekFrom::Start(file.offset()))?;
+  let mut stored = TeeReader {
+    reader: reader.take(file.size_on_disk() as u64),
+    writer,
+  };
+  io::copy(
+    &mut decompress_reader(
+      &mut stored,
+      file.uncompressed_size() as usize,
+      file_compression_options(header, file),
+    )?,
+    md5_context,
+  )?;
+  // Decoders may stop before the end of the stored bytes
+  io::copy(&mut stored, &mut io::sink())?;
+  Ok(())
+}
+
+// Writes everything read from the reader to the writer
+struct TeeReader<'a, R> {
+  reader: R,
+  writer: &'a mut dyn Write,
+}
+
+impl<'a, R: Read> Read for TeeReader<'a, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.reader.read(buf)?;
+    self.writer.write_all(&buf[..read])?;
+    Ok(read)
+  }
+}
+
 pub trait LsvV13ReadExt: Read + Seek {
   fn read_lsv_v13_header(&mut self, header: &mut Header) -> Result<(), Error> {
     self.seek(SeekFrom::End(-8))?;
@@ -67,7 +151,7 @@ pub trait LsvV13ReadExt: Read + Seek {
     let part_count = self.read_u16::<LittleEndian>()?;
     let flags = self.read_u8()?;
     let priority = self.read_u8()?;
-    let _md5_hash = {
+    let md5 = {
       let mut hash = [0; 16];
       self.read_exact(&mut hash)?;
       hash
@@ -75,17 +159,18 @@ pub trait LsvV13ReadExt: Read + Seek {
 
     header.set_version(version.into());
     header.set_part_count(part_count);
-    header.set_file_table_offset(file_table_offset);
+    header.set_file_table_offset(file_table_offset as u64);
     header.set_file_table_size(file_table_size);
     header.set_flags(flags);
     header.set_priority(priority);
+    header.set_md5(md5);
 
     Ok(())
   }
 
   fn read_lsv_v13_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
     let is_solid = package.header().is_solid();
-    self.seek(SeekFrom::Start(package.header().file_table_offset() as u64))?;
+    self.seek(SeekFrom::Start(package.header().file_table_offset()))?;
     let file_count = self.read_i32::<LittleEndian>()?;
     let compressed_bytes = self.read_bytes(package.header().file_table_size() as usize - 4)?;
     let uncompressed_size = (256 + 6 * 4) * file_count as usize;
@@ -106,7 +191,7 @@ pub trait LsvV13ReadExt: Read + Seek {
 
       let mut file = File::new();
       file.set_path(PathBuf::from(path));
-      file.set_offset(offset);
+      file.set_offset(offset as u64);
       file.set_size_on_disk(size_on_disk);
       file.set_uncompressed_size(uncompressed_size);
       file.set_part_index(part_index);
@@ -117,19 +202,29 @@ pub trait LsvV13ReadExt: Read + Seek {
 
     // If this is a solid archive, we directly decompress the entire file table
     if is_solid {
-      let mut total_size_on_disk = 0u32;
-      let mut first_offset = 0xffffffffu32;
-      let mut last_offset = 0u32;
-      for (_, file) in package.files() {
+      // Files are stored back to back in the frame, so they have to be read in the order of their offsets
+      let mut file_paths: Vec<PathBuf> = package.files().keys().cloned().collect();
+      file_paths.sort_by_key(|file_path| {
+        let file = package.file(file_path).unwrap();
+        (file.offset(), file.size_on_disk())
+      });
+
+      let mut total_size_on_disk = 0u64;
+      let mut total_uncompressed_size = 0u64;
+      let mut first_offset = u64::MAX;
+      let mut last_offset = 0u64;
+      for file in package.files().values() {
         if file.offset() < first_offset {
           first_offset = file.offset();
         }
-        if file.offset() > last_offset {
-          last_offset = file.offset();
+        if file.offset() + file.size_on_disk() as u64 > last_offset {
+          last_offset = file.offset() + file.size_on_disk() as u64;
         }
-        total_size_on_disk += file.size_on_disk();
+        total_size_on_disk += file.size_on_disk() as u64;
+        total_uncompressed_size += file.uncompressed_size() as u64;
       }
 
+      // The first file starts right after the 7 byte LZ4 frame header
       if first_offset != 7 || last_offset - first_offset != total_size_on_disk {
         return Err(Error::InvalidFileTable);
       }
@@ -140,19 +235,18 @@ pub trait LsvV13ReadExt: Read + Seek {
       compression_options.set_chunked(true);
       let uncompressed_frame = self.read_decompressed(
         last_offset as usize,
-        total_size_on_disk as usize,
+        total_uncompressed_size as usize,
         compression_options,
       )?;
 
       let mut solid_cursor = Cursor::new(uncompressed_frame);
-      solid_cursor.seek(SeekFrom::Start(first_offset as u64))?;
-
-      let file_names: Vec<PathBuf> = package.files().keys().cloned().collect();
-      for file_path in file_names {
+      let mut offset = first_offset;
+      for file_path in file_paths {
         let file = package.file_mut(&file_path).unwrap();
-        if file.offset() != solid_cursor.position() as u32 {
+        if file.offset() != offset {
           return Err(Error::InvalidFileTable);
         }
+        offset += file.size_on_disk() as u64;
 
         let contents = solid_cursor.read_bytes(file.uncompressed_size() as usize)?;
         file.set_contents(Some(contents));
@@ -164,3 +258,65 @@ pub trait LsvV13ReadExt: Read + Seek {
 }
 
 impl<R: Read + Seek + ?Sized> LsvV13ReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Write, path::Path};
+
+  use byteorder::WriteBytesExt;
+  use lz4_flex::compress;
+
+  use crate::{
+    lsv::{
+      header::{Version, SIGNATURE},
+      read::{read_lsv_file_contents, LsvReadExt},
+    },
+    util::write::BinaryWriteExt,
+  };
+
+  use super::*;
+
+  // Builds a single-part v13 package with a single uncompressed file
+  fn v13_fixture() -> Vec<u8> {
+    let contents = b"Hello, World!".to_vec();
+
+    let mut entries = Cursor::new(vec![]);
+    entries.write_utf8_string("meta.lsf", 256).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    entries.write_u32::<LittleEndian>(contents.len() as u32).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    let file_table = compress(entries.get_ref());
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_all(&contents).unwrap();
+    writer.write_i32::<LittleEndian>(1).unwrap();
+    writer.write_all(&file_table).unwrap();
+    writer.write_u32::<LittleEndian>(13).unwrap();
+    writer.write_u32::<LittleEndian>(contents.len() as u32).unwrap();
+    writer.write_u32::<LittleEndian>(4 + file_table.len() as u32).unwrap();
+    writer.write_u16::<LittleEndian>(1).unwrap();
+    writer.write_u8(Header::FLAGS_NONE).unwrap();
+    writer.write_u8(0).unwrap();
+    writer.write_all(&[0u8; 16]).unwrap();
+    writer.write_u32::<LittleEndian>(40).unwrap();
+    writer.write_all(&SIGNATURE).unwrap();
+    writer.into_inner()
+  }
+
+  #[test]
+  fn test_read_lsv_v13_package() {
+    let mut reader = Cursor::new(v13_fixture());
+    let mut package = Package::default();
+    reader.read_lsv_package(&mut package).unwrap();
+    assert_eq!(package.header().version(), Version::V13);
+    assert_eq!(package.files().len(), 1);
+
+    let file = package.file(Path::new("meta.lsf")).unwrap();
+    let mut readers = vec![reader];
+    let contents = read_lsv_file_contents(&mut readers, package.header(), file).unwrap();
+    assert_eq!(contents, b"Hello, World!".to_vec());
+  }
+}
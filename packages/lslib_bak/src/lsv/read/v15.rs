@@ -0,0 +1,175 @@
+use std::{
+  io::{Cursor, Read, Seek, SeekFrom},
+  path::PathBuf,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use lz4_flex::decompress;
+
+use crate::{
+  error::Error,
+  lsv::{
+    file::File,
+    header::{Header, Version},
+    package::Package,
+  },
+  util::read::BinaryReadExt,
+};
+
+// Name (256) + offset (8) + size on disk (8) + uncompressed size (8) + part index (4) + flags (4) + crc (4) + unknown (4)
+const FILE_ENTRY_SIZE: usize = 256 + 3 * 8 + 4 * 4;
+
+fn checked_size(path: &str, size: u64) -> Result<u32, Error> {
+  u32::try_from(size).map_err(|_| Error::FileTooLarge(path.to_owned(), size))
+}
+
+pub trait LsvV15ReadExt: Read + Seek {
+  fn read_lsv_v15_header(&mut self, header: &mut Header) -> Result<(), Error> {
+    // Skip the signature, it has already been checked
+    self.seek(SeekFrom::Start(4))?;
+
+    let version = self.read_u32::<LittleEndian>()?;
+    let file_table_offset = self.read_u64::<LittleEndian>()?;
+    let file_table_size = self.read_u32::<LittleEndian>()?;
+    let flags = self.read_u8()?;
+    let priority = self.read_u8()?;
+    let md5 = {
+      let mut hash = [0; 16];
+      self.read_exact(&mut hash)?;
+      hash
+    };
+    // v15 packages always consist of a single part
+    let part_count = if version >= Version::V16 as u32 {
+      self.read_u16::<LittleEndian>()?
+    } else {
+      1
+    };
+
+    header.set_version(version.into());
+    header.set_part_count(part_count);
+    header.set_file_table_offset(file_table_offset);
+    header.set_file_table_size(file_table_size);
+    header.set_flags(flags);
+    header.set_priority(priority);
+    header.set_md5(md5);
+
+    Ok(())
+  }
+
+  fn read_lsv_v15_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
+    self.seek(SeekFrom::Start(package.header().file_table_offset()))?;
+    let file_count = self.read_i32::<LittleEndian>()?;
+    // Unlike older versions, the compressed size is stored in front of the file table
+    let compressed_size = self.read_i32::<LittleEndian>()?;
+    let compressed_bytes = self.read_bytes(compressed_size as usize)?;
+    let uncompressed_size = FILE_ENTRY_SIZE * file_count as usize;
+    let uncompressed_bytes = decompress(&compressed_bytes, uncompressed_size)?;
+
+    let mut cursor = Cursor::new(&uncompressed_bytes);
+
+    package.files_mut().reserve(file_count as usize);
+
+    for _ in 0..file_count {
+      let path = cursor.read_utf8_string(256)?;
+      let offset = cursor.read_u64::<LittleEndian>()?;
+      let size_on_disk = cursor.read_u64::<LittleEndian>()?;
+      let uncompressed_size = cursor.read_u64::<LittleEndian>()?;
+      let part_index = cursor.read_u32::<LittleEndian>()?;
+      let flags = cursor.read_u32::<LittleEndian>()?;
+      let crc = cursor.read_u32::<LittleEndian>()?;
+      let _unknown = cursor.read_u32::<LittleEndian>()?;
+
+      let mut file = File::new();
+      file.set_size_on_disk(checked_size(&path, size_on_disk)?);
+      file.set_uncompressed_size(checked_size(&path, uncompressed_size)?);
+      file.set_path(PathBuf::from(path));
+      file.set_offset(offset);
+      file.set_part_index(part_index);
+      file.set_flags(flags);
+      file.set_crc(crc);
+      package.files_mut().insert(file.path().to_owned(), file);
+    }
+
+    Ok(())
+  }
+}
+
+impl<R: Read + Seek + ?Sized> LsvV15ReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Write, path::Path};
+
+  use byteorder::WriteBytesExt;
+  use crc32fast::hash;
+  use lz4_flex::compress;
+
+  use crate::{
+    lsv::{
+      header::SIGNATURE,
+      read::{read_lsv_file_contents, LsvReadExt},
+    },
+    util::write::BinaryWriteExt,
+  };
+
+  use super::*;
+
+  // Builds a single-part package with an LZ4 compressed file, stored behind the file table
+  fn v15_fixture(version: Version) -> Vec<u8> {
+    let contents = b"Mods/Gustav/meta.lsx, Mods/Gustav/meta.lsx, Mods/Gustav/meta.lsx".to_vec();
+    let compressed = compress(&contents);
+
+    let header_size: u64 = if version >= Version::V16 { 40 } else { 38 };
+    let file_offset = header_size;
+    let mut entries = Cursor::new(vec![]);
+    entries.write_utf8_string("Mods/Gustav/meta.lsx", 256).unwrap();
+    entries.write_u64::<LittleEndian>(file_offset).unwrap();
+    entries.write_u64::<LittleEndian>(compressed.len() as u64).unwrap();
+    entries.write_u64::<LittleEndian>(contents.len() as u64).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    entries.write_u32::<LittleEndian>(0x22).unwrap();
+    entries.write_u32::<LittleEndian>(hash(&compressed)).unwrap();
+    entries.write_u32::<LittleEndian>(0).unwrap();
+    let file_table = compress(entries.get_ref());
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_all(&SIGNATURE).unwrap();
+    writer.write_u32::<LittleEndian>(version as u32).unwrap();
+    writer
+      .write_u64::<LittleEndian>(file_offset + compressed.len() as u64)
+      .unwrap();
+    writer.write_u32::<LittleEndian>(8 + file_table.len() as u32).unwrap();
+    writer.write_u8(Header::FLAGS_NONE).unwrap();
+    writer.write_u8(30).unwrap();
+    writer.write_all(&[0u8; 16]).unwrap();
+    if version >= Version::V16 {
+      writer.write_u16::<LittleEndian>(1).unwrap();
+    }
+    writer.write_all(&compressed).unwrap();
+    writer.write_i32::<LittleEndian>(1).unwrap();
+    writer.write_i32::<LittleEndian>(file_table.len() as i32).unwrap();
+    writer.write_all(&file_table).unwrap();
+    writer.into_inner()
+  }
+
+  #[test]
+  fn test_read_lsv_v15_and_v16_packages() {
+    for version in [Version::V15, Version::V16] {
+      let mut reader = Cursor::new(v15_fixture(version));
+      let mut package = Package::default();
+      reader.read_lsv_package(&mut package).unwrap();
+      assert_eq!(package.header().version(), version);
+      assert_eq!(package.header().part_count(), 1);
+      assert_eq!(package.header().priority(), 30);
+      assert_eq!(package.files().len(), 1);
+
+      let file = package.file(Path::new("Mods/Gustav/meta.lsx")).unwrap();
+      let mut readers = vec![reader];
+      let contents = read_lsv_file_contents(&mut readers, package.header(), file).unwrap();
+      assert_eq!(
+        contents,
+        b"Mods/Gustav/meta.lsx, Mods/Gustav/meta.lsx, Mods/Gustav/meta.lsx".to_vec()
+      );
+    }
+  }
+}
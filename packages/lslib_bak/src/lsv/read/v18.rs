@@ -0,0 +1,126 @@
+use std::{
+  io::{Cursor, Read, Seek, SeekFrom},
+  path::PathBuf,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use lz4_flex::decompress;
+
+use crate::{
+  error::Error,
+  lsv::{file::File, package::Package},
+  util::read::BinaryReadExt,
+};
+
+// Name (256) + offset (4 + 2) + part index (1) + flags (1) + size on disk (4) + uncompressed size (4)
+const FILE_ENTRY_SIZE: usize = 256 + 4 + 2 + 1 + 1 + 4 + 4;
+
+pub trait LsvV18ReadExt: Read + Seek {
+  // The header layout is shared with v16, see LsvV15ReadExt::read_lsv_v15_header
+  fn read_lsv_v18_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
+    self.seek(SeekFrom::Start(package.header().file_table_offset()))?;
+    let file_count = self.read_i32::<LittleEndian>()?;
+    let compressed_size = self.read_i32::<LittleEndian>()?;
+    let compressed_bytes = self.read_bytes(compressed_size as usize)?;
+    let uncompressed_size = FILE_ENTRY_SIZE * file_count as usize;
+    let uncompressed_bytes = decompress(&compressed_bytes, uncompressed_size)?;
+
+    let mut cursor = Cursor::new(&uncompressed_bytes);
+
+    package.files_mut().reserve(file_count as usize);
+
+    for _ in 0..file_count {
+      let path = cursor.read_utf8_string(256)?;
+      // The 48-bit offset is split into a low 32-bit and a high 16-bit part
+      let offset_low = cursor.read_u32::<LittleEndian>()?;
+      let offset_high = cursor.read_u16::<LittleEndian>()?;
+      let part_index = cursor.read_u8()?;
+      let flags = cursor.read_u8()?;
+      let size_on_disk = cursor.read_u32::<LittleEndian>()?;
+      let uncompressed_size = cursor.read_u32::<LittleEndian>()?;
+
+      let mut file = File::new();
+      file.set_path(PathBuf::from(path));
+      file.set_offset(offset_low as u64 | (offset_high as u64) << 32);
+      file.set_size_on_disk(size_on_disk);
+      file.set_uncompressed_size(uncompressed_size);
+      file.set_part_index(part_index as u32);
+      file.set_flags(flags as u32);
+      // v18 file entries don't carry a CRC anymore
+      file.set_crc(0);
+      package.files_mut().insert(file.path().to_owned(), file);
+    }
+
+    Ok(())
+  }
+}
+
+impl<R: Read + Seek + ?Sized> LsvV18ReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Write, path::Path};
+
+  use byteorder::WriteBytesExt;
+  use lz4_flex::compress;
+
+  use crate::{
+    compression::Method,
+    lsv::{
+      header::{Header, Version, SIGNATURE},
+      read::{read_lsv_file_contents, LsvReadExt},
+    },
+    util::write::BinaryWriteExt,
+  };
+
+  use super::*;
+
+  // Builds a single-part v18 package with a Zstd compressed file
+  fn v18_fixture(contents: &[u8]) -> Vec<u8> {
+    let compressed = zstd::encode_all(contents, 0).unwrap();
+
+    let file_offset = 40u64;
+    let mut entries = Cursor::new(vec![]);
+    entries.write_utf8_string("Public/Shared/Stats.txt", 256).unwrap();
+    entries.write_u32::<LittleEndian>(file_offset as u32).unwrap();
+    entries.write_u16::<LittleEndian>(0).unwrap();
+    entries.write_u8(0).unwrap();
+    entries.write_u8(Method::Zstd as u8).unwrap();
+    entries.write_u32::<LittleEndian>(compressed.len() as u32).unwrap();
+    entries.write_u32::<LittleEndian>(contents.len() as u32).unwrap();
+    let file_table = compress(entries.get_ref());
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_all(&SIGNATURE).unwrap();
+    writer.write_u32::<LittleEndian>(18).unwrap();
+    writer
+      .write_u64::<LittleEndian>(file_offset + compressed.len() as u64)
+      .unwrap();
+    writer.write_u32::<LittleEndian>(8 + file_table.len() as u32).unwrap();
+    writer.write_u8(Header::FLAGS_NONE).unwrap();
+    writer.write_u8(0).unwrap();
+    writer.write_all(&[0u8; 16]).unwrap();
+    writer.write_u16::<LittleEndian>(1).unwrap();
+    writer.write_all(&compressed).unwrap();
+    writer.write_i32::<LittleEndian>(1).unwrap();
+    writer.write_i32::<LittleEndian>(file_table.len() as i32).unwrap();
+    writer.write_all(&file_table).unwrap();
+    writer.into_inner()
+  }
+
+  #[test]
+  fn test_read_lsv_v18_package() {
+    let contents = b"new entry \"WPN_Longsword\", new entry \"WPN_Shortsword\"".to_vec();
+    let mut reader = Cursor::new(v18_fixture(&contents));
+    let mut package = Package::default();
+    reader.read_lsv_package(&mut package).unwrap();
+    assert_eq!(package.header().version(), Version::V18);
+    assert_eq!(package.files().len(), 1);
+
+    let file = package.file(Path::new("Public/Shared/Stats.txt")).unwrap();
+    assert_eq!(file.compression_method(), Method::Zstd);
+    let mut readers = vec![reader];
+    let read_contents = read_lsv_file_contents(&mut readers, package.header(), file).unwrap();
+    assert_eq!(read_contents, contents);
+  }
+}
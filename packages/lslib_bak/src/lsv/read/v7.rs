@@ -0,0 +1,161 @@
+use std::{
+  io::{Read, Seek, SeekFrom},
+  path::PathBuf,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{
+  compression::{CompressionOptions, Level, Method},
+  error::Error,
+  lsv::{file::File, header::Header, package::Package},
+  util::read::BinaryReadExt,
+};
+
+// Version (4) + data offset (4) + part count (4) + file table size (4) + endianness (1)
+const HEADER_SIZE: u32 = 17;
+
+pub trait LsvV7ReadExt: Read + Seek {
+  fn read_lsv_v7_header(&mut self, header: &mut Header) -> Result<(), Error> {
+    // v7 and v9 packages have no signature, the header starts right away
+    self.seek(SeekFrom::Start(0))?;
+
+    let version = self.read_u32::<LittleEndian>()?;
+    let data_offset = self.read_u32::<LittleEndian>()?;
+    let part_count = self.read_u32::<LittleEndian>()?;
+    let file_table_size = self.read_u32::<LittleEndian>()?;
+    let _little_endian = self.read_u8()?;
+
+    header.set_version(version.into());
+    header.set_part_count(part_count as u16);
+    // The file table (starting with the file count) directly follows the header
+    header.set_file_table_offset(HEADER_SIZE as u64);
+    header.set_file_table_size(file_table_size);
+    header.set_data_offset(data_offset);
+    // Flags and priority were introduced with v10
+    header.set_flags(Header::FLAGS_NONE);
+    header.set_priority(0);
+
+    Ok(())
+  }
+
+  fn read_lsv_v7_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
+    let data_offset = package.header().data_offset();
+    self.seek(SeekFrom::Start(package.header().file_table_offset()))?;
+    let file_count = self.read_u32::<LittleEndian>()?;
+
+    package.files_mut().reserve(file_count as usize);
+
+    // The file table isn't compressed in v7 and v9 packages
+    for _ in 0..file_count {
+      let path = self.read_utf8_string(256)?;
+      let offset = self.read_u32::<LittleEndian>()?;
+      let size_on_disk = self.read_u32::<LittleEndian>()?;
+      let uncompressed_size = self.read_u32::<LittleEndian>()?;
+      let part_index = self.read_u32::<LittleEndian>()?;
+
+      let mut file = File::new();
+      file.set_path(PathBuf::from(path));
+      // Offsets in the main archive are relative to the data section
+      if part_index == 0 {
+        file.set_offset((offset + data_offset) as u64);
+      } else {
+        file.set_offset(offset as u64);
+      }
+      file.set_size_on_disk(size_on_disk);
+      file.set_uncompressed_size(uncompressed_size);
+      file.set_part_index(part_index);
+      // Entries carry no flags, compressed files are always zlib compressed
+      if uncompressed_size > 0 {
+        let flags: u8 = CompressionOptions::new(Method::Zlib, Level::Default).into();
+        file.set_flags(flags as u32);
+      }
+      file.set_crc(0);
+      package.files_mut().insert(file.path().to_owned(), file);
+    }
+
+    Ok(())
+  }
+}
+
+impl<R: Read + Seek + ?Sized> LsvV7ReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::{Cursor, Write},
+    path::Path,
+  };
+
+  use byteorder::WriteBytesExt;
+  use flate2::{write::ZlibEncoder, Compression};
+
+  use crate::{
+    lsv::{
+      header::Version,
+      read::{read_lsv_file_contents, LsvReadExt},
+    },
+    util::write::BinaryWriteExt,
+  };
+
+  use super::*;
+
+  // Builds a single-part v9 package with an uncompressed and a zlib compressed file
+  fn v9_fixture() -> Vec<u8> {
+    let plain = b"<save/>".to_vec();
+    let packed = b"globals globals globals globals globals globals".to_vec();
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(&packed).unwrap();
+    let packed_bytes = encoder.finish().unwrap();
+
+    let mut entries = Cursor::new(vec![]);
+    for (path, offset, size_on_disk, uncompressed_size) in [
+      ("meta.lsf", 0, plain.len(), 0),
+      ("globals.lsf", plain.len(), packed_bytes.len(), packed.len()),
+    ] {
+      entries.write_utf8_string(path, 256).unwrap();
+      entries.write_u32::<LittleEndian>(offset as u32).unwrap();
+      entries.write_u32::<LittleEndian>(size_on_disk as u32).unwrap();
+      entries.write_u32::<LittleEndian>(uncompressed_size as u32).unwrap();
+      entries.write_u32::<LittleEndian>(0).unwrap();
+    }
+    let entries = entries.into_inner();
+    let data_offset = HEADER_SIZE + 4 + entries.len() as u32;
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_u32::<LittleEndian>(9).unwrap();
+    writer.write_u32::<LittleEndian>(data_offset).unwrap();
+    writer.write_u32::<LittleEndian>(1).unwrap();
+    writer.write_u32::<LittleEndian>(entries.len() as u32).unwrap();
+    writer.write_u8(1).unwrap();
+    writer.write_u32::<LittleEndian>(2).unwrap();
+    writer.write_all(&entries).unwrap();
+    writer.write_all(&plain).unwrap();
+    writer.write_all(&packed_bytes).unwrap();
+    writer.into_inner()
+  }
+
+  #[test]
+  fn test_read_lsv_v9_package() {
+    let mut reader = Cursor::new(v9_fixture());
+    let mut package = Package::default();
+    reader.read_lsv_package(&mut package).unwrap();
+    assert_eq!(package.header().version(), Version::V9);
+    assert_eq!(package.header().part_count(), 1);
+    assert_eq!(package.files().len(), 2);
+
+    let plain = package.file(Path::new("meta.lsf")).unwrap();
+    assert!(!plain.is_compressed());
+    let mut readers = vec![reader];
+    let contents = read_lsv_file_contents(&mut readers, package.header(), plain).unwrap();
+    assert_eq!(contents, b"<save/>".to_vec());
+
+    let packed = package.file(Path::new("globals.lsf")).unwrap();
+    assert_eq!(packed.compression_method(), Method::Zlib);
+    let contents = read_lsv_file_contents(&mut readers, package.header(), packed).unwrap();
+    assert_eq!(
+      contents,
+      b"globals globals globals globals globals globals".to_vec()
+    );
+  }
+}
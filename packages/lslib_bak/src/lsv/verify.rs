@@ -0,0 +1,326 @@
+use std::{
+  collections::HashMap,
+  io::{Cursor, Read, Seek, SeekFrom},
+  path::{Path, PathBuf},
+};
+
+use crc32fast::hash;
+
+use crate::{
+  compression::{read::DecompressReadExt, CompressionOptions},
+  error::Error,
+  util::read::BinaryReadExt,
+};
+
+use super::{
+  file::File,
+  package::Package,
+  write::{finalize_lsv_archive_hash, sorted_file_paths},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+  // The file points to a part that doesn't exist
+  InvalidPart(u32),
+  // The stored bytes reach beyond the end of their part
+  OutOfBounds {
+    offset: u64,
+    size: u64,
+    part_size: u64,
+  },
+  // The stored bytes overlap with the ones of another file
+  Overlap(PathBuf),
+  CrcMismatch {
+    expected: u32,
+    actual: u32,
+  },
+  // The stored bytes couldn't be decompressed
+  Unreadable(String),
+}
+
+impl Issue {
+  pub fn message(&self) -> String {
+    match self {
+      Self::InvalidPart(part) => format!("Invalid part index: {}", part),
+      Self::OutOfBounds {
+        offset,
+        size,
+        part_size,
+      } => format!(
+        "Out of bounds: {} bytes at offset {} (part size: {})",
+        size, offset, part_size
+      ),
+      Self::Overlap(path) => format!("Overlaps with {}", path.to_string_lossy()),
+      Self::CrcMismatch { expected, actual } => format!(
+        "CRC mismatch (expected: {:08x}, actual: {:08x})",
+        expected, actual
+      ),
+      Self::Unreadable(message) => format!("Unreadable: {}", message),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Md5Status {
+  Match,
+  Mismatch {
+    expected: [u8; 16],
+    actual: [u8; 16],
+  },
+  // The package doesn't carry a hash we are able to recompute
+  Unchecked,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileIssue {
+  path: PathBuf,
+  issue: Issue,
+}
+
+impl FileIssue {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn issue(&self) -> &Issue {
+    &self.issue
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport {
+  file_count: usize,
+  md5: Md5Status,
+  issues: Vec<FileIssue>,
+}
+
+impl VerifyReport {
+  pub fn file_count(&self) -> usize {
+    self.file_count
+  }
+
+  pub fn md5(&self) -> &Md5Status {
+    &self.md5
+  }
+
+  pub fn issues(&self) -> &Vec<FileIssue> {
+    &self.issues
+  }
+
+  pub fn is_ok(&self) -> bool {
+    self.issues.is_empty() && !matches!(self.md5, Md5Status::Mismatch { .. })
+  }
+}
+
+// Checks the bounds, CRC and contents of every file and the archive hash of the package.
+// Problems with single files are collected into the report, only IO errors are returned directly.
+pub fn verify_lsv_package<R: Read + Seek>(
+  readers: &mut [R],
+  package: &Package,
+) -> Result<VerifyReport, Error> {
+  let mut part_sizes = Vec::with_capacity(readers.len());
+  for reader in readers.iter_mut() {
+    part_sizes.push(reader.seek(SeekFrom::End(0))?);
+  }
+
+  let mut issues = vec![];
+  let file_paths = sorted_file_paths(package);
+
+  // Files of the same part must not share any bytes
+  let mut files_by_part: HashMap<u32, Vec<&File>> = HashMap::new();
+  for file_path in &file_paths {
+    let file = package.file(file_path).unwrap();
+    if file.size_on_disk() > 0 {
+      files_by_part
+        .entry(file.part_index())
+        .or_default()
+        .push(file);
+    }
+  }
+  for files in files_by_part.values_mut() {
+    files.sort_by_key(|file| file.offset());
+    for pair in files.windows(2) {
+      if pair[1].offset() < pair[0].offset() + pair[0].size_on_disk() as u64 {
+        issues.push(FileIssue {
+          path: pair[1].path().to_owned(),
+          issue: Issue::Overlap(pair[0].path().to_owned()),
+        });
+      }
+    }
+  }
+
+  let mut md5_context = md5::Context::new();
+  let mut hashed_all_files = true;
+  for file_path in &file_paths {
+    let file = package.file(file_path).unwrap();
+    match verify_lsv_file(readers, &part_sizes, package, file)? {
+      Ok(contents) => md5_context.consume(&contents),
+      Err(issue) => {
+        hashed_all_files = false;
+        issues.push(FileIssue {
+          path: file_path.to_owned(),
+          issue,
+        });
+      }
+    }
+  }
+
+  // The archive hash covers the files in path order, packages may also leave it at zero
+  let expected = package.header().md5();
+  let md5 = if !package.header().version().has_md5() || expected == [0; 16] || !hashed_all_files {
+    Md5Status::Unchecked
+  } else {
+    let actual = finalize_lsv_archive_hash(md5_context);
+    if actual == expected {
+      Md5Status::Match
+    } else {
+      Md5Status::Mismatch { expected, actual }
+    }
+  };
+
+  Ok(VerifyReport {
+    file_count: file_paths.len(),
+    md5,
+    issues,
+  })
+}
+
+// Returns the uncompressed contents of an intact file or the issue found with it
+fn verify_lsv_file<R: Read + Seek>(
+  readers: &mut [R],
+  part_sizes: &[u64],
+  package: &Package,
+  file: &File,
+) -> Result<Result<Vec<u8>, Issue>, Error> {
+  let reader = match readers.get_mut(file.part_index() as usize) {
+    Some(reader) => reader,
+    None => return Ok(Err(Issue::InvalidPart(file.part_index()))),
+  };
+
+  let part_size = part_sizes[file.part_index() as usize];
+  let size = file.size_on_disk() as u64;
+  if file.offset() + size > part_size {
+    return Ok(Err(Issue::OutOfBounds {
+      offset: file.offset(),
+      size,
+      part_size,
+    }));
+  }
+
+  reader.seek(SeekFrom::Start(file.offset()))?;
+  let bytes = reader.read_bytes(file.size_on_disk() as usize)?;
+
+  if package.header().version().has_crc() {
+    let actual = hash(&bytes);
+    if actual != file.crc() {
+      return Ok(Err(Issue::CrcMismatch {
+        expected: file.crc(),
+        actual,
+      }));
+    }
+  }
+
+  // Solid archives are decompressed as a whole while reading the file table
+  if package.header().is_solid() {
+    return Ok(
+      file
+        .contents()
+        .clone()
+        .ok_or(Issue::Unreadable("Contents not loaded".to_string())),
+    );
+  }
+
+  if !file.is_compressed() {
+    return Ok(Ok(bytes));
+  }
+
+  let compression_options: CompressionOptions = file.compression_method().into();
+  let contents = Cursor::new(&bytes).read_decompressed(
+    bytes.len(),
+    file.uncompressed_size() as usize,
+    compression_options,
+  );
+  Ok(contents.map_err(|error| Issue::Unreadable(error.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    compression::{Level, Method},
+    lsv::{header::Version, write::LsvWriteExt},
+  };
+
+  use super::*;
+
+  fn fixture(version: Version) -> (Package, Vec<u8>) {
+    let mut package = Package::default();
+    package.header_mut().set_version(version);
+    let flags: u8 = CompressionOptions::new(Method::Lz4, Level::Default).into();
+    for (path, contents) in [
+      ("meta.lsf", "meta meta meta"),
+      ("globals.lsf", "globals globals"),
+    ] {
+      let mut file = File::new();
+      file.set_path(PathBuf::from(path));
+      file.set_flags(flags as u32);
+      file.set_contents(Some(contents.as_bytes().to_vec()));
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_lsv_package(&mut package).unwrap();
+    (package, writer.into_inner())
+  }
+
+  #[test]
+  fn test_verify_lsv_package() {
+    for version in [Version::V13, Version::V15, Version::V16, Version::V18] {
+      let (package, bytes) = fixture(version);
+      let report = verify_lsv_package(&mut [Cursor::new(bytes)], &package).unwrap();
+      assert!(report.is_ok());
+      assert_eq!(report.file_count(), 2);
+      assert_eq!(report.md5(), &Md5Status::Match);
+    }
+  }
+
+  #[test]
+  fn test_verify_lsv_package_md5_mismatch() {
+    let (mut package, bytes) = fixture(Version::V18);
+    package.header_mut().set_md5([1; 16]);
+    let report = verify_lsv_package(&mut [Cursor::new(bytes)], &package).unwrap();
+    assert!(!report.is_ok());
+    assert!(report.issues().is_empty());
+    assert!(matches!(report.md5(), Md5Status::Mismatch { .. }));
+  }
+
+  #[test]
+  fn test_verify_lsv_package_zero_crc() {
+    let (mut package, bytes) = fixture(Version::V13);
+    package.file_mut(Path::new("meta.lsf")).unwrap().set_crc(0);
+    let report = verify_lsv_package(&mut [Cursor::new(bytes)], &package).unwrap();
+    assert_eq!(report.issues().len(), 1);
+    assert!(matches!(
+      report.issues()[0].issue(),
+      Issue::CrcMismatch { expected: 0, .. }
+    ));
+  }
+
+  #[test]
+  fn test_verify_lsv_package_corrupted() {
+    let (package, mut bytes) = fixture(Version::V13);
+    let corrupted = package.file(Path::new("meta.lsf")).unwrap();
+    bytes[corrupted.offset() as usize] ^= 0xff;
+
+    let report = verify_lsv_package(&mut [Cursor::new(bytes)], &package).unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.md5(), &Md5Status::Unchecked);
+    assert_eq!(report.issues().len(), 1);
+    assert_eq!(report.issues()[0].path(), Path::new("meta.lsf"));
+    assert!(matches!(
+      report.issues()[0].issue(),
+      Issue::CrcMismatch { .. }
+    ));
+  }
+}
@@ -0,0 +1,98 @@
+mod v13;
+mod v15;
+
+use std::{
+  io::{Seek, SeekFrom, Write},
+  path::PathBuf,
+};
+
+use crate::{error::Error, lsv::header::Version};
+
+use self::{
+  v13::{write_lsv_v13_part_files, LsvV13WriteExt},
+  v15::{write_lsv_v15_part_files, LsvV15WriteExt},
+};
+
+use super::{file::File, package::Package};
+
+// Files are written in path order, so the resulting archive doesn't depend on the file table's hashing.
+// Paths are compared ordinally (not by component) like LSLib does.
+pub fn sorted_file_paths(package: &Package) -> Vec<PathBuf> {
+  let mut file_paths: Vec<PathBuf> = package.files().keys().cloned().collect();
+  file_paths.sort_by(|a, b| a.as_os_str().cmp(b.as_os_str()));
+  file_paths
+}
+
+// Offsets and sizes stored as 32 bit values can't exceed 4 GiB
+pub fn checked_u32(path: &str, value: u64) -> Result<u32, Error> {
+  u32::try_from(value).map_err(|_| Error::FileTooLarge(path.to_owned(), value))
+}
+
+// The archive hash is the MD5 over the uncompressed contents of all files in path order,
+// with every byte of the digest incremented by one.
+pub fn lsv_archive_hash<'a, I: IntoIterator<Item = &'a Vec<u8>>>(contents: I) -> [u8; 16] {
+  let mut context = md5::Context::new();
+  for file_contents in contents {
+    context.consume(file_contents);
+  }
+  finalize_lsv_archive_hash(context)
+}
+
+// Finishes an archive hash whose contents have been consumed incrementally
+pub(crate) fn finalize_lsv_archive_hash(context: md5::Context) -> [u8; 16] {
+  let mut hash = context.finalize().0;
+  for byte in hash.iter_mut() {
+    *byte = byte.wrapping_add(1);
+  }
+  hash
+}
+
+pub trait LsvWriteExt: Write + Seek {
+  fn write_lsv_package(&mut self, package: &mut Package) -> Result<(), Error> {
+    match package.header().version() {
+      Version::V13 => self.write_lsv_v13_package(package),
+      Version::V15 | Version::V16 | Version::V18 => self.write_lsv_v15_package(package),
+      version => Err(Error::InvalidVersion(version as i32)),
+    }
+  }
+}
+
+impl<W: Write + Seek + ?Sized> LsvWriteExt for W {}
+
+// Creates the part file with the given index once the current part of a split package is full
+pub type CreatePart<'a, W> = &'a mut dyn FnMut(u16) -> Result<W, Error>;
+
+// Copies the stored bytes of a file without contents, e.g. from the package it was read from, and
+// feeds its uncompressed contents into the archive hash
+pub type CopyStored<'a> =
+  &'a mut dyn FnMut(&File, &mut dyn Write, &mut md5::Context) -> Result<(), Error>;
+
+// Writes a package into its part streams. The first stream is the main archive, which also
+// receives the file table and header. Further parts are only created if split is given, see
+// write_lsv_v13_part_files.
+pub fn write_lsv_package_parts<W: Write + Seek>(
+  streams: &mut Vec<W>,
+  package: &mut Package,
+  mut split: Option<(u64, CreatePart<'_, W>)>,
+  copy_stored: Option<CopyStored<'_>>,
+) -> Result<(), Error> {
+  if streams.is_empty() {
+    match &mut split {
+      Some((_, create_part)) => streams.push(create_part(0)?),
+      None => return Err(Error::Unsupported("Packages without streams".to_string())),
+    }
+  }
+  match package.header().version() {
+    Version::V13 => {
+      write_lsv_v13_part_files(streams, package, split, copy_stored)?;
+      let main_stream = &mut streams[0];
+      main_stream.seek(SeekFrom::End(0))?;
+      main_stream.write_lsv_v13_file_table(package)?;
+      main_stream.write_lsv_v13_header(package)
+    }
+    Version::V15 | Version::V16 | Version::V18 => {
+      write_lsv_v15_part_files(streams, package, split, copy_stored)
+    }
+    version => Err(Error::InvalidVersion(version as i32)),
+  }
+}
@@ -0,0 +1,388 @@
+use std::io::{Cursor, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use crc32fast::hash;
+use lz4_flex::{compress as compress_block, frame::FrameEncoder};
+
+use crate::{
+  compression::{write::compress, CompressionOptions, Method},
+  error::Error,
+  lsv::{
+    header::{Header, SIGNATURE},
+    package::Package,
+  },
+  util::write::BinaryWriteExt,
+};
+
+use super::{checked_u32, finalize_lsv_archive_hash, sorted_file_paths, CopyStored, CreatePart};
+
+// Version (4) + file table offset (4) + file table size (4) + part count (2) + flags (1) + priority (1) + md5 (16)
+const HEADER_SIZE: u32 = 32;
+
+// Files of packages that allow memory mapping start at aligned offsets
+const MEMORY_MAPPING_ALIGNMENT: u64 = 0x40;
+
+// Size of the LZ4 frame header (magic + descriptor) without content size or dictionary
+const LZ4_FRAME_HEADER_SIZE: u64 = 7;
+
+pub trait LsvV13WriteExt: Write + Seek {
+  fn write_lsv_v13_package(&mut self, package: &mut Package) -> Result<(), Error> {
+    self.write_lsv_v13_files(package)?;
+    self.write_lsv_v13_file_table(package)?;
+    self.write_lsv_v13_header(package)?;
+    Ok(())
+  }
+
+  fn write_lsv_v13_files(&mut self, package: &mut Package) -> Result<(), Error> {
+    write_lsv_v13_part_files(&mut vec![self], package, None, None)
+  }
+
+  // Solid archives store all files in a single LZ4 frame starting at the beginning of the file,
+  // every file is flushed into its own blocks so the offsets point into the compressed frame
+  fn write_lsv_v13_solid_files(&mut self, package: &mut Package) -> Result<(), Error> {
+    let file_paths = sorted_file_paths(package);
+    let mut encoder = FrameEncoder::new(Vec::new());
+    let mut md5_context = md5::Context::new();
+    let mut last_file_path = None;
+
+    for file_path in file_paths {
+      let file = package.file_mut(&file_path).unwrap();
+      let contents = file
+        .contents()
+        .as_ref()
+        .ok_or(Error::FileEmpty(file_path.to_string_lossy().into_owned()))?;
+
+      md5_context.consume(contents);
+      let start = (encoder.get_ref().len() as u64).max(LZ4_FRAME_HEADER_SIZE);
+      encoder.write_all(contents)?;
+      encoder.flush()?;
+      let end = (encoder.get_ref().len() as u64).max(LZ4_FRAME_HEADER_SIZE);
+      let path = file_path.to_string_lossy();
+      let uncompressed_size = checked_u32(&path, contents.len() as u64)?;
+
+      // Solid archives are always LZ4 compressed, only the level is taken over
+      let compression_options: CompressionOptions = (file.flags() as u8).into();
+      let flags: u8 = CompressionOptions::new(Method::Lz4, *compression_options.level()).into();
+
+      file.set_offset(start);
+      file.set_size_on_disk(checked_u32(&path, end - start)?);
+      file.set_uncompressed_size(uncompressed_size);
+      file.set_part_index(0);
+      file.set_flags(flags as u32);
+      last_file_path = Some(file_path);
+    }
+
+    let frame_size = encoder.get_ref().len() as u64;
+    let frame = encoder.finish().map_err(std::io::Error::from)?;
+
+    // The frame end mark is accounted to the last file
+    if let Some(file_path) = last_file_path {
+      let file = package.file_mut(&file_path).unwrap();
+      let end_mark_size = frame.len() as u64 - frame_size.max(LZ4_FRAME_HEADER_SIZE);
+      let size_on_disk = file.size_on_disk() as u64 + end_mark_size;
+      file.set_size_on_disk(checked_u32(&file_path.to_string_lossy(), size_on_disk)?);
+    }
+
+    // CRCs cover the stored bytes of every file, so they can only be computed once the frame is complete
+    for file in package.files_mut().values_mut() {
+      let start = file.offset() as usize;
+      file.set_crc(hash(&frame[start..start + file.size_on_disk() as usize]));
+    }
+    package
+      .header_mut()
+      .set_md5(finalize_lsv_archive_hash(md5_context));
+
+    self.seek(SeekFrom::Start(0))?;
+    self.write_all(&frame)?;
+
+    Ok(())
+  }
+
+  fn write_lsv_v13_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
+    let file_paths = sorted_file_paths(package);
+    let mut cursor = Cursor::new(Vec::with_capacity((256 + 6 * 4) * file_paths.len()));
+
+    for file_path in &file_paths {
+      let file = package.file(file_path).unwrap();
+      let path = file.path().to_string_lossy().replace('\\', "/");
+      if path.len() >= 256 {
+        return Err(Error::InvalidPath(path));
+      }
+      cursor.write_utf8_string(&path, 256)?;
+      cursor.write_u32::<LittleEndian>(checked_u32(&path, file.offset())?)?;
+      cursor.write_u32::<LittleEndian>(file.size_on_disk())?;
+      cursor.write_u32::<LittleEndian>(file.uncompressed_size())?;
+      cursor.write_u32::<LittleEndian>(file.part_index())?;
+      cursor.write_u32::<LittleEndian>(file.flags())?;
+      cursor.write_u32::<LittleEndian>(file.crc())?;
+    }
+
+    let file_table_offset = self.stream_position()?;
+    let compressed_bytes = compress_block(cursor.get_ref());
+    self.write_i32::<LittleEndian>(file_paths.len() as i32)?;
+    self.write_all(&compressed_bytes)?;
+
+    let file_table_size = checked_u32("file table", 4 + compressed_bytes.len() as u64)?;
+    let header = package.header_mut();
+    header.set_file_table_offset(file_table_offset);
+    header.set_file_table_size(file_table_size);
+
+    Ok(())
+  }
+
+  // The archive hash is computed while the files are written
+  fn write_lsv_v13_header(&mut self, package: &mut Package) -> Result<(), Error> {
+    let header: &Header = package.header();
+
+    self.write_u32::<LittleEndian>(header.version() as u32)?;
+    self.write_u32::<LittleEndian>(checked_u32("file table", header.file_table_offset())?)?;
+    self.write_u32::<LittleEndian>(header.file_table_size())?;
+    self.write_u16::<LittleEndian>(header.part_count())?;
+    self.write_u8(header.flags())?;
+    self.write_u8(header.priority())?;
+    self.write_all(&header.md5())?;
+    // The header is located by its size (including the size itself and the signature) at the end of the file
+    self.write_u32::<LittleEndian>(HEADER_SIZE + 8)?;
+    self.write_all(&SIGNATURE)?;
+
+    Ok(())
+  }
+}
+
+impl<W: Write + Seek + ?Sized> LsvV13WriteExt for W {}
+
+// Bytes a file is written with, either compressed from its contents or copied as stored
+enum FileBytes<'a, 'b> {
+  Compressed(Vec<u8>, u32),
+  Stored(&'a mut CopyStored<'b>),
+}
+
+// Writes the file contents into the part streams, starting with the last one. Packages are only
+// split when a maximum part size is given, a file that would exceed it in the current part starts
+// a new one. Files without contents are copied through copy_stored if given. Solid archives are
+// never split.
+pub fn write_lsv_v13_part_files<W: Write + Seek>(
+  streams: &mut Vec<W>,
+  package: &mut Package,
+  mut split: Option<(u64, CreatePart<'_, W>)>,
+  mut copy_stored: Option<CopyStored<'_>>,
+) -> Result<(), Error> {
+  if package.header().is_solid() {
+    streams[0].write_lsv_v13_solid_files(package)?;
+    package.header_mut().set_part_count(1);
+    return Ok(());
+  }
+
+  let is_aligned = package.header().flags() & Header::FLAGS_ALLOW_MEMORY_MAPPING != 0;
+  let mut md5_context = md5::Context::new();
+  for file_path in sorted_file_paths(package) {
+    let file = package.file_mut(&file_path).unwrap();
+    let bytes = match (file.contents(), copy_stored.as_mut()) {
+      (Some(contents), _) => {
+        md5_context.consume(contents);
+        let compression_options: CompressionOptions = (file.flags() as u8).into();
+        let bytes = compress(contents, &compression_options)?;
+        let uncompressed_size = if file.is_compressed() {
+          checked_u32(&file_path.to_string_lossy(), contents.len() as u64)?
+        } else {
+          0
+        };
+        FileBytes::Compressed(bytes, uncompressed_size)
+      }
+      (None, Some(copy_stored)) => FileBytes::Stored(copy_stored),
+      (None, None) => return Err(Error::FileEmpty(file_path.to_string_lossy().into_owned())),
+    };
+    let size = match &bytes {
+      FileBytes::Compressed(bytes, _) => bytes.len() as u64,
+      FileBytes::Stored(_) => file.size_on_disk() as u64,
+    };
+
+    let mut part_index = streams.len() - 1;
+    let mut offset = streams[part_index].stream_position()?;
+    // Files are never split, a part only exceeds the limit if a single file is larger than it
+    if let Some((max_part_size, create_part)) = &mut split {
+      if offset > 0 && offset + size > *max_part_size {
+        part_index += 1;
+        streams.push(create_part(part_index as u16)?);
+        offset = streams[part_index].stream_position()?;
+      }
+    }
+    if is_aligned && offset % MEMORY_MAPPING_ALIGNMENT != 0 {
+      let padding = MEMORY_MAPPING_ALIGNMENT - offset % MEMORY_MAPPING_ALIGNMENT;
+      streams[part_index].write_all(&vec![0; padding as usize])?;
+      offset += padding;
+    }
+
+    match bytes {
+      FileBytes::Compressed(bytes, uncompressed_size) => {
+        let size_on_disk = checked_u32(&file_path.to_string_lossy(), bytes.len() as u64)?;
+        streams[part_index].write_all(&bytes)?;
+        file.set_size_on_disk(size_on_disk);
+        file.set_uncompressed_size(uncompressed_size);
+        file.set_crc(hash(&bytes));
+      }
+      // Copied files keep their sizes, flags and CRC
+      FileBytes::Stored(copy_stored) => {
+        copy_stored(file, &mut streams[part_index], &mut md5_context)?
+      }
+    }
+    file.set_offset(offset);
+    file.set_part_index(part_index as u32);
+  }
+
+  package
+    .header_mut()
+    .set_md5(finalize_lsv_archive_hash(md5_context));
+  package.header_mut().set_part_count(streams.len() as u16);
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use crate::{
+    compression::{Level, Method},
+    lsv::{
+      file::File,
+      header::Version,
+      read::{read_lsv_file_contents, LsvReadExt},
+      write::LsvWriteExt,
+    },
+  };
+
+  use super::*;
+
+  fn new_file(path: &str, method: Method, contents: &[u8]) -> File {
+    let mut file = File::new();
+    file.set_path(PathBuf::from(path));
+    let flags: u8 = CompressionOptions::new(method, Level::Default).into();
+    file.set_flags(if method == Method::None {
+      0
+    } else {
+      flags as u32
+    });
+    file.set_contents(Some(contents.to_vec()));
+    file
+  }
+
+  #[test]
+  fn test_write_lsv_v13_package() {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    package.header_mut().set_priority(21);
+    for file in [
+      new_file("meta.lsf", Method::None, b"meta"),
+      new_file(
+        "globals.lsf",
+        Method::Zlib,
+        b"globals globals globals globals",
+      ),
+      new_file(
+        "Levels/Cyseal.lsf",
+        Method::Lz4,
+        b"cyseal cyseal cyseal cyseal",
+      ),
+    ] {
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_lsv_package(&mut package).unwrap();
+
+    let mut reader = Cursor::new(writer.into_inner());
+    let mut read_package = Package::default();
+    reader.read_lsv_package(&mut read_package).unwrap();
+    assert_eq!(read_package.header().version(), Version::V13);
+    assert_eq!(read_package.header().priority(), 21);
+    assert_eq!(read_package.header().part_count(), 1);
+    assert_eq!(read_package.files().len(), 3);
+
+    let mut readers = vec![reader];
+    for (path, file) in package.files() {
+      let read_file = read_package.file(path).unwrap();
+      assert_eq!(read_file.flags(), file.flags());
+      assert_eq!(read_file.crc(), file.crc());
+      let contents =
+        read_lsv_file_contents(&mut readers, read_package.header(), read_file).unwrap();
+      assert_eq!(&contents, file.contents().as_ref().unwrap());
+    }
+  }
+
+  #[test]
+  fn test_write_lsv_v13_solid_package() {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    package.header_mut().set_flags(Header::FLAGS_SOLID);
+    for file in [
+      new_file("meta.lsf", Method::None, b"meta"),
+      new_file(
+        "globals.lsf",
+        Method::Lz4,
+        b"globals globals globals globals",
+      ),
+      new_file(
+        "Levels/Cyseal.lsf",
+        Method::Zlib,
+        b"cyseal cyseal cyseal cyseal",
+      ),
+    ] {
+      package
+        .file_table_mut()
+        .insert(file.path().to_owned(), file);
+    }
+
+    let mut writer = Cursor::new(vec![]);
+    writer.write_lsv_package(&mut package).unwrap();
+
+    let mut reader = Cursor::new(writer.into_inner());
+    let mut read_package = Package::default();
+    reader.read_lsv_package(&mut read_package).unwrap();
+    assert!(read_package.header().is_solid());
+    assert_eq!(read_package.files().len(), 3);
+
+    let mut readers = vec![reader];
+    for (path, file) in package.files() {
+      let read_file = read_package.file(path).unwrap();
+      assert_eq!(read_file.compression_method(), Method::Lz4);
+      assert_eq!(read_file.contents(), file.contents());
+      let contents =
+        read_lsv_file_contents(&mut readers, read_package.header(), read_file).unwrap();
+      assert_eq!(&contents, file.contents().as_ref().unwrap());
+    }
+  }
+
+  #[test]
+  fn test_write_lsv_v13_package_requires_contents() {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    let mut file = new_file("meta.lsf", Method::None, b"");
+    file.clear_contents();
+    package
+      .file_table_mut()
+      .insert(Path::new("meta.lsf").to_owned(), file);
+
+    let mut writer = Cursor::new(vec![]);
+    assert!(writer.write_lsv_package(&mut package).is_err());
+  }
+
+  #[test]
+  fn test_write_lsv_v13_offset_too_large() {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V13);
+    let mut file = new_file("meta.lsf", Method::None, b"meta");
+    file.set_offset(u32::MAX as u64 + 1);
+    package
+      .file_table_mut()
+      .insert(Path::new("meta.lsf").to_owned(), file);
+
+    let mut writer = Cursor::new(vec![]);
+    assert!(matches!(
+      writer.write_lsv_v13_file_table(&mut package),
+      Err(Error::FileTooLarge(path, _)) if path == "meta.lsf"
+    ));
+  }
+}
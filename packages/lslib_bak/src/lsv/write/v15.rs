@@ -0,0 +1,198 @@
+use std::io::{Cursor, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use lz4_flex::compress as compress_block;
+
+use crate::{
+  error::Error,
+  lsv::{
+    header::{Header, Version, SIGNATURE},
+    package::Package,
+  },
+  util::write::BinaryWriteExt,
+};
+
+use super::{sorted_file_paths, v13::write_lsv_v13_part_files, CopyStored, CreatePart};
+
+// Signature (4) + version (4) + file table offset (8) + file table size (4) + flags (1) + priority (1) + md5 (16)
+const V15_HEADER_SIZE: usize = 38;
+
+// v16 and v18 append the part count (2)
+const V16_HEADER_SIZE: usize = V15_HEADER_SIZE + 2;
+
+pub trait LsvV15WriteExt: Write + Seek {
+  // Writes v15, v16 and v18 packages, which share the header at the start of the file
+  fn write_lsv_v15_package(&mut self, package: &mut Package) -> Result<(), Error> {
+    write_lsv_v15_part_files(&mut vec![self], package, None, None)
+  }
+
+  fn write_lsv_v15_file_table(&mut self, package: &mut Package) -> Result<(), Error> {
+    let version = package.header().version();
+    let file_paths = sorted_file_paths(package);
+    let mut cursor = Cursor::new(vec![]);
+
+    for file_path in &file_paths {
+      let file = package.file(file_path).unwrap();
+      let path = file.path().to_string_lossy().replace('\\', "/");
+      if path.len() >= 256 {
+        return Err(Error::InvalidPath(path));
+      }
+      cursor.write_utf8_string(&path, 256)?;
+      if version == Version::V18 {
+        // The 48-bit offset is split into a low 32-bit and a high 16-bit part
+        cursor.write_u32::<LittleEndian>(file.offset() as u32)?;
+        cursor.write_u16::<LittleEndian>((file.offset() >> 32) as u16)?;
+        cursor.write_u8(file.part_index() as u8)?;
+        cursor.write_u8(file.flags() as u8)?;
+        cursor.write_u32::<LittleEndian>(file.size_on_disk())?;
+        cursor.write_u32::<LittleEndian>(file.uncompressed_size())?;
+      } else {
+        cursor.write_u64::<LittleEndian>(file.offset())?;
+        cursor.write_u64::<LittleEndian>(file.size_on_disk() as u64)?;
+        cursor.write_u64::<LittleEndian>(file.uncompressed_size() as u64)?;
+        cursor.write_u32::<LittleEndian>(file.part_index())?;
+        cursor.write_u32::<LittleEndian>(file.flags())?;
+        cursor.write_u32::<LittleEndian>(file.crc())?;
+        cursor.write_u32::<LittleEndian>(0)?;
+      }
+    }
+
+    let file_table_offset = self.stream_position()?;
+    let compressed_bytes = compress_block(cursor.get_ref());
+    self.write_i32::<LittleEndian>(file_paths.len() as i32)?;
+    self.write_i32::<LittleEndian>(compressed_bytes.len() as i32)?;
+    self.write_all(&compressed_bytes)?;
+
+    let header = package.header_mut();
+    header.set_file_table_offset(file_table_offset);
+    header.set_file_table_size(8 + compressed_bytes.len() as u32);
+
+    Ok(())
+  }
+
+  // The archive hash is computed while the files are written
+  fn write_lsv_v15_header(&mut self, package: &mut Package) -> Result<(), Error> {
+    let header: &Header = package.header();
+
+    self.write_all(&SIGNATURE)?;
+    self.write_u32::<LittleEndian>(header.version() as u32)?;
+    self.write_u64::<LittleEndian>(header.file_table_offset())?;
+    self.write_u32::<LittleEndian>(header.file_table_size())?;
+    self.write_u8(header.flags())?;
+    self.write_u8(header.priority())?;
+    self.write_all(&header.md5())?;
+    if header.version() >= Version::V16 {
+      self.write_u16::<LittleEndian>(header.part_count())?;
+    }
+
+    Ok(())
+  }
+}
+
+impl<W: Write + Seek + ?Sized> LsvV15WriteExt for W {}
+
+// Writes the files like v13 does, but behind the space reserved for the header. The file table
+// follows the files in the main archive, the header is filled in last.
+pub fn write_lsv_v15_part_files<W: Write + Seek>(
+  streams: &mut Vec<W>,
+  package: &mut Package,
+  split: Option<(u64, CreatePart<'_, W>)>,
+  copy_stored: Option<CopyStored<'_>>,
+) -> Result<(), Error> {
+  let version = package.header().version();
+  if package.header().is_solid() {
+    return Err(Error::Unsupported(format!(
+      "Solid v{} packages",
+      version as i32
+    )));
+  }
+
+  let header_size = if version >= Version::V16 {
+    V16_HEADER_SIZE
+  } else {
+    V15_HEADER_SIZE
+  };
+  streams[0].seek(SeekFrom::Start(0))?;
+  streams[0].write_all(&vec![0; header_size])?;
+
+  // v15 packages always consist of a single part
+  let split = if version == Version::V15 { None } else { split };
+  write_lsv_v13_part_files(streams, package, split, copy_stored)?;
+
+  let main_stream = &mut streams[0];
+  main_stream.seek(SeekFrom::End(0))?;
+  main_stream.write_lsv_v15_file_table(package)?;
+  main_stream.seek(SeekFrom::Start(0))?;
+  main_stream.write_lsv_v15_header(package)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use crate::{
+    compression::{CompressionOptions, Level, Method},
+    lsv::{
+      file::File,
+      read::{read_lsv_file_contents, LsvReadExt},
+      write::LsvWriteExt,
+    },
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_write_lsv_v15_packages() {
+    for version in [Version::V15, Version::V16, Version::V18] {
+      let mut package = Package::default();
+      package.header_mut().set_version(version);
+      package.header_mut().set_priority(30);
+      for (path, method) in [
+        ("Public/Shared/Stats.txt", Method::Zstd),
+        ("Public/Shared/meta.lsx", Method::None),
+        ("Localization/English.loca", Method::Lz4),
+      ] {
+        let mut file = File::new();
+        file.set_path(PathBuf::from(path));
+        if method != Method::None {
+          let flags: u8 = CompressionOptions::new(method, Level::Default).into();
+          file.set_flags(flags as u32);
+        }
+        file.set_contents(Some(path.repeat(4).into_bytes()));
+        package
+          .file_table_mut()
+          .insert(file.path().to_owned(), file);
+      }
+
+      let mut writer = Cursor::new(vec![]);
+      writer.write_lsv_package(&mut package).unwrap();
+
+      let mut reader = Cursor::new(writer.into_inner());
+      let mut read_package = Package::default();
+      reader.read_lsv_package(&mut read_package).unwrap();
+      assert_eq!(read_package.header().version(), version);
+      assert_eq!(read_package.header().priority(), 30);
+      assert_eq!(read_package.header().part_count(), 1);
+      assert_eq!(read_package.header().md5(), package.header().md5());
+      assert_eq!(read_package.files().len(), 3);
+
+      let mut readers = vec![reader];
+      for (path, file) in package.files() {
+        let read_file = read_package.file(path).unwrap();
+        assert_eq!(read_file.compression_method(), file.compression_method());
+        let contents =
+          read_lsv_file_contents(&mut readers, read_package.header(), read_file).unwrap();
+        assert_eq!(&contents, file.contents().as_ref().unwrap());
+      }
+    }
+  }
+
+  #[test]
+  fn test_write_lsv_v15_solid_package() {
+    let mut package = Package::default();
+    package.header_mut().set_version(Version::V18);
+    package.header_mut().set_flags(Header::FLAGS_SOLID);
+    let mut writer = Cursor::new(vec![]);
+    assert!(writer.write_lsv_package(&mut package).is_err());
+  }
+}
@@ -0,0 +1,3 @@
+pub mod read;
+mod value;
+pub mod write;
@@ -0,0 +1,298 @@
+use std::{collections::HashMap, io::BufRead};
+
+use quick_xml::{
+  events::{BytesStart, Event},
+  name::QName,
+  Reader,
+};
+
+use crate::{
+  error::Error,
+  resource::{
+    metadata::Metadata,
+    node::{attribute::Attribute, data::Data},
+    value::{TranslatedFsStringArgument, Value},
+    Resource,
+  },
+  util::arena::{ArenaWriter, Index},
+};
+
+use super::value::parse_lsx_value;
+
+// The XML attributes of an element, unescaped
+fn element_attributes(element: &BytesStart) -> Result<HashMap<String, String>, Error> {
+  let mut attributes = HashMap::new();
+  for attribute in element.attributes() {
+    let attribute = attribute.map_err(quick_xml::Error::from)?;
+    let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+    attributes.insert(key, attribute.unescape_value()?.into_owned());
+  }
+  Ok(attributes)
+}
+
+fn required<'a>(
+  attributes: &'a HashMap<String, String>,
+  element: &str,
+  key: &str,
+) -> Result<&'a str, Error> {
+  attributes
+    .get(key)
+    .map(|value| value.as_str())
+    .ok_or(Error::InvalidLsx(format!(
+      "<{}> without {} attribute",
+      element, key
+    )))
+}
+
+fn optional<'a>(attributes: &'a HashMap<String, String>, key: &str) -> &'a str {
+  attributes
+    .get(key)
+    .map(|value| value.as_str())
+    .unwrap_or("")
+}
+
+fn parse_number<T: std::str::FromStr + Default>(
+  attributes: &HashMap<String, String>,
+  key: &str,
+) -> Result<T, Error> {
+  match attributes.get(key) {
+    Some(value) => value
+      .trim()
+      .parse()
+      .map_err(|_| Error::InvalidLsx(format!("Invalid {}: {}", key, value))),
+    None => Ok(T::default()),
+  }
+}
+
+// Reads the arguments of a translated string up to the end of its element
+fn read_lsx_translated_fs_string<R: BufRead>(
+  reader: &mut Reader<R>,
+  attributes: &HashMap<String, String>,
+) -> Result<Value, Error> {
+  let mut arguments = vec![];
+  let mut argument: Option<(String, String)> = None;
+  let mut buffer = vec![];
+  loop {
+    match reader.read_event_into(&mut buffer)? {
+      Event::Start(element) => match element.name().as_ref() {
+        b"argument" => {
+          let argument_attributes = element_attributes(&element)?;
+          argument = Some((
+            required(&argument_attributes, "argument", "key")?.to_owned(),
+            optional(&argument_attributes, "value").to_owned(),
+          ));
+        }
+        b"string" => {
+          let string_attributes = element_attributes(&element)?;
+          let (key, value) = argument.take().ok_or(Error::InvalidLsx(
+            "<string> outside of <argument>".to_string(),
+          ))?;
+          let string = read_lsx_translated_fs_string(reader, &string_attributes)?;
+          arguments.push(TranslatedFsStringArgument { key, string, value });
+        }
+        _ => {}
+      },
+      Event::End(element) => {
+        if matches!(element.name().as_ref(), b"attribute" | b"string") {
+          break;
+        }
+      }
+      Event::Eof => {
+        return Err(Error::InvalidLsx(
+          "Unexpected end of translated string".to_string(),
+        ))
+      }
+      _ => {}
+    }
+    buffer.clear();
+  }
+
+  Ok(Value::TranslatedFsString {
+    version: parse_number(attributes, "version")?,
+    value: optional(attributes, "value").to_owned(),
+    handle: optional(attributes, "handle").to_owned(),
+    arguments,
+  })
+}
+
+fn read_lsx_attribute<R: BufRead>(
+  reader: &mut Reader<R>,
+  attributes: &HashMap<String, String>,
+) -> Result<(String, Value), Error> {
+  let name = required(attributes, "attribute", "id")?.to_owned();
+  let type_name = required(attributes, "attribute", "type")?;
+  let type_id = Value::type_id_from_name(type_name)
+    .ok_or(Error::InvalidLsx(format!("Invalid type: {}", type_name)))?;
+
+  let value = match type_id {
+    28 => Value::TranslatedString {
+      version: parse_number(attributes, "version")?,
+      value: optional(attributes, "value").to_owned(),
+      handle: optional(attributes, "handle").to_owned(),
+    },
+    33 => return Ok((name, read_lsx_translated_fs_string(reader, attributes)?)),
+    _ => parse_lsx_value(type_id, optional(attributes, "value"))?,
+  };
+  reader.read_to_end_into(QName(b"attribute"), &mut vec![])?;
+  Ok((name, value))
+}
+
+pub trait LsxReadExt: BufRead {
+  // Reads an LSX document. Like in LSF files, the node of every region becomes a root node.
+  fn read_lsx_resource(&mut self) -> Result<Resource, Error> {
+    let mut reader = Reader::from_reader(self);
+    reader.expand_empty_elements(true);
+
+    let mut resource = Resource::new();
+    let mut metadata = Metadata::new();
+    let mut region: Option<String> = None;
+    let mut nodes: Vec<Index> = vec![];
+    let mut buffer = vec![];
+    loop {
+      match reader.read_event_into(&mut buffer)? {
+        Event::Start(element) => {
+          let attributes = element_attributes(&element)?;
+          match element.name().as_ref() {
+            b"header" => metadata.set_timestamp(parse_number(&attributes, "time")?),
+            b"version" => {
+              metadata.set_major_version(parse_number(&attributes, "major")?);
+              metadata.set_minor_version(parse_number(&attributes, "minor")?);
+              metadata.set_revision(parse_number(&attributes, "revision")?);
+              metadata.set_build_number(parse_number(&attributes, "build")?);
+            }
+            b"region" => region = Some(required(&attributes, "region", "id")?.to_owned()),
+            b"node" => {
+              let name = required(&attributes, "node", "id")?.to_owned();
+              let mut data = match (&region, nodes.last()) {
+                (Some(region), None) => Data::new_region(name, region.to_owned()),
+                _ => Data::new(name),
+              };
+              data.set_key(attributes.get("key").cloned());
+              let index = resource.alloc(data, nodes.last().copied());
+              nodes.push(index);
+            }
+            b"attribute" => {
+              let node = *nodes.last().ok_or(Error::InvalidLsx(
+                "<attribute> outside of <node>".to_string(),
+              ))?;
+              let (name, value) = read_lsx_attribute(&mut reader, &attributes)?;
+              resource
+                .value_mut(node)
+                .attributes_mut()
+                .insert(name, Attribute::new_value(value));
+            }
+            _ => {}
+          }
+        }
+        Event::End(element) => match element.name().as_ref() {
+          b"node" => {
+            nodes.pop();
+          }
+          b"region" => region = None,
+          _ => {}
+        },
+        Event::Eof => break,
+        _ => {}
+      }
+      buffer.clear();
+    }
+
+    resource.set_metadata(metadata);
+    Ok(resource)
+  }
+}
+
+impl<R: BufRead + ?Sized> LsxReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use crate::{
+    lsx::write::LsxWriteExt,
+    resource::node::{attribute::AttributeMap, data::Kind},
+    util::arena::ArenaReader,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_read_lsx_resource() {
+    let lsx =
+      include_str!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/modsettings.lsx");
+    let resource = Cursor::new(lsx).read_lsx_resource().unwrap();
+    assert_eq!(resource.metadata().major_version(), 2);
+    assert_eq!(resource.metadata().timestamp(), 1682269346);
+
+    let root = resource.root_indexes()[0];
+    let data = resource.value(root);
+    assert_eq!(data.name(), "root");
+    assert_eq!(data.kind(), &Kind::Region("ModuleSettings".to_string()));
+
+    let mods = resource.child_indexes(root)[0];
+    let module = resource.child_indexes(mods)[0];
+    let attributes = resource.value(module).attributes();
+    assert_eq!(
+      attributes.get("Folder").unwrap().value(),
+      &Value::LswString("Main".to_string())
+    );
+    assert_eq!(
+      attributes.get("Name").unwrap().value(),
+      &Value::FixedString("Main".to_string())
+    );
+  }
+
+  #[test]
+  fn test_lsx_round_trip() {
+    let mut resource = Resource::new();
+    let mut attributes = AttributeMap::new();
+    attributes.insert(
+      "DisplayName".to_string(),
+      Attribute::new_value(Value::TranslatedFsString {
+        version: 1,
+        value: "".to_string(),
+        handle: "h1".to_string(),
+        arguments: vec![TranslatedFsStringArgument {
+          key: "Name".to_string(),
+          string: Value::TranslatedFsString {
+            version: 0,
+            value: "Fane".to_string(),
+            handle: "h2".to_string(),
+            arguments: vec![],
+          },
+          value: "<b>".to_string(),
+        }],
+      }),
+    );
+    attributes.insert(
+      "Description".to_string(),
+      Attribute::new_value(Value::TranslatedString {
+        version: 3,
+        value: "".to_string(),
+        handle: "h3".to_string(),
+      }),
+    );
+    attributes.insert(
+      "Notes".to_string(),
+      Attribute::new_value(Value::LsString("Line 1\nLine 2 & \"3\"".to_string())),
+    );
+    let root = resource.alloc(
+      Data::new_region("Characters".to_string(), "Characters".to_string()),
+      None,
+    );
+    resource.alloc(
+      Data::new_with_attributes("Character".to_string(), attributes),
+      Some(root),
+    );
+
+    let mut lsx = vec![];
+    lsx.write_lsx_resource(&resource).unwrap();
+    let read_resource = Cursor::new(&lsx).read_lsx_resource().unwrap();
+    assert_eq!(read_resource.size(), 2);
+    let character = read_resource.child_indexes(read_resource.root_indexes()[0])[0];
+    assert_eq!(
+      read_resource.value(character),
+      resource.value(resource.child_indexes(root)[0])
+    );
+  }
+}
@@ -0,0 +1,218 @@
+use std::str::FromStr;
+
+use crate::{error::Error, resource::value::Value};
+
+const BASE64_ALPHABET: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+  let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let group = (chunk[0] as u32) << 16
+      | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+      | *chunk.get(2).unwrap_or(&0) as u32;
+    for position in 0..4 {
+      if position <= chunk.len() {
+        let sextet = (group >> (18 - 6 * position)) & 0x3f;
+        encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+  let encoded = encoded.trim_end_matches('=');
+  let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+  let mut group = 0u32;
+  for (position, character) in encoded.bytes().enumerate() {
+    let sextet = BASE64_ALPHABET
+      .iter()
+      .position(|alphabet_character| *alphabet_character == character)?;
+    group = group << 6 | sextet as u32;
+    if position % 4 == 3 {
+      bytes.extend_from_slice(&group.to_be_bytes()[1..]);
+      group = 0;
+    }
+  }
+  match encoded.len() % 4 {
+    0 => {}
+    2 => bytes.push((group >> 4) as u8),
+    3 => bytes.extend_from_slice(&((group >> 2) as u16).to_be_bytes()),
+    _ => return None,
+  }
+  Some(bytes)
+}
+
+// GUIDs are stored like .NET does, with the first three groups in little endian
+fn format_guid(bytes: &[u8; 16]) -> String {
+  format!(
+    "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+    bytes[3], bytes[2], bytes[1], bytes[0], bytes[5], bytes[4], bytes[7], bytes[6],
+    bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]
+  )
+}
+
+fn parse_guid(guid: &str) -> Option<[u8; 16]> {
+  let hex = guid.replace('-', "");
+  if hex.len() != 32 || !hex.is_ascii() {
+    return None;
+  }
+  let mut bytes = [0u8; 16];
+  for (index, byte) in bytes.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+  }
+  bytes[0..4].reverse();
+  bytes[4..6].reverse();
+  bytes[6..8].reverse();
+  Some(bytes)
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+  values
+    .iter()
+    .map(|value| value.to_string())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn parse<T: FromStr>(value: &str) -> Option<T> {
+  value.trim().parse().ok()
+}
+
+fn parse_array<T: FromStr + Default + Copy, const N: usize>(value: &str) -> Option<[T; N]> {
+  let mut array = [T::default(); N];
+  let mut components = value.split_whitespace();
+  for component in array.iter_mut() {
+    *component = components.next()?.parse().ok()?;
+  }
+  components.next().is_none().then_some(array)
+}
+
+fn parse_matrix<const R: usize, const C: usize>(value: &str) -> Option<[[f32; C]; R]> {
+  let mut matrix = [[0f32; C]; R];
+  let mut components = value.split_whitespace();
+  for component in matrix.iter_mut().flatten() {
+    *component = components.next()?.parse().ok()?;
+  }
+  components.next().is_none().then_some(matrix)
+}
+
+// Formats simple values the way they appear in the value attribute
+pub(crate) fn format_lsx_value(value: &Value) -> String {
+  match value {
+    Value::Bool(value) => (if *value { "True" } else { "False" }).to_string(),
+    Value::IVec2(value) => join(value),
+    Value::IVec3(value) => join(value),
+    Value::IVec4(value) => join(value),
+    Value::Vec2(value) => join(value),
+    Value::Vec3(value) => join(value),
+    Value::Vec4(value) => join(value),
+    Value::Mat2(value) => join(&value.concat()),
+    Value::Mat3(value) => join(&value.concat()),
+    Value::Mat3x4(value) => join(&value.concat()),
+    Value::Mat4x3(value) => join(&value.concat()),
+    Value::Mat4(value) => join(&value.concat()),
+    Value::ScratchBuffer(value) => encode_base64(value),
+    Value::Uuid(value) => format_guid(value),
+    Value::None => String::new(),
+    value => value.to_string(),
+  }
+}
+
+// Parses the value attribute of simple values. Translated strings carry more than one attribute,
+// so they are built by the reader.
+pub(crate) fn parse_lsx_value(type_id: u32, value: &str) -> Result<Value, Error> {
+  let parsed = match type_id {
+    0 => Some(Value::None),
+    1 => parse(value).map(Value::Byte),
+    2 => parse(value).map(Value::Short),
+    3 => parse(value).map(Value::UShort),
+    4 => parse(value).map(Value::Int),
+    5 => parse(value).map(Value::UInt),
+    6 => parse(value).map(Value::Float),
+    7 => parse(value).map(Value::Double),
+    8 => parse_array(value).map(Value::IVec2),
+    9 => parse_array(value).map(Value::IVec3),
+    10 => parse_array(value).map(Value::IVec4),
+    11 => parse_array(value).map(Value::Vec2),
+    12 => parse_array(value).map(Value::Vec3),
+    13 => parse_array(value).map(Value::Vec4),
+    14 => parse_matrix(value).map(Value::Mat2),
+    15 => parse_matrix(value).map(Value::Mat3),
+    16 => parse_matrix(value).map(Value::Mat3x4),
+    17 => parse_matrix(value).map(Value::Mat4x3),
+    18 => parse_matrix(value).map(Value::Mat4),
+    19 => match value.trim().to_lowercase().as_str() {
+      "true" | "1" => Some(Value::Bool(true)),
+      "false" | "0" => Some(Value::Bool(false)),
+      _ => None,
+    },
+    20 => Some(Value::String(value.to_owned())),
+    21 => Some(Value::Path(value.to_owned())),
+    22 => Some(Value::FixedString(value.to_owned())),
+    23 => Some(Value::LsString(value.to_owned())),
+    24 => parse(value).map(Value::ULongLong),
+    25 => decode_base64(value.trim()).map(Value::ScratchBuffer),
+    26 => parse(value).map(Value::Long),
+    27 => parse(value).map(Value::Int8),
+    29 => Some(Value::WString(value.to_owned())),
+    30 => Some(Value::LswString(value.to_owned())),
+    31 => parse_guid(value.trim()).map(Value::Uuid),
+    32 => parse(value).map(Value::Int64),
+    _ => return Err(Error::InvalidTypeId(type_id)),
+  };
+  parsed.ok_or(Error::InvalidValue(type_id, value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_base64() {
+    for (decoded, encoded) in [
+      (&b""[..], ""),
+      (b"f", "Zg=="),
+      (b"fo", "Zm8="),
+      (b"foo", "Zm9v"),
+      (b"foobar", "Zm9vYmFy"),
+    ] {
+      assert_eq!(encode_base64(decoded), encoded);
+      assert_eq!(decode_base64(encoded).unwrap(), decoded);
+    }
+    assert!(decode_base64("Z").is_none());
+    assert!(decode_base64("Zm9v!").is_none());
+  }
+
+  #[test]
+  fn test_lsx_value_round_trip() {
+    let values = [
+      Value::Bool(true),
+      Value::Int8(-5),
+      Value::Float(0.1),
+      Value::Vec3([1.0, -2.5, 3.25]),
+      Value::Mat2([[1.0, 2.0], [3.0, 4.0]]),
+      Value::FixedString("Shared".to_string()),
+      Value::ScratchBuffer(vec![0, 1, 2, 254, 255]),
+      Value::Uuid([
+        0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde,
+        0xf0,
+      ]),
+    ];
+    for value in values {
+      let formatted = format_lsx_value(&value);
+      assert_eq!(parse_lsx_value(value.type_id(), &formatted).unwrap(), value);
+    }
+    assert_eq!(
+      format_lsx_value(&Value::Uuid(
+        parse_guid("12345678-1234-5678-1234-56789abcdef0").unwrap()
+      )),
+      "12345678-1234-5678-1234-56789abcdef0"
+    );
+    assert!(parse_lsx_value(8, "1 2 3").is_err());
+    assert!(parse_lsx_value(19, "yes").is_err());
+  }
+}
@@ -0,0 +1,256 @@
+use std::io::Write;
+
+use crate::{
+  error::Error,
+  resource::{
+    node::data::Kind,
+    value::{TranslatedFsStringArgument, Value},
+    Resource,
+  },
+  util::arena::{ArenaReader, Index},
+};
+
+use super::value::format_lsx_value;
+
+fn escape(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for character in value.chars() {
+    match character {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      '\n' => escaped.push_str("&#10;"),
+      '\r' => escaped.push_str("&#13;"),
+      '\t' => escaped.push_str("&#9;"),
+      _ => escaped.push(character),
+    }
+  }
+  escaped
+}
+
+pub trait LsxWriteExt: Write {
+  fn write_lsx_resource(&mut self, resource: &Resource) -> Result<(), Error> {
+    let metadata = resource.metadata();
+    writeln!(self, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+    writeln!(self, "<save>")?;
+    writeln!(
+      self,
+      "\t<version major=\"{}\" minor=\"{}\" revision=\"{}\" build=\"{}\" />",
+      metadata.major_version(),
+      metadata.minor_version(),
+      metadata.revision(),
+      metadata.build_number()
+    )?;
+
+    // Every root node is a region, LSF files name them after the node
+    for &index in resource.root_indexes() {
+      let data = resource.value(index);
+      let region = match data.kind() {
+        Kind::Region(region) => region.as_str(),
+        Kind::Element => data.name(),
+      };
+      writeln!(self, "\t<region id=\"{}\">", escape(region))?;
+      self.write_lsx_node(resource, index, 2)?;
+      writeln!(self, "\t</region>")?;
+    }
+
+    writeln!(self, "</save>")?;
+    Ok(())
+  }
+
+  fn write_lsx_node(
+    &mut self,
+    resource: &Resource,
+    index: Index,
+    depth: usize,
+  ) -> Result<(), Error> {
+    let indent = "\t".repeat(depth);
+    let data = resource.value(index);
+    let children = resource.child_indexes(index);
+    let mut attributes: Vec<_> = data.attributes().iter().collect();
+    attributes.sort_by_key(|(name, _)| *name);
+
+    let key = match data.key() {
+      Some(key) => format!(" key=\"{}\"", escape(key)),
+      None => String::new(),
+    };
+
+    if attributes.is_empty() && children.is_empty() {
+      writeln!(
+        self,
+        "{}<node id=\"{}\"{} />",
+        indent,
+        escape(data.name()),
+        key
+      )?;
+      return Ok(());
+    }
+
+    writeln!(
+      self,
+      "{}<node id=\"{}\"{}>",
+      indent,
+      escape(data.name()),
+      key
+    )?;
+    for (name, attribute) in attributes {
+      self.write_lsx_attribute(name, attribute.value(), depth + 1)?;
+    }
+    if !children.is_empty() {
+      writeln!(self, "{}\t<children>", indent)?;
+      for &child_index in children {
+        self.write_lsx_node(resource, child_index, depth + 2)?;
+      }
+      writeln!(self, "{}\t</children>", indent)?;
+    }
+    writeln!(self, "{}</node>", indent)?;
+    Ok(())
+  }
+
+  fn write_lsx_attribute(&mut self, name: &str, value: &Value, depth: usize) -> Result<(), Error> {
+    let indent = "\t".repeat(depth);
+    write!(
+      self,
+      "{}<attribute id=\"{}\" type=\"{}\"",
+      indent,
+      escape(name),
+      value.type_name()
+    )?;
+    match value {
+      Value::TranslatedString {
+        version,
+        value,
+        handle,
+      } => {
+        write!(
+          self,
+          " handle=\"{}\" version=\"{}\"",
+          escape(handle),
+          version
+        )?;
+        if !value.is_empty() {
+          write!(self, " value=\"{}\"", escape(value))?;
+        }
+        writeln!(self, " />")?;
+      }
+      Value::TranslatedFsString { .. } => {
+        self.write_lsx_translated_fs_string(value, depth)?;
+        writeln!(self, "{}</attribute>", indent)?;
+      }
+      value => writeln!(self, " value=\"{}\" />", escape(&format_lsx_value(value)))?,
+    }
+    Ok(())
+  }
+
+  // Writes the remaining attributes and argument elements of an already opened element
+  fn write_lsx_translated_fs_string(&mut self, value: &Value, depth: usize) -> Result<(), Error> {
+    let indent = "\t".repeat(depth);
+    let (version, value, handle, arguments): (u16, &str, &str, &[TranslatedFsStringArgument]) =
+      match value {
+        Value::TranslatedFsString {
+          version,
+          value,
+          handle,
+          arguments,
+        } => (*version, value, handle, arguments),
+        Value::TranslatedString {
+          version,
+          value,
+          handle,
+        } => (*version, value, handle, &[]),
+        _ => (0, "", "", &[]),
+      };
+    writeln!(
+      self,
+      " value=\"{}\" handle=\"{}\" version=\"{}\" arguments=\"{}\">",
+      escape(value),
+      escape(handle),
+      version,
+      arguments.len()
+    )?;
+    if !arguments.is_empty() {
+      writeln!(self, "{}\t<arguments>", indent)?;
+      for argument in arguments {
+        writeln!(
+          self,
+          "{}\t\t<argument key=\"{}\" value=\"{}\">",
+          indent,
+          escape(&argument.key),
+          escape(&argument.value)
+        )?;
+        write!(self, "{}\t\t\t<string", indent)?;
+        self.write_lsx_translated_fs_string(&argument.string, depth + 3)?;
+        writeln!(self, "{}\t\t\t</string>", indent)?;
+        writeln!(self, "{}\t\t</argument>", indent)?;
+      }
+      writeln!(self, "{}\t</arguments>", indent)?;
+    }
+    Ok(())
+  }
+}
+
+impl<W: Write + ?Sized> LsxWriteExt for W {}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use crate::{
+    lsx::read::LsxReadExt,
+    resource::node::{
+      attribute::{Attribute, AttributeMap},
+      data::Data,
+    },
+    util::arena::ArenaWriter,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_write_lsx_resource() {
+    let mut resource = Resource::new();
+    let mut attributes = AttributeMap::new();
+    attributes.insert(
+      "Name".to_string(),
+      Attribute::new_value(Value::LsString("Ifan <Ben> Mac".to_string())),
+    );
+    attributes.insert(
+      "Position".to_string(),
+      Attribute::new_value(Value::Vec3([1.0, 2.5, -3.0])),
+    );
+    attributes.insert(
+      "IsDead".to_string(),
+      Attribute::new_value(Value::Bool(false)),
+    );
+    let root = resource.alloc(Data::new("Config".to_string()), None);
+    let mut character = Data::new_with_attributes("Character".to_string(), attributes);
+    character.set_key(Some("Name".to_string()));
+    resource.alloc(character, Some(root));
+
+    let mut bytes = vec![];
+    bytes.write_lsx_resource(&resource).unwrap();
+    let lsx = String::from_utf8(bytes).unwrap();
+    assert_eq!(
+      lsx,
+      "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+       <save>\n\
+       \t<version major=\"0\" minor=\"0\" revision=\"0\" build=\"0\" />\n\
+       \t<region id=\"Config\">\n\
+       \t\t<node id=\"Config\">\n\
+       \t\t\t<children>\n\
+       \t\t\t\t<node id=\"Character\" key=\"Name\">\n\
+       \t\t\t\t\t<attribute id=\"IsDead\" type=\"bool\" value=\"False\" />\n\
+       \t\t\t\t\t<attribute id=\"Name\" type=\"LSString\" value=\"Ifan &lt;Ben&gt; Mac\" />\n\
+       \t\t\t\t\t<attribute id=\"Position\" type=\"fvec3\" value=\"1 2.5 -3\" />\n\
+       \t\t\t\t</node>\n\
+       \t\t\t</children>\n\
+       \t\t</node>\n\
+       \t</region>\n\
+       </save>\n"
+    );
+    let read_resource = Cursor::new(lsx).read_lsx_resource().unwrap();
+    assert_eq!(read_resource.value(1).key(), Some("Name"));
+  }
+}
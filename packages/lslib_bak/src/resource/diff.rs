@@ -0,0 +1,239 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::util::arena::{ArenaReader, Index};
+
+use super::{reader::ResourceReader, value::Value, Resource};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceChange {
+  // Only the topmost added or removed node is reported, not its children
+  NodeAdded(String),
+  NodeRemoved(String),
+  AttributeAdded(String, String, Value),
+  AttributeRemoved(String, String, Value),
+  AttributeChanged(String, String, Value, Value),
+}
+
+impl ResourceChange {
+  // Path of the node, as returned by ResourceReader::full_path
+  pub fn path(&self) -> &str {
+    match self {
+      Self::NodeAdded(path)
+      | Self::NodeRemoved(path)
+      | Self::AttributeAdded(path, ..)
+      | Self::AttributeRemoved(path, ..)
+      | Self::AttributeChanged(path, ..) => path,
+    }
+  }
+
+  pub fn message(&self) -> String {
+    match self {
+      Self::NodeAdded(path) => format!("+ {}", path),
+      Self::NodeRemoved(path) => format!("- {}", path),
+      Self::AttributeAdded(path, name, value) => format!("+ {}@{} = {:?}", path, name, value),
+      Self::AttributeRemoved(path, name, value) => format!("- {}@{} = {:?}", path, name, value),
+      Self::AttributeChanged(path, name, old_value, new_value) => {
+        format!("~ {}@{}: {:?} -> {:?}", path, name, old_value, new_value)
+      }
+    }
+  }
+}
+
+// Paths of all nodes in pre-order, formatted like ResourceReader::full_path. Computing them top
+// down keeps this linear for nodes with many children.
+fn node_paths(resource: &Resource) -> Vec<(String, Index)> {
+  fn visit(
+    resource: &Resource,
+    parent_path: &str,
+    indexes: &[Index],
+    paths: &mut Vec<(String, Index)>,
+  ) {
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for &index in indexes {
+      *name_counts.entry(resource.value(index).name()).or_default() += 1;
+    }
+    let mut name_indexes: HashMap<&str, usize> = HashMap::new();
+    for &index in indexes {
+      let name = resource.value(index).name();
+      let path = match name_counts[name] {
+        1 => format!("{}/{}", parent_path, name),
+        _ => {
+          let name_index = name_indexes.entry(name).or_default();
+          *name_index += 1;
+          format!("{}/{}[{}]", parent_path, name, *name_index - 1)
+        }
+      };
+      paths.push((path.clone(), index));
+      visit(resource, &path, resource.child_indexes(index), paths);
+    }
+  }
+
+  let mut paths = Vec::with_capacity(resource.size());
+  visit(resource, "", resource.root_indexes(), &mut paths);
+  paths
+}
+
+// Compares two resources node by node. Nodes are matched by their path, so inserting a node
+// between siblings of the same name shows up as changes to the following siblings.
+pub fn diff_resources(old: &Resource, new: &Resource) -> Vec<ResourceChange> {
+  let old_paths = node_paths(old);
+  let new_paths = node_paths(new);
+  let old_indexes: HashMap<&str, Index> = old_paths
+    .iter()
+    .map(|(path, index)| (path.as_str(), *index))
+    .collect();
+  let new_indexes: HashMap<&str, Index> = new_paths
+    .iter()
+    .map(|(path, index)| (path.as_str(), *index))
+    .collect();
+
+  let mut changes = vec![];
+  let mut removed: BTreeSet<Index> = BTreeSet::new();
+  for (path, old_index) in &old_paths {
+    let new_index = match new_indexes.get(path.as_str()) {
+      Some(&new_index) => new_index,
+      None => {
+        let parent_removed = old
+          .parent_index(*old_index)
+          .map(|parent_index| removed.contains(&parent_index))
+          .unwrap_or(false);
+        if !parent_removed {
+          changes.push(ResourceChange::NodeRemoved(path.to_owned()));
+        }
+        removed.insert(*old_index);
+        continue;
+      }
+    };
+
+    let old_attributes = old.value(*old_index).attributes();
+    let new_attributes = new.value(new_index).attributes();
+    let names: BTreeSet<&String> = old_attributes
+      .iter()
+      .chain(new_attributes.iter())
+      .map(|(name, _)| name)
+      .collect();
+    for name in names {
+      match (
+        old.attribute_value(*old_index, name),
+        new.attribute_value(new_index, name),
+      ) {
+        (Some(old_value), Some(new_value)) if old_value != new_value => {
+          changes.push(ResourceChange::AttributeChanged(
+            path.to_owned(),
+            name.to_owned(),
+            old_value.clone(),
+            new_value.clone(),
+          ))
+        }
+        (Some(old_value), None) => changes.push(ResourceChange::AttributeRemoved(
+          path.to_owned(),
+          name.to_owned(),
+          old_value.clone(),
+        )),
+        (None, Some(new_value)) => changes.push(ResourceChange::AttributeAdded(
+          path.to_owned(),
+          name.to_owned(),
+          new_value.clone(),
+        )),
+        _ => {}
+      }
+    }
+  }
+
+  let mut added: BTreeSet<Index> = BTreeSet::new();
+  for (path, new_index) in &new_paths {
+    if old_indexes.contains_key(path.as_str()) {
+      continue;
+    }
+    let parent_added = new
+      .parent_index(*new_index)
+      .map(|parent_index| added.contains(&parent_index))
+      .unwrap_or(false);
+    if !parent_added {
+      changes.push(ResourceChange::NodeAdded(path.to_owned()));
+    }
+    added.insert(*new_index);
+  }
+
+  changes
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    resource::node::{attribute::Attribute, data::Data},
+    util::arena::ArenaWriter,
+  };
+
+  use super::*;
+
+  fn character(resource: &mut Resource, parent: Index, name: &str, level: i32) -> Index {
+    let index = resource.alloc(Data::new("Character".to_string()), Some(parent));
+    let attributes = resource.value_mut(index).attributes_mut();
+    attributes.insert(
+      "Name".to_string(),
+      Attribute::new_value(Value::LsString(name.to_string())),
+    );
+    attributes.insert("Level".to_string(), Attribute::new_value(Value::Int(level)));
+    index
+  }
+
+  #[test]
+  fn test_node_paths() {
+    let mut resource = Resource::new();
+    let root = resource.alloc(Data::new("root".to_string()), None);
+    character(&mut resource, root, "Ifan", 1);
+    let lohse = character(&mut resource, root, "Lohse", 1);
+    resource.alloc(Data::new("Stats".to_string()), Some(lohse));
+
+    for (path, index) in node_paths(&resource) {
+      assert_eq!(path, resource.full_path(index));
+    }
+  }
+
+  #[test]
+  fn test_diff_resources() {
+    let mut old = Resource::new();
+    let root = old.alloc(Data::new("root".to_string()), None);
+    character(&mut old, root, "Ifan", 1);
+    let lohse = character(&mut old, root, "Lohse", 1);
+    old.alloc(Data::new("Stats".to_string()), Some(lohse));
+
+    let mut new = Resource::new();
+    let root = new.alloc(Data::new("root".to_string()), None);
+    let ifan = character(&mut new, root, "Ifan", 2);
+    new
+      .value_mut(ifan)
+      .attributes_mut()
+      .insert("Gold".to_string(), Attribute::new_value(Value::Int(10)));
+    let lohse = character(&mut new, root, "Lohse", 1);
+    new.value_mut(lohse).attributes_mut().remove("Level");
+    let items = new.alloc(Data::new("Items".to_string()), Some(root));
+    new.alloc(Data::new("Item".to_string()), Some(items));
+
+    assert_eq!(
+      diff_resources(&old, &new),
+      vec![
+        ResourceChange::AttributeAdded(
+          "/root/Character[0]".to_string(),
+          "Gold".to_string(),
+          Value::Int(10)
+        ),
+        ResourceChange::AttributeChanged(
+          "/root/Character[0]".to_string(),
+          "Level".to_string(),
+          Value::Int(1),
+          Value::Int(2)
+        ),
+        ResourceChange::AttributeRemoved(
+          "/root/Character[1]".to_string(),
+          "Level".to_string(),
+          Value::Int(1)
+        ),
+        ResourceChange::NodeRemoved("/root/Character[1]/Stats".to_string()),
+        ResourceChange::NodeAdded("/root/Items".to_string()),
+      ]
+    );
+    assert!(diff_resources(&old, &old).is_empty());
+  }
+}
@@ -1,6 +1,8 @@
 pub mod read;
 
-#[derive(Default, Debug, Clone, Copy)]
+use serde::Serialize;
+
+#[derive(Serialize, Default, Debug, Clone, Copy)]
 pub struct Metadata {
   timestamp: u64,
   major_version: u32,
@@ -14,6 +16,38 @@ impl Metadata {
     Default::default()
   }
 
+  // Unpacks an engine version like it's stored in LSF headers (32 bits before v5, 64 bits since)
+  pub fn from_packed_version(packed_version: i64, is_64_bit: bool) -> Self {
+    let mut metadata = Self::new();
+    if is_64_bit {
+      metadata.set_major_version(((packed_version >> 55) & 0x7f) as u32);
+      metadata.set_minor_version(((packed_version >> 47) & 0xff) as u32);
+      metadata.set_revision(((packed_version >> 31) & 0xffff) as u32);
+      metadata.set_build_number((packed_version & 0x7fffffff) as u32);
+    } else {
+      metadata.set_major_version(((packed_version >> 28) & 0x0f) as u32);
+      metadata.set_minor_version(((packed_version >> 24) & 0x0f) as u32);
+      metadata.set_revision(((packed_version >> 16) & 0xff) as u32);
+      metadata.set_build_number((packed_version & 0xffff) as u32);
+    }
+    metadata
+  }
+
+  // Packs the engine version for LSF headers, the counterpart of from_packed_version
+  pub fn packed_version(&self, is_64_bit: bool) -> i64 {
+    if is_64_bit {
+      ((self.major_version as i64 & 0x7f) << 55)
+        | ((self.minor_version as i64 & 0xff) << 47)
+        | ((self.revision as i64 & 0xffff) << 31)
+        | (self.build_number as i64 & 0x7fffffff)
+    } else {
+      ((self.major_version as i64 & 0x0f) << 28)
+        | ((self.minor_version as i64 & 0x0f) << 24)
+        | ((self.revision as i64 & 0xff) << 16)
+        | (self.build_number as i64 & 0xffff)
+    }
+  }
+
   pub fn timestamp(&self) -> u64 {
     self.timestamp
   }
@@ -1,30 +1,71 @@
+pub mod diff;
 pub mod metadata;
 pub mod node;
 pub mod reader;
 pub mod value;
+pub mod visitor;
 pub mod writer;
 
-use std::fmt::Debug;
+use std::{collections::BTreeMap, fmt::Debug};
 
 use serde::{ser::SerializeStruct, Serialize};
 
-use crate::util::arena::{Arena, ArenaReader};
+use crate::{
+  lsf::layout::Layout,
+  util::arena::{Arena, ArenaReader, Index},
+};
 
-use self::node::data::Data;
+use self::{
+  metadata::Metadata,
+  node::{
+    attribute::Attribute,
+    data::{Data, Kind},
+  },
+};
 
 pub struct Resource {
   arena: Arena<Data>,
+  metadata: Metadata,
+  lsf_layout: Option<Layout>,
 }
 
 impl Resource {
   pub fn new() -> Self {
     Self {
       arena: Arena::new(),
+      metadata: Metadata::new(),
+      lsf_layout: None,
     }
   }
 
   pub fn new_with_arena(arena: Arena<Data>) -> Self {
-    Self { arena }
+    Self {
+      arena,
+      metadata: Metadata::new(),
+      lsf_layout: None,
+    }
+  }
+
+  // Engine version the resource was written with
+  pub fn metadata(&self) -> &Metadata {
+    &self.metadata
+  }
+
+  pub fn set_metadata(&mut self, metadata: Metadata) {
+    self.metadata = metadata;
+  }
+
+  // Layout of the LSF file the resource was read from, reused when it's written as LSF again
+  pub fn lsf_layout(&self) -> Option<&Layout> {
+    self.lsf_layout.as_ref()
+  }
+
+  pub fn lsf_layout_mut(&mut self) -> Option<&mut Layout> {
+    self.lsf_layout.as_mut()
+  }
+
+  pub fn set_lsf_layout(&mut self, lsf_layout: Option<Layout>) {
+    self.lsf_layout = lsf_layout;
   }
 }
 
@@ -39,12 +80,54 @@ impl Debug for Resource {
 
 impl Serialize for Resource {
   fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let roots: Vec<SerializeNode> = self
+      .root_indexes()
+      .iter()
+      .map(|&index| SerializeNode {
+        resource: self,
+        index,
+      })
+      .collect();
     let mut resource = serializer.serialize_struct("Resource", 2)?;
-    resource.serialize_field("roots", &self.root_indexes())?;
+    resource.serialize_field("metadata", &self.metadata)?;
+    resource.serialize_field("roots", &roots)?;
     resource.end()
   }
 }
 
+// Serializes a node with its attributes (ordered by name, so the output is stable) and all of its children
+struct SerializeNode<'a> {
+  resource: &'a Resource,
+  index: Index,
+}
+
+impl Serialize for SerializeNode<'_> {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let data = self.resource.value(self.index);
+    let region = match data.kind() {
+      Kind::Region(region) => Some(region),
+      Kind::Element => None,
+    };
+    let attributes: BTreeMap<&String, &Attribute> = data.attributes().iter().collect();
+    let children: Vec<SerializeNode> = self
+      .resource
+      .child_indexes(self.index)
+      .iter()
+      .map(|&index| SerializeNode {
+        resource: self.resource,
+        index,
+      })
+      .collect();
+
+    let mut node = serializer.serialize_struct("Node", 4)?;
+    node.serialize_field("name", data.name())?;
+    node.serialize_field("region", &region)?;
+    node.serialize_field("attributes", &attributes)?;
+    node.serialize_field("children", &children)?;
+    node.end()
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::util::arena::ArenaWriter;
@@ -11,6 +11,7 @@ pub struct Data {
   kind: Kind,
   name: String,
   attributes: AttributeMap,
+  key: Option<String>,
 }
 
 impl Data {
@@ -19,6 +20,7 @@ impl Data {
       kind: Kind::Element,
       name,
       attributes: AttributeMap::new(),
+      key: None,
     }
   }
 
@@ -27,6 +29,7 @@ impl Data {
       kind: Kind::Element,
       name,
       attributes,
+      key: None,
     }
   }
 
@@ -35,6 +38,7 @@ impl Data {
       kind: Kind::Region(region),
       name,
       attributes: AttributeMap::new(),
+      key: None,
     }
   }
 
@@ -61,4 +65,13 @@ impl Data {
   pub fn attributes_mut(&mut self) -> &mut AttributeMap {
     &mut self.attributes
   }
+
+  // Name of the attribute that identifies the node among its siblings, LSF v6 and newer
+  pub fn key(&self) -> Option<&str> {
+    self.key.as_deref()
+  }
+
+  pub fn set_key(&mut self, key: Option<String>) {
+    self.key = key;
+  }
 }
@@ -33,24 +33,16 @@ impl ArenaReader<Data> for Resource {
 }
 
 pub trait ResourceReader: ArenaReader<Data> {
-  fn matches(&self, index: Index, selector: &Selector) -> bool;
-  fn find(&self, selector: &Selector) -> Vec<Index>;
-  fn full_path(&self, index: Index) -> String;
-  fn attribute(&self, index: Index, name: &str) -> Option<&Attribute>;
-  fn attribute_value(&self, index: Index, name: &str) -> Option<&Value>;
-  fn resolve(&self, index: Index, path: &str) -> Option<Index>;
-  fn resolve_attribute(&self, index: Index, path: &str) -> Option<&Attribute>;
-  fn resolve_attribute_value(&self, index: Index, path: &str) -> Option<&Value>;
-}
-
-impl ResourceReader for Resource {
+  // Name of a node, readers that decode nodes lazily can return it without the attributes
+  fn node_name(&self, index: Index) -> &str {
+    self.value(index).name()
+  }
   fn matches(&self, index: Index, selector: &Selector) -> bool {
-    let value = self.value(index);
     match selector {
       Selector::Any => true,
-      Selector::Name(name) => value.name() == *name,
+      Selector::Name(name) => self.node_name(index) == *name,
       Selector::AttributeEquals(name, attr_value) => {
-        value.attributes().get(name).map(|a| a.value()) == Some(attr_value)
+        self.attribute_value(index, name) == Some(attr_value)
       }
       Selector::AnyChildMatches(selector) => self
         .child_indexes(index)
@@ -84,11 +76,11 @@ impl ResourceReader for Resource {
         let similar_children: Vec<&Index> = self
           .child_indexes(parent_index)
           .iter()
-          .filter(|&&child_index| self.value(index).name() == self.value(child_index).name())
+          .filter(|&&child_index| self.node_name(index) == self.node_name(child_index))
           .collect();
         let similar_children_count = similar_children.len();
         match similar_children_count {
-          1 => format!("{}/{}", parent_path, self.value(index).name()),
+          1 => format!("{}/{}", parent_path, self.node_name(index)),
           _ => {
             let own_parent_index = similar_children
               .iter()
@@ -97,13 +89,13 @@ impl ResourceReader for Resource {
             format!(
               "{}/{}[{}]",
               parent_path,
-              self.value(index).name(),
+              self.node_name(index),
               own_parent_index
             )
           }
         }
       }
-      None => format!("/{}", self.value(index).name()),
+      None => format!("/{}", self.node_name(index)),
     }
   }
 
@@ -140,7 +132,7 @@ impl ResourceReader for Resource {
       }
       let mut found = false;
       for child_index in self.child_indexes(index) {
-        if self.value(*child_index).name() == part {
+        if self.node_name(*child_index) == part {
           found = true;
           index = *child_index;
           break;
@@ -153,7 +145,7 @@ impl ResourceReader for Resource {
         let similar_children: Vec<&Index> = self
           .child_indexes(index)
           .iter()
-          .filter(|&&child_index| self.value(index).name() == self.value(child_index).name())
+          .filter(|&&child_index| self.node_name(index) == self.node_name(child_index))
           .collect();
         if similar_children.len() <= index_offset {
           return None;
@@ -191,6 +183,8 @@ impl ResourceReader for Resource {
   }
 }
 
+impl ResourceReader for Resource {}
+
 #[cfg(test)]
 mod tests {
   use crate::{resource::node::attribute::AttributeMap, util::arena::ArenaWriter};
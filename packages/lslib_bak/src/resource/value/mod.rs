@@ -116,8 +116,46 @@ impl Value {
   }
 }
 
-impl Into<u32> for Value {
-  fn into(self) -> u32 {
+// Type names used in LSX files, indexed by type ID
+const TYPE_NAMES: [&str; 34] = [
+  "None",
+  "uint8",
+  "int16",
+  "uint16",
+  "int32",
+  "uint32",
+  "float",
+  "double",
+  "ivec2",
+  "ivec3",
+  "ivec4",
+  "fvec2",
+  "fvec3",
+  "fvec4",
+  "mat2x2",
+  "mat3x3",
+  "mat3x4",
+  "mat4x3",
+  "mat4x4",
+  "bool",
+  "string",
+  "path",
+  "FixedString",
+  "LSString",
+  "uint64",
+  "ScratchBuffer",
+  "old_int64",
+  "int8",
+  "TranslatedString",
+  "WString",
+  "LSWString",
+  "guid",
+  "int64",
+  "TranslatedFSString",
+];
+
+impl Value {
+  pub fn type_id(&self) -> u32 {
     match self {
       Self::None => 0,
       Self::Byte(_) => 1,
@@ -155,6 +193,27 @@ impl Into<u32> for Value {
       Self::TranslatedFsString { .. } => 33,
     }
   }
+
+  pub fn type_name(&self) -> &'static str {
+    TYPE_NAMES[self.type_id() as usize]
+  }
+
+  // Accepts type names as well as the numeric type IDs older LSX files use
+  pub fn type_id_from_name(type_name: &str) -> Option<u32> {
+    match TYPE_NAMES.iter().position(|name| *name == type_name) {
+      Some(type_id) => Some(type_id as u32),
+      None => type_name
+        .parse::<u32>()
+        .ok()
+        .filter(|type_id| (*type_id as usize) < TYPE_NAMES.len()),
+    }
+  }
+}
+
+impl From<Value> for u32 {
+  fn from(value: Value) -> Self {
+    value.type_id()
+  }
 }
 
 impl Debug for Value {
@@ -0,0 +1,128 @@
+use super::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+  Continue,
+  // Skips the remaining attributes and the children of the current node
+  SkipSubtree,
+  // Ends the traversal
+  Stop,
+}
+
+// Callbacks for traversing a resource while it's read, without building the tree. Nodes are
+// visited depth first, the attributes of a node before its children. exit_node is called for every
+// entered node, also when its subtree was skipped, unless the traversal was stopped.
+pub trait ResourceVisitor {
+  fn enter_node(&mut self, _name: &str) -> Visit {
+    Visit::Continue
+  }
+
+  fn attribute(&mut self, _name: &str, _value: &Value) -> Visit {
+    Visit::Continue
+  }
+
+  fn exit_node(&mut self, _name: &str) -> Visit {
+    Visit::Continue
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use crate::{
+    lsb::read::LsbReadExt,
+    lsf::{header::Version, read::LsfReadExt, write::LsfWriteExt},
+    resource::reader::ResourceReader,
+    util::arena::ArenaReader,
+  };
+
+  use super::*;
+
+  #[derive(Default)]
+  struct Recorder {
+    depth: usize,
+    entered: Vec<String>,
+    attribute_count: usize,
+    exited: usize,
+    skip: Option<String>,
+    stop_after: Option<usize>,
+  }
+
+  impl ResourceVisitor for Recorder {
+    fn enter_node(&mut self, name: &str) -> Visit {
+      self.depth += 1;
+      self.entered.push(name.to_owned());
+      match &self.skip {
+        Some(skip) if skip.as_str() == name => Visit::SkipSubtree,
+        _ => Visit::Continue,
+      }
+    }
+
+    fn attribute(&mut self, _name: &str, _value: &Value) -> Visit {
+      self.attribute_count += 1;
+      match self.stop_after {
+        Some(count) if count == self.attribute_count => Visit::Stop,
+        _ => Visit::Continue,
+      }
+    }
+
+    fn exit_node(&mut self, _name: &str) -> Visit {
+      self.depth -= 1;
+      self.exited += 1;
+      Visit::Continue
+    }
+  }
+
+  #[test]
+  fn test_visit() {
+    let lsb =
+      include_bytes!("../../../lsedit/examples/dos_ee/PlayerProfiles/TestProfile/profile.lsb");
+    let resource = Cursor::new(lsb).read_lsb_resource().unwrap();
+    let mut lsf = vec![];
+    lsf.write_lsf_resource(&resource, Version::V6).unwrap();
+    let names: Vec<_> = resource
+      .root_indexes()
+      .iter()
+      .flat_map(|&root_index| resource.recursive_iter(root_index))
+      .map(|index| resource.node_name(index).to_owned())
+      .collect();
+    let attribute_count: usize = (0..resource.size())
+      .map(|index| resource.value(index).attributes().iter().count())
+      .sum();
+    let visit = |recorder: &mut Recorder, from_lsb: bool| match from_lsb {
+      true => Cursor::new(lsb).visit_lsb_resource(recorder).unwrap(),
+      false => Cursor::new(&lsf).visit_lsf_resource(recorder).unwrap(),
+    };
+
+    for from_lsb in [true, false] {
+      let mut recorder = Recorder::default();
+      visit(&mut recorder, from_lsb);
+      assert_eq!(recorder.entered, names);
+      assert_eq!(recorder.attribute_count, attribute_count);
+      assert_eq!(recorder.exited, names.len());
+      assert_eq!(recorder.depth, 0);
+
+      // Skipped nodes are still exited, their children aren't entered
+      let skipped = resource.root_indexes()[0];
+      let mut recorder = Recorder {
+        skip: Some(resource.node_name(skipped).to_owned()),
+        ..Default::default()
+      };
+      visit(&mut recorder, from_lsb);
+      let skipped_count = resource.recursive_iter(skipped).count() - 1;
+      assert_eq!(recorder.entered.len(), names.len() - skipped_count);
+      assert_eq!(recorder.exited, recorder.entered.len());
+      assert_eq!(recorder.depth, 0);
+
+      let mut recorder = Recorder {
+        stop_after: Some(1),
+        ..Default::default()
+      };
+      visit(&mut recorder, from_lsb);
+      assert_eq!(recorder.attribute_count, 1);
+      assert_eq!(recorder.exited, 0);
+      assert!(recorder.depth > 0);
+    }
+  }
+}
@@ -51,16 +51,17 @@ impl<T> Arena<T> {
   }
 }
 
+// Pre-order iterator over the indexes of a subtree, for any reader of the tree structure
 pub struct RecursiveIter<'a, V> {
-  arena: &'a Arena<V>,
+  reader: &'a dyn ArenaReader<V>,
   stack: Vec<Index>,
 }
 
 impl<'a, V> RecursiveIter<'a, V> {
-  pub fn new(arena: &'a Arena<V>, index: Index) -> Self {
-    let mut stack = Vec::with_capacity(arena.nodes.len());
+  pub fn new(reader: &'a dyn ArenaReader<V>, index: Index) -> Self {
+    let mut stack = Vec::with_capacity(reader.size());
     stack.push(index);
-    Self { arena, stack }
+    Self { reader, stack }
   }
 }
 
@@ -68,13 +69,11 @@ impl<'a, V> Iterator for RecursiveIter<'a, V> {
   type Item = Index;
 
   fn next(&mut self) -> Option<Self::Item> {
-    if let Some(index) = self.stack.pop() {
-      let node = &self.arena.nodes[index];
-      self.stack.extend(node.child_indexes.iter().rev());
-      Some(node.index)
-    } else {
-      None
-    }
+    let index = self.stack.pop()?;
+    self
+      .stack
+      .extend(self.reader.child_indexes(index).iter().rev());
+    Some(index)
   }
 }
 